@@ -0,0 +1,137 @@
+//! Throughput of checking auth tokens, as `/verify-token` and every authenticated route do,
+//! with many requests at once.
+//!
+//! `locked_sync_connection` is how the Redis stores used to talk to Redis: one blocking connection
+//! behind a lock, so every check waited for the one before it and blocked a tokio worker meanwhile.
+//! `connection_manager` is the multiplexed connection [RedisBannedTokenStore] uses now.
+//!
+//! Needs Redis at REDIS_HOST_NAME and Postgres at DATABASE_URL, like the service itself:
+//!
+//! ```bash
+//! cargo bench --bench verify_token
+//! ```
+use std::sync::Arc;
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use redis::Commands;
+use secrecy::{ExposeSecret, Secret};
+use tokio::runtime::Runtime;
+use tokio::sync::RwLock;
+
+use auth_service::domain::{BannedTokenStore, Email};
+use auth_service::services::{BannedTokenStoreError, RedisBannedTokenStore};
+use auth_service::utils::auth::{generate_auth_cookie, validate_token};
+use auth_service::utils::constants::{DATABASE_URL, REDIS_HOST_NAME};
+use auth_service::{get_postgres_pool, get_redis_client, get_redis_connection};
+
+const CONCURRENT_REQUESTS: [usize; 3] = [1, 16, 64];
+
+/// The token checks of the old Redis store, for comparison.
+#[derive(Clone)]
+struct LockedSyncConnectionStore {
+    conn: Arc<RwLock<redis::Connection>>,
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for LockedSyncConnectionStore {
+    async fn add_banned_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        self.conn.write().await
+            .set(format!("banned_token:{}", jti), true)
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    async fn claim_token(&mut self, jti: String) -> Result<bool, BannedTokenStoreError> {
+        self.conn.write().await
+            .set_nx(format!("banned_token:{}", jti), true)
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    async fn is_banned(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        self.conn.write().await
+            .exists(format!("banned_token:{}", jti))
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        self.conn.write().await
+            .set(format!("tokens_revoked_at_ms:{}", email.as_ref().expose_secret()), Utc::now().timestamp_millis())
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    async fn get_tokens_revoked_at(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError> {
+        let revoked_at: Option<usize> = self.conn.write().await
+            .get(format!("tokens_revoked_at_ms:{}", email.as_ref().expose_secret()))
+            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(revoked_at.filter(|&revoked_at| revoked_at != 0))
+    }
+}
+
+/// Checks `concurrency` tokens at once, each from its own task like a request would be.
+async fn verify_tokens<S: BannedTokenStore>(store: &Arc<RwLock<S>>, token: &Arc<String>, concurrency: usize) {
+    let checks: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let store = store.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                validate_token(&token, store.read().await)
+                    .await
+                    .expect("token should be valid");
+            })
+        })
+        .collect();
+
+    for check in checks {
+        check.await.expect("token check panicked");
+    }
+}
+
+fn verify_token(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("Failed to start tokio runtime");
+
+    let email = Email::parse(Secret::new("benchmark@example.com".to_string())).unwrap();
+    let token = Arc::new(generate_auth_cookie(&email, None).unwrap().value().to_string());
+
+    let (connection_manager, locked_sync_connection) = runtime.block_on(async {
+        let pg_pool = get_postgres_pool(&DATABASE_URL)
+            .await
+            .expect("Failed to create Postgres connection pool");
+        sqlx::migrate!()
+            .run(&pg_pool)
+            .await
+            .expect("Failed to run migrations");
+
+        let redis_conn = get_redis_connection(REDIS_HOST_NAME.to_owned())
+            .await
+            .expect("Failed to get Redis connection");
+        let store = RedisBannedTokenStore::new(redis_conn, pg_pool);
+        // Caches that the user was never revoked, so neither store goes to Postgres.
+        store.get_tokens_revoked_at(&email).await.unwrap();
+
+        let sync_conn = get_redis_client(REDIS_HOST_NAME.to_owned())
+            .expect("Failed to get Redis client")
+            .get_connection()
+            .expect("Failed to get Redis connection");
+
+        (
+            Arc::new(RwLock::new(store)),
+            Arc::new(RwLock::new(LockedSyncConnectionStore { conn: Arc::new(RwLock::new(sync_conn)) })),
+        )
+    });
+
+    let mut group = c.benchmark_group("verify_token");
+    for concurrency in CONCURRENT_REQUESTS {
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(BenchmarkId::new("locked_sync_connection", concurrency), &concurrency, |b, &concurrency| {
+            b.to_async(&runtime).iter(|| verify_tokens(&locked_sync_connection, &token, concurrency));
+        });
+        group.bench_with_input(BenchmarkId::new("connection_manager", concurrency), &concurrency, |b, &concurrency| {
+            b.to_async(&runtime).iter(|| verify_tokens(&connection_manager, &token, concurrency));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify_token);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS password_reset_tokens(
+   token TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   issued_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS password_reset_tokens_email_idx ON password_reset_tokens(email);
@@ -0,0 +1,10 @@
+-- Accounts created before email verification existed are treated as verified.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ALTER COLUMN verified SET DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS email_verification_tokens(
+   token TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL UNIQUE REFERENCES users(email) ON DELETE CASCADE,
+   issued_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
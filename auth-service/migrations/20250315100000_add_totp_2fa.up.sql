@@ -0,0 +1,10 @@
+-- `requires_2fa` only knew about emailed codes, so it becomes a method column.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'none'
+    CHECK (two_fa_method IN ('none', 'email', 'totp'));
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+ALTER TABLE users DROP COLUMN IF EXISTS requires_2fa;
+
+-- AES-256-GCM encrypted base32 TOTP secret, only set once the user starts enrollment.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret TEXT;
+-- The time step of the last TOTP code accepted, so no code is accepted twice.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS passkey_login_challenges;
+DROP TABLE IF EXISTS passkey_registration_challenges;
+DROP TABLE IF EXISTS passkey_credentials;
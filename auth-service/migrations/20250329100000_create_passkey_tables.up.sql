@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS passkey_credentials(
+   credential_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   public_key BYTEA NOT NULL,
+   sign_count BIGINT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS passkey_credentials_email_idx ON passkey_credentials(email);
+
+-- The pending registration of each user.
+CREATE TABLE IF NOT EXISTS passkey_registration_challenges(
+   email TEXT NOT NULL PRIMARY KEY,
+   challenge TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS passkey_registration_challenges_expires_at_idx ON passkey_registration_challenges(expires_at);
+
+-- Login challenges are keyed by an ID handed to the client, not by an email,
+-- so nobody can replace the challenge of someone else's login.
+CREATE TABLE IF NOT EXISTS passkey_login_challenges(
+   challenge_id TEXT NOT NULL PRIMARY KEY,
+   challenge TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS passkey_login_challenges_expires_at_idx ON passkey_login_challenges(expires_at);
@@ -0,0 +1,13 @@
+-- Rotated tokens are kept until they expire so replaying one can be detected.
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   family_id TEXT NOT NULL,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   expires_at TIMESTAMPTZ NOT NULL,
+   rotated BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens(family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_email_idx ON refresh_tokens(email);
+-- Expired tokens are purged periodically.
+CREATE INDEX IF NOT EXISTS refresh_tokens_expires_at_idx ON refresh_tokens(expires_at);
@@ -0,0 +1,6 @@
+-- Tokens issued to the user at or before `revoked_at_ms` (unix milliseconds) are rejected.
+-- Redis caches these, this table keeps them across Redis restarts.
+CREATE TABLE IF NOT EXISTS token_revocations(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   revoked_at_ms BIGINT NOT NULL
+);
@@ -0,0 +1,12 @@
+-- One row per login. Refresh token families use the session ID as their family ID.
+CREATE TABLE IF NOT EXISTS sessions(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   device_label TEXT NOT NULL,
+   user_agent TEXT,
+   ip_address TEXT,
+   created_at TIMESTAMPTZ NOT NULL,
+   last_seen_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
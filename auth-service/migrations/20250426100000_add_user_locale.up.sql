@@ -0,0 +1,2 @@
+-- Language the user picked at signup for their emails. NULL means use the request's Accept-Language.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS locale TEXT;
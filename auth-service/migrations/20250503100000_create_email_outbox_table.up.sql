@@ -0,0 +1,18 @@
+-- Emails waiting to be delivered, and how each delivery went.
+-- The text and HTML are emptied once an email is sent or dead-lettered.
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id TEXT NOT NULL PRIMARY KEY,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   text_content TEXT NOT NULL,
+   html_content TEXT NOT NULL,
+   status TEXT NOT NULL,
+   attempts INTEGER NOT NULL DEFAULT 0,
+   last_error TEXT,
+   created_at TIMESTAMPTZ NOT NULL,
+   next_attempt_at TIMESTAMPTZ NOT NULL,
+   sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx ON email_outbox(next_attempt_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS email_outbox_recipient_idx ON email_outbox(recipient);
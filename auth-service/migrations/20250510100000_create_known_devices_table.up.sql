@@ -0,0 +1,9 @@
+-- Devices each user has logged in from, by browser and OS, and network of the IP address.
+CREATE TABLE IF NOT EXISTS known_devices(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   device_label TEXT NOT NULL,
+   network TEXT NOT NULL,
+   first_seen_at TIMESTAMPTZ NOT NULL,
+   last_seen_at TIMESTAMPTZ NOT NULL,
+   PRIMARY KEY (email, device_label, network)
+);
@@ -0,0 +1,8 @@
+-- Banned token and session IDs, for when Redis isn't used.
+-- Rows are useless once the token would have expired anyway, and are purged periodically.
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   jti TEXT NOT NULL PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens(expires_at);
@@ -0,0 +1,13 @@
+-- The pending 2FA login attempt of each user, for when Redis isn't used.
+-- Expired attempts are ignored, and purged periodically.
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   email TEXT NOT NULL PRIMARY KEY,
+   login_attempt_id TEXT NOT NULL,
+   code TEXT NOT NULL,
+   failed_attempts INTEGER NOT NULL,
+   resends INTEGER NOT NULL,
+   sent_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes(expires_at);
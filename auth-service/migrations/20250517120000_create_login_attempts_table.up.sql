@@ -0,0 +1,10 @@
+-- Failed password logins per email or IP address, for when Redis isn't used.
+-- Counts are ignored once they expire, and purged periodically.
+CREATE TABLE IF NOT EXISTS login_attempts(
+   attempt_key TEXT NOT NULL PRIMARY KEY,
+   failures INTEGER NOT NULL,
+   last_failure_at TIMESTAMPTZ NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS login_attempts_expires_at_idx ON login_attempts(expires_at);
@@ -1,6 +1,6 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use tokio::sync::{Notify, RwLock};
+use crate::domain::{BannedTokenStore, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TwoFACodeStore, UserStore};
 
 /// The `AppState` struct holds the application state.
 /// It contains a reference to the user store.
@@ -10,8 +10,8 @@ use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
 /// specify the concrete type of the user store at compile time.*
 ///
 /// - This implementation also adds a `Clone` bound to the `T` type parameter which allows us to
-/// wrap the `UserStore` in an `Arc` smart pointer with a `RwLock` to allow for concurrent access.\
-/// \
+///   wrap the `UserStore` in an `Arc` smart pointer with a `RwLock` to allow for concurrent access.
+///
 /// This is in addition to the `UserStore` trait bound. \
 /// which already implements `Sized`, `Send`, and `Sync` \
 /// \
@@ -19,29 +19,51 @@ use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
 ///
 /// ###### Pros:
 /// - The compiler can optimize the code better due
-/// to the concrete type being known at compile time.
+///   to the concrete type being known at compile time.
 ///
 /// ###### Cons:
 /// - It requires more boilerplate code, which could be a bit cumbersome later
-/// if we have a lot of different types that implement the `UserStore` trait with different
-/// trait bound requirements. \
+///   if we have a lot of different types that implement the `UserStore` trait with different
+///   trait bound requirements.
+///
 /// **see: [Application::build](crate::Application::build)**
 ///
 #[derive(Clone)]
-pub struct AppState<T: UserStore, U: BannedTokenStore, V: TwoFACodeStore, W: EmailClient> {
+pub struct AppState<T: UserStore, U: BannedTokenStore, V: TwoFACodeStore, W: EmailClient, X: PasswordResetTokenStore, Y: EmailVerificationTokenStore, Z: RecoveryCodeStore, A: PasskeyStore, B: RefreshTokenStore, C: SessionStore, D: LoginAttemptStore, E: EmailOutboxStore, F: KnownDeviceStore> {
     pub user_store: Arc<RwLock<T>>,
     pub banned_token_store: Arc<RwLock<U>>,
     pub two_fa_code_store: Arc<RwLock<V>>,
     pub email_client: Arc<RwLock<W>>,
+    pub password_reset_token_store: Arc<RwLock<X>>,
+    pub email_verification_token_store: Arc<RwLock<Y>>,
+    pub recovery_code_store: Arc<RwLock<Z>>,
+    pub passkey_store: Arc<RwLock<A>>,
+    pub refresh_token_store: Arc<RwLock<B>>,
+    pub session_store: Arc<RwLock<C>>,
+    pub login_attempt_store: Arc<RwLock<D>>,
+    pub email_outbox: Arc<RwLock<E>>,
+    pub known_device_store: Arc<RwLock<F>>,
+    /// Wakes the [EmailOutboxWorker](crate::services::EmailOutboxWorker) when an email is queued.
+    pub email_outbox_wakeup: Arc<Notify>,
 }
 
-impl <T, U, V, W>AppState<T, U, V, W>
+impl <T, U, V, W, X, Y, Z, A, B, C, D, E, F>AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
       W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore,
 {
-    pub fn new(user_store: Arc<RwLock<T>>, banned_token_store: Arc<RwLock<U>>, two_fa_code_store: Arc<RwLock<V>>, email_client: Arc<RwLock<W>>) -> Self {
-        Self { user_store, banned_token_store, two_fa_code_store, email_client }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(user_store: Arc<RwLock<T>>, banned_token_store: Arc<RwLock<U>>, two_fa_code_store: Arc<RwLock<V>>, email_client: Arc<RwLock<W>>, password_reset_token_store: Arc<RwLock<X>>, email_verification_token_store: Arc<RwLock<Y>>, recovery_code_store: Arc<RwLock<Z>>, passkey_store: Arc<RwLock<A>>, refresh_token_store: Arc<RwLock<B>>, session_store: Arc<RwLock<C>>, login_attempt_store: Arc<RwLock<D>>, email_outbox: Arc<RwLock<E>>, known_device_store: Arc<RwLock<F>>) -> Self {
+        Self { user_store, banned_token_store, two_fa_code_store, email_client, password_reset_token_store, email_verification_token_store, recovery_code_store, passkey_store, refresh_token_store, session_store, login_attempt_store, email_outbox, known_device_store, email_outbox_wakeup: Arc::new(Notify::new()) }
     }
 }
\ No newline at end of file
@@ -1,9 +1,13 @@
 use std::fmt::{Debug, Display};
 use std::str::FromStr;
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Context, eyre, Result};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use crate::services::BannedTokenStoreError;
-use super::{Email, Password, User};
+use super::{DeviceFingerprint, DeviceSighting, Email, LoginAttemptKey, LoginFailures, PasskeyChallenge, PasskeyChallengeId, PasskeyCredential, Password, QueuedEmail, QueuedEmailId, Session, SessionId, TotpSecret, TwoFAMethod, User};
 
 #[derive(Debug, Error)]
 pub enum UserStoreError {
@@ -21,14 +25,14 @@ pub enum UserStoreError {
 
 impl PartialEq for UserStoreError {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::UserAlreadyExists, Self::UserAlreadyExists) => true,
-            (Self::UserNotFound, Self::UserNotFound) => true,
-            (Self::InvalidCredentials, Self::InvalidCredentials) => true,
-            (Self::UnexpectedError(_), Self::UnexpectedError(_)) => true,
-            (Self::TokenBanned, Self::TokenBanned) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (Self::UserAlreadyExists, Self::UserAlreadyExists)
+                | (Self::UserNotFound, Self::UserNotFound)
+                | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+                | (Self::TokenBanned, Self::TokenBanned)
+        )
     }
 }
 
@@ -42,11 +46,170 @@ pub enum TwoFACodeStoreError {
 
 impl PartialEq for TwoFACodeStoreError {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound) => true,
-            (Self::UnexpectedError(_), Self::UnexpectedError(_)) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EmailVerificationTokenStoreError {
+    #[error("Email verification token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for EmailVerificationTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyStoreError {
+    #[error("Passkey credential already exists")]
+    CredentialAlreadyExists,
+    #[error("Passkey credential not found")]
+    CredentialNotFound,
+    #[error("Passkey challenge not found")]
+    ChallengeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for PasskeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CredentialAlreadyExists, Self::CredentialAlreadyExists)
+                | (Self::CredentialNotFound, Self::CredentialNotFound)
+                | (Self::ChallengeNotFound, Self::ChallengeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Refresh token was already used")]
+    TokenReused,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::TokenReused, Self::TokenReused)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoginAttemptStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for LoginAttemptStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!((self, other), (Self::UnexpectedError(_), Self::UnexpectedError(_)))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EmailOutboxStoreError {
+    #[error("Email not found")]
+    EmailNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for EmailOutboxStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::EmailNotFound, Self::EmailNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum KnownDeviceStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] color_eyre::eyre::Report),
+}
+
+impl PartialEq for KnownDeviceStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!((self, other), (Self::UnexpectedError(_), Self::UnexpectedError(_)))
     }
 }
 
@@ -103,7 +266,7 @@ where
     Self: Sized + Send + Sync + Clone + 'static,
 {
     pub fn parse(code: String) -> Result<Self> {
-        if code.len() == 6 && code.chars().all(|c| c.is_digit(10)) {
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
             Ok(Self(code))
         } else {
             Err(eyre!("Invalid 2FA code"))
@@ -117,6 +280,179 @@ impl AsRef<str> for TwoFACode {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordResetToken(String);
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl PasswordResetToken
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    pub fn parse(token: String) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid password reset token"))
+        }
+    }
+}
+
+impl FromDbString for PasswordResetToken {
+    fn from_db_string(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmailVerificationToken(String);
+
+impl Default for EmailVerificationToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl EmailVerificationToken
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    pub fn parse(token: String) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid email verification token"))
+        }
+    }
+}
+
+impl FromDbString for EmailVerificationToken {
+    fn from_db_string(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl AsRef<str> for EmailVerificationToken {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+
+/// One-time code that stands in for a [TwoFACode] when the user has lost their 2FA device.
+///
+/// Codes look like `k3x9q-7mpa2` and are matched case-insensitively.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| char::from(rng.sample(Alphanumeric)).to_ascii_lowercase())
+                .collect()
+        };
+        Self(format!("{}-{}", group(), group()))
+    }
+}
+
+impl RecoveryCode
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    pub fn parse(code: String) -> Result<Self> {
+        let code = code.trim().to_ascii_lowercase();
+        let is_valid = match code.split_once('-') {
+            Some((first, second)) => [first, second].iter().all(|group| {
+                group.len() == RECOVERY_CODE_GROUP_LENGTH
+                    && group.chars().all(|c| c.is_ascii_alphanumeric())
+            }),
+            None => false,
+        };
+
+        if is_valid {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+
+    /// Codes are stored hashed so a leaked table can't be used to log in.
+    /// A fast unsalted hash is enough here since the codes are random rather than user chosen,
+    /// and it lets stores look a code up directly.
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Opaque token traded in at `/refresh-token` for a new access token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshToken(String);
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl RefreshToken
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    pub fn parse(token: String) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+
+    /// Stores only keep the hash, so a leaked table can't be used to refresh sessions.
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+const RANDOM_TOKEN_LENGTH: usize = 32;
+
+// Tokens sent by email stand in for a password or an inbox check, so they need
+// to be a lot harder to guess than a six digit 2FA code.
+fn generate_random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(RANDOM_TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+fn is_valid_random_token(token: &str) -> bool {
+    token.len() == RANDOM_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
 pub trait FromDbString {
     fn from_db_string(s: &str) -> Self;
 }
@@ -129,6 +465,15 @@ where
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError>;
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    /// Stores the secret of a TOTP enrollment, replacing any previous one.
+    /// It only takes effect once the method is switched with [set_two_fa_method](UserStore::set_two_fa_method).
+    async fn set_totp_secret(&mut self, email: &Email, secret: TotpSecret) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError>;
+    /// Records that a TOTP code for the time `step` was accepted, unless one for that step or a later one already was.
+    /// Returns `false` then, since each code must only be accepted once (RFC 6238, section 5.2).
+    async fn accept_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError>;
 }
 
 #[async_trait::async_trait]
@@ -136,15 +481,68 @@ pub trait BannedTokenStore
 where
     Self: Sized + Send + Sync + Clone + 'static,
 {
-    async fn add_banned_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
-    async fn is_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    /// Bans a single token by its `jti` claim, or every token of a session by its `sid` claim.
+    async fn add_banned_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError>;
+    /// Bans a single-use token unless it already is, in one step.
+    /// Returns `false` if it was already banned, so of concurrent requests with the token only one gets to use it.
+    async fn claim_token(&mut self, jti: String) -> Result<bool, BannedTokenStoreError>;
+    async fn is_banned(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
+    /// Revokes every token issued to the user up to now.
+    /// Tokens issued afterward are not affected.
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), BannedTokenStoreError>;
+    /// Returns the unix time in milliseconds of the user's last [revoke_all_tokens](BannedTokenStore::revoke_all_tokens) call, if any.
+    async fn get_tokens_revoked_at(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError>;
+}
+
+/// Where banned tokens are kept. Per-user revocations are always in Postgres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BannedTokenStoreBackend {
+    Redis,
+    Postgres,
 }
 
+impl FromStr for BannedTokenStoreBackend {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "redis" => Ok(BannedTokenStoreBackend::Redis),
+            "postgres" => Ok(BannedTokenStoreBackend::Postgres),
+            _ => Err(eyre!("Banned token store must be one of redis or postgres")),
+        }
+    }
+}
+
+/// Where pending 2FA login attempts are kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TwoFACodeStoreBackend {
+    /// Lost on restart and not shared between instances. Only meant for a single local instance.
+    Memory,
+    Redis,
+    Postgres,
+}
+
+impl FromStr for TwoFACodeStoreBackend {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "memory" => Ok(TwoFACodeStoreBackend::Memory),
+            "redis" => Ok(TwoFACodeStoreBackend::Redis),
+            "postgres" => Ok(TwoFACodeStoreBackend::Postgres),
+            _ => Err(eyre!("2FA code store must be one of memory, redis or postgres")),
+        }
+    }
+}
+
+/// The pending 2FA login attempt of each user.
+/// Codes expire [TWO_FA_CODE_TTL_SECONDS](crate::utils::constants::TWO_FA_CODE_TTL_SECONDS) after they're added.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore
 where
     Self: Sized + Send + Sync + Clone + 'static,
 {
+    /// Replaces the user's pending login attempt, along with its failed attempts.
     async fn add_code(
         &mut self,
         email: &Email,
@@ -156,4 +554,232 @@ where
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    /// Counts a wrong guess against the pending login attempt and returns how many there have been.
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError>;
+    /// Returns how many times the pending code has been resent, and when it was last sent.
+    async fn get_resends(&self, email: &Email) -> Result<(u32, DateTime<Utc>), TwoFACodeStoreError>;
+    /// Swaps in a new code for the pending login attempt and counts it as a resend.
+    /// The attempt keeps its expiry and failed attempts.
+    async fn replace_code(&mut self, email: &Email, code: TwoFACode) -> Result<(), TwoFACodeStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    /// Stores a reset token for the user, replacing any token they were issued before.
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    /// Removes the token and returns the email it was issued to.
+    /// Expired tokens are treated as not found.
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    /// Returns when the user's current token was issued, used to throttle reset emails.
+    async fn get_token_issued_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<DateTime<Utc>>, PasswordResetTokenStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait EmailVerificationTokenStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    /// Stores a verification token for the user, replacing any token they were issued before.
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError>;
+    /// Removes the token and returns the email it was issued to.
+    /// Expired tokens are treated as not found.
+    async fn consume_token(
+        &mut self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError>;
+    /// Returns when the user's current token was issued, used to throttle resends.
+    async fn get_token_issued_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<DateTime<Utc>>, EmailVerificationTokenStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    /// Stores a new set of codes for the user, invalidating every code they had before.
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError>;
+    /// Removes the code so it can't be used again.
+    /// Returns [CodeNotFound](RecoveryCodeStoreError::CodeNotFound) if the user has no such code.
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    async fn add_credential(&mut self, credential: PasskeyCredential) -> Result<(), PasskeyStoreError>;
+    async fn get_credentials(&self, email: &Email) -> Result<Vec<PasskeyCredential>, PasskeyStoreError>;
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &str,
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError>;
+    /// Stores the challenge for registering a passkey, replacing any unfinished registration of the user.
+    async fn add_registration_challenge(
+        &mut self,
+        email: &Email,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError>;
+    /// Removes and returns the challenge so each one can only be answered once.
+    /// Expired challenges are treated as not found.
+    async fn consume_registration_challenge(&mut self, email: &Email) -> Result<PasskeyChallenge, PasskeyStoreError>;
+    /// Stores the challenge of a login under its own ID. Logins start before the user is known,
+    /// so keying them by email would let anyone replace the challenge of someone else's login.
+    async fn add_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError>;
+    /// Removes and returns the challenge so each one can only be answered once.
+    /// Expired challenges are treated as not found.
+    async fn consume_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+    ) -> Result<PasskeyChallenge, PasskeyStoreError>;
+}
+
+/// Refresh tokens are grouped into families, one per login.
+/// Each refresh rotates the family onto a new token, and the old one is kept around to detect replays.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    /// Starts a new family for a fresh login. The family takes the ID of the login's session.
+    async fn create_family(&mut self, email: &Email, session_id: &SessionId, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+    /// Replaces `current` with `next` in its family and returns who the family belongs to, and its session.
+    /// If `current` was already rotated, the whole family is revoked and [RefreshTokenStoreError::TokenReused] is returned.
+    async fn rotate_token(
+        &mut self,
+        current: &RefreshToken,
+        next: &RefreshToken,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError>;
+    /// Revokes the family the token belongs to. Unknown tokens are ignored.
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+    /// Revokes every family belonging to the user.
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+/// Devices the user is logged in on, one entry per login.
+/// Sessions that haven't been refreshed for [REFRESH_TOKEN_TTL_SECONDS](crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS)
+/// have ended and are no longer returned.
+#[async_trait::async_trait]
+pub trait SessionStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    /// Returns the user's sessions, most recently seen first.
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    /// Records that the session was just used.
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+/// Where failed login counts are kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginAttemptStoreBackend {
+    Redis,
+    Postgres,
+}
+
+impl FromStr for LoginAttemptStoreBackend {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "redis" => Ok(LoginAttemptStoreBackend::Redis),
+            "postgres" => Ok(LoginAttemptStoreBackend::Postgres),
+            _ => Err(eyre!("Login attempt store must be one of redis or postgres")),
+        }
+    }
+}
+
+/// Failed password logins, counted per [LoginAttemptKey].
+/// A count is forgotten once [LOGIN_LOCKOUT_SECONDS](crate::utils::constants::LOGIN_LOCKOUT_SECONDS)
+/// pass without another failure, which is also when a lockout ends.
+#[async_trait::async_trait]
+pub trait LoginAttemptStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    async fn get_failures(&self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError>;
+    /// Counts an attempt as failed before its password is checked, and returns the failures from before it.
+    /// Reading and counting are one step, so concurrent attempts each see the ones that got in first.
+    async fn reserve_attempt(&mut self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError>;
+    /// Takes back a reserved attempt, given the failures [reserve_attempt](LoginAttemptStore::reserve_attempt) returned for it.
+    /// If no other attempt was counted since, the failures go back to those, last failure time included.
+    /// Otherwise only the count goes down, since the last failure time is another attempt's.
+    async fn release_attempt(&mut self, key: &LoginAttemptKey, previous: Option<LoginFailures>) -> Result<(), LoginAttemptStoreError>;
+    async fn reset_failures(&mut self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError>;
+}
+
+/// Emails waiting for the [EmailOutboxWorker](crate::services::EmailOutboxWorker) to deliver them, and what became of them.
+#[async_trait::async_trait]
+pub trait EmailOutboxStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    async fn enqueue(&mut self, email: QueuedEmail) -> Result<(), EmailOutboxStoreError>;
+    /// Returns up to `limit` pending emails that are due, oldest first, and pushes their next attempt back by
+    /// [EMAIL_OUTBOX_LEASE_SECONDS](crate::utils::constants::EMAIL_OUTBOX_LEASE_SECONDS),
+    /// so no other worker sends them in the meantime.
+    async fn claim_due(&mut self, limit: u32) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError>;
+    async fn mark_sent(&mut self, id: &QueuedEmailId) -> Result<(), EmailOutboxStoreError>;
+    /// Counts a failed attempt. The email is tried again at `retry_at`, or dead-lettered when it's `None`.
+    async fn record_failure(
+        &mut self,
+        id: &QueuedEmailId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxStoreError>;
+    /// Returns the emails queued for the recipient, newest first.
+    async fn get_emails(&self, recipient: &Email) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError>;
+}
+
+/// Devices each user has logged in from, to spot logins from new ones.
+#[async_trait::async_trait]
+pub trait KnownDeviceStore
+where
+    Self: Sized + Send + Sync + Clone + 'static,
+{
+    /// Remembers the device for the user, and returns whether they had logged in from it before.
+    async fn add_device(
+        &mut self,
+        email: &Email,
+        fingerprint: &DeviceFingerprint,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError>;
+    /// Forgets every device of the user.
+    async fn forget_devices(&mut self, email: &Email) -> Result<(), KnownDeviceStoreError>;
 }
@@ -1,5 +1,4 @@
 use std::hash::Hash;
-use std::str::FromStr;
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
 use crate::domain::{AuthAPIError, FromDbString};
@@ -25,7 +24,7 @@ impl Eq for Email {}
 
 impl PartialEq for Email {
     fn eq(&self, other: &Self) -> bool {
-        self.email.expose_secret().to_owned() == other.email.expose_secret().to_owned()
+        *self.email.expose_secret() == *other.email.expose_secret()
     }
 }
 
@@ -1,5 +1,22 @@
+use chrono::{DateTime, Utc};
 use super::Email;
 
+/// A rendered email, with the same content as plain text and HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// An email as it was handed to an [EmailClient].
+#[derive(Debug, Clone)]
+pub struct SentEmail {
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub sent_at: DateTime<Utc>,
+}
+
 #[async_trait::async_trait]
 pub trait EmailClient
 where
@@ -8,7 +25,12 @@ where
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
+        message: &EmailMessage,
     ) -> Result<(), String>;
-}
\ No newline at end of file
+
+    /// Emails sent so far, oldest first, to `recipient` or to anyone when it's `None`.
+    /// Only clients that keep what they send return any.
+    async fn sent_emails(&self, _recipient: Option<&Email>) -> Vec<SentEmail> {
+        Vec::new()
+    }
+}
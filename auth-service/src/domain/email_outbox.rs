@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use crate::domain::{Email, EmailMessage, FromDbString};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueuedEmailId(String);
+
+impl QueuedEmailId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid queued email id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for QueuedEmailId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for QueuedEmailId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FromDbString for QueuedEmailId {
+    fn from_db_string(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or for a retry.
+    Pending,
+    Sent,
+    /// Every attempt failed, so it won't be tried again.
+    DeadLettered,
+}
+
+impl DeliveryStatus {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "sent" => Ok(Self::Sent),
+            "dead_lettered" => Ok(Self::DeadLettered),
+            _ => Err(eyre!("Invalid delivery status: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Sent => "sent",
+            Self::DeadLettered => "dead_lettered",
+        }
+    }
+}
+
+/// An email in the outbox, with how its delivery is going.
+///
+/// The message is dropped once the email is sent or dead-lettered, since it can hold codes and tokens.
+/// Only the subject is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedEmail {
+    pub id: QueuedEmailId,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub status: DeliveryStatus,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When a pending email is next tried.
+    pub next_attempt_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+impl QueuedEmail {
+    /// A pending email, due right away.
+    pub fn new(recipient: Email, message: EmailMessage) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: QueuedEmailId::default(),
+            recipient,
+            message,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+            next_attempt_at: now,
+            sent_at: None,
+        }
+    }
+}
@@ -15,4 +15,21 @@ pub enum AuthAPIError {
     InvalidToken,
     #[error("Malformed Request")]
     MalformedRequest,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("Too many requests")]
+    TooManyRequests,
+    #[error("2FA already enabled")]
+    TwoFAAlreadyEnabled,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    /// Holds the number of seconds until the next login attempt is allowed.
+    #[error("Too many failed login attempts")]
+    TooManyLoginAttempts(i64),
+    #[error("Too many failed 2FA attempts")]
+    TooManyTwoFAAttempts,
+    #[error("No next signing key configured")]
+    NoNextSigningKey,
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use std::net::IpAddr;
+use super::session::device_label;
+
+const UNKNOWN_NETWORK: &str = "unknown";
+
+/// What a login is recognized by: the browser and OS from the user agent, and the network of the IP address.
+/// Browser updates and new addresses from the same provider don't make a device new.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceFingerprint {
+    /// Like "Firefox on Windows", see [Session::device_label](super::Session::device_label).
+    pub device_label: String,
+    /// The /24 of an IPv4 address or the /48 of an IPv6 one.
+    pub network: String,
+}
+
+impl DeviceFingerprint {
+    pub fn new(user_agent: Option<&str>, ip_address: Option<&str>) -> Self {
+        Self {
+            device_label: device_label(user_agent),
+            network: network(ip_address),
+        }
+    }
+}
+
+fn network(ip_address: Option<&str>) -> String {
+    match ip_address.and_then(|ip_address| ip_address.parse::<IpAddr>().ok()) {
+        Some(IpAddr::V4(ip)) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        },
+        Some(IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+            Some(ip) => network(Some(&ip.to_string())),
+            None => {
+                let [a, b, c, ..] = ip.segments();
+                format!("{:x}:{:x}:{:x}::/48", a, b, c)
+            },
+        },
+        None => UNKNOWN_NETWORK.to_string(),
+    }
+}
+
+/// Whether a login came from a device the user had logged in from before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceSighting {
+    /// The user had no known devices yet, e.g. on their first login.
+    First,
+    Known,
+    New,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIREFOX_ON_LINUX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+
+    #[test]
+    fn addresses_in_the_same_network_match() {
+        assert_eq!(
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("203.0.113.7")),
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("203.0.113.200")),
+        );
+        assert_eq!(
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("2001:db8:1:2::1")),
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("2001:db8:1:ffff::2")),
+        );
+        assert_ne!(
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("203.0.113.7")),
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("198.51.100.7")),
+        );
+    }
+
+    #[test]
+    fn network_of_address() {
+        assert_eq!(network(Some("203.0.113.7")), "203.0.113.0/24");
+        assert_eq!(network(Some("::ffff:203.0.113.7")), "203.0.113.0/24");
+        assert_eq!(network(Some("2001:db8:1:2::1")), "2001:db8:1::/48");
+        assert_eq!(network(Some("not an address")), "unknown");
+        assert_eq!(network(None), "unknown");
+    }
+
+    #[test]
+    fn browser_updates_dont_change_fingerprint() {
+        let updated = FIREFOX_ON_LINUX.replace("125.0", "126.0");
+        assert_eq!(
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("203.0.113.7")),
+            DeviceFingerprint::new(Some(&updated), Some("203.0.113.7")),
+        );
+        assert_ne!(
+            DeviceFingerprint::new(Some(FIREFOX_ON_LINUX), Some("203.0.113.7")),
+            DeviceFingerprint::new(Some("curl/8.5.0"), Some("203.0.113.7")),
+        );
+    }
+}
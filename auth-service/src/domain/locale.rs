@@ -0,0 +1,74 @@
+use color_eyre::eyre::{eyre, Result};
+
+/// A language emails can be sent in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    /// Accepts a BCP 47 language tag. Only the language matters, so `es-MX` is Spanish.
+    pub fn parse(tag: &str) -> Result<Self> {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        match language.trim().to_lowercase().as_str() {
+            "en" => Ok(Self::En),
+            "es" => Ok(Self::Es),
+            _ => Err(eyre!("Unsupported language: {}", tag)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Es => "es",
+        }
+    }
+
+    /// Picks the supported language the client ranks highest in an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let tag = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+
+            if let Ok(locale) = Self::parse(tag) {
+                // Ties go to whichever the client listed first.
+                if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                    best = Some((locale, quality));
+                }
+            }
+        }
+
+        best.map(|(locale, _)| locale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+
+    #[test]
+    fn region_and_case_are_ignored() {
+        assert_eq!(Locale::parse("en").unwrap(), Locale::En);
+        assert_eq!(Locale::parse("es-MX").unwrap(), Locale::Es);
+        assert_eq!(Locale::parse("ES_es").unwrap(), Locale::Es);
+        assert!(Locale::parse("fr").is_err());
+        assert!(Locale::parse("").is_err());
+    }
+
+    #[test]
+    fn accept_language_picks_highest_supported_quality() {
+        assert_eq!(Locale::from_accept_language("es-ES,es;q=0.9,en;q=0.8"), Some(Locale::Es));
+        assert_eq!(Locale::from_accept_language("fr-FR, en;q=0.5, es;q=0.7"), Some(Locale::Es));
+        assert_eq!(Locale::from_accept_language("en-US, es"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("es;q=0, en;q=0.1"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("fr, de;q=0.9"), None);
+        assert_eq!(Locale::from_accept_language("*"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+use crate::domain::Email;
+
+/// What failed logins are counted against.
+/// Guessing passwords for one account is slowed down by its email, and trying many accounts by the client's IP.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LoginAttemptKey {
+    Email(Email),
+    IpAddress(String),
+}
+
+impl LoginAttemptKey {
+    /// Unique across both kinds of key, for stores that need a single string.
+    pub fn as_key(&self) -> String {
+        match self {
+            Self::Email(email) => format!("email:{}", email.as_ref().expose_secret()),
+            Self::IpAddress(ip_address) => format!("ip:{}", ip_address),
+        }
+    }
+}
+
+/// Consecutive failed logins for one [LoginAttemptKey].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoginFailures {
+    pub count: u32,
+    pub last_failure_at: DateTime<Utc>,
+}
+
+/// How repeated failed logins are slowed down, and when they're locked out.
+///
+/// Past `free_failures`, every attempt has to wait for a delay after the previous failure,
+/// starting at one second and doubling each time. At `lockout_failures` the wait becomes `lockout_seconds`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoginThrottle {
+    pub free_failures: u32,
+    pub lockout_failures: u32,
+    pub lockout_seconds: i64,
+}
+
+impl LoginThrottle {
+    pub fn is_locked_out(&self, failures: u32) -> bool {
+        failures >= self.lockout_failures
+    }
+
+    /// How long after the last of `failures` failed logins the next attempt is allowed.
+    pub fn wait_seconds(&self, failures: u32) -> i64 {
+        if self.is_locked_out(failures) {
+            return self.lockout_seconds;
+        }
+        if failures <= self.free_failures {
+            return 0;
+        }
+
+        2_i64.checked_pow(failures - self.free_failures - 1)
+            .unwrap_or(i64::MAX)
+            .min(self.lockout_seconds)
+    }
+
+    /// Whole seconds until another attempt is allowed, or `None` if one is allowed now.
+    pub fn retry_after(&self, failures: &LoginFailures, now: DateTime<Utc>) -> Option<i64> {
+        let allowed_at = failures.last_failure_at + Duration::seconds(self.wait_seconds(failures.count));
+        let remaining = allowed_at - now;
+        if remaining <= Duration::zero() {
+            return None;
+        }
+
+        // Round up, so clients that wait exactly this long aren't turned away again.
+        let seconds = remaining.num_seconds();
+        Some(if remaining > Duration::seconds(seconds) { seconds + 1 } else { seconds })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THROTTLE: LoginThrottle = LoginThrottle {
+        free_failures: 2,
+        lockout_failures: 6,
+        lockout_seconds: 900,
+    };
+
+    #[test]
+    fn test_wait_seconds() {
+        let waits: Vec<i64> = (0..=7).map(|failures| THROTTLE.wait_seconds(failures)).collect();
+
+        assert_eq!(waits, vec![0, 0, 0, 1, 2, 4, 900, 900]);
+    }
+
+    #[test]
+    fn test_wait_seconds_never_exceeds_lockout() {
+        let throttle = LoginThrottle { lockout_failures: 100, ..THROTTLE };
+
+        assert_eq!(throttle.wait_seconds(20), 900);
+        assert_eq!(throttle.wait_seconds(99), 900);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = Utc::now();
+        let failures = |count, seconds_ago| LoginFailures {
+            count,
+            last_failure_at: now - Duration::seconds(seconds_ago),
+        };
+
+        assert_eq!(THROTTLE.retry_after(&failures(2, 0), now), None);
+        assert_eq!(THROTTLE.retry_after(&failures(4, 0), now), Some(2));
+        assert_eq!(THROTTLE.retry_after(&failures(4, 2), now), None);
+        assert_eq!(THROTTLE.retry_after(&failures(6, 100), now), Some(800));
+        assert_eq!(THROTTLE.retry_after(&failures(6, 900), now), None);
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        let now = Utc::now();
+        let failures = LoginFailures {
+            count: 3,
+            last_failure_at: now - Duration::milliseconds(500),
+        };
+
+        assert_eq!(THROTTLE.retry_after(&failures, now), Some(1));
+    }
+}
@@ -4,10 +4,24 @@ mod data_stores;
 mod password;
 mod email;
 mod email_client;
+mod totp;
+mod passkey;
+mod session;
+mod login_attempt;
+mod locale;
+mod email_outbox;
+mod known_device;
 
 pub use user::*;
 pub use error::*;
 pub use data_stores::*;
 pub use password::*;
 pub use email::*;
-pub use email_client::*;
\ No newline at end of file
+pub use email_client::*;
+pub use totp::*;
+pub use passkey::*;
+pub use session::*;
+pub use login_attempt::*;
+pub use locale::*;
+pub use email_outbox::*;
+pub use known_device::*;
\ No newline at end of file
@@ -0,0 +1,76 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use color_eyre::eyre::{eyre, Result};
+use crate::domain::{Email, FromDbString};
+
+/// Number of random bytes in a ceremony challenge. The WebAuthn spec asks for at least 16.
+const PASSKEY_CHALLENGE_BYTES: usize = 32;
+
+/// Identifies a started passkey login. It's handed to the client along with the challenge and sent back with the assertion.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PasskeyChallengeId(String);
+
+impl PasskeyChallengeId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).map_err(|_| eyre!("Invalid passkey challenge id"))?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for PasskeyChallengeId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for PasskeyChallengeId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Base64url encoded random challenge the authenticator has to sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasskeyChallenge(String);
+
+impl Default for PasskeyChallenge {
+    fn default() -> Self {
+        let bytes: [u8; PASSKEY_CHALLENGE_BYTES] = rand::random();
+        Self(BASE64_URL.encode(bytes))
+    }
+}
+
+impl AsRef<str> for PasskeyChallenge {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FromDbString for PasskeyChallenge {
+    fn from_db_string(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+/// A public key credential registered by one of the user's authenticators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasskeyCredential {
+    /// Base64url encoded credential ID chosen by the authenticator.
+    pub credential_id: String,
+    pub email: Email,
+    /// Uncompressed SEC1 encoded P-256 public key.
+    pub public_key: Vec<u8>,
+    /// Signature counter last reported by the authenticator, used to detect cloned keys.
+    pub sign_count: u32,
+}
+
+impl PasskeyCredential {
+    pub fn parse_credential_id(id: &str) -> Result<String> {
+        let bytes = BASE64_URL.decode(id)
+            .map_err(|_| eyre!("Credential ID is not valid base64url"))?;
+        if bytes.is_empty() {
+            return Err(eyre!("Credential ID is empty"));
+        }
+        Ok(BASE64_URL.encode(bytes))
+    }
+}
@@ -1,5 +1,4 @@
 use std::hash::Hash;
-use std::str::FromStr;
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
 use crate::domain::{AuthAPIError, FromDbString};
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use crate::domain::{Email, FromDbString};
+
+/// Identifies one login. Access tokens carry it as their `sid` claim and
+/// it doubles as the ID of the session's refresh token family.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = uuid::Uuid::parse_str(&id).wrap_err("Invalid session id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl FromDbString for SessionId {
+    fn from_db_string(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+/// A device the user is logged in on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    /// Short description like "Firefox on Windows", derived from the user agent.
+    pub device_label: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Updated whenever the session's access token is refreshed.
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(email: Email, user_agent: Option<String>, ip_address: Option<String>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: SessionId::default(),
+            email,
+            device_label: device_label(user_agent.as_deref()),
+            user_agent,
+            ip_address,
+            created_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+const UNKNOWN_DEVICE_LABEL: &str = "Unknown device";
+
+// Order matters: Edge and Opera user agents also mention Chrome, and Chrome's mentions Safari.
+const BROWSERS: [(&str, &str); 6] = [
+    ("Edg/", "Edge"),
+    ("OPR/", "Opera"),
+    ("Firefox/", "Firefox"),
+    ("Chrome/", "Chrome"),
+    ("Safari/", "Safari"),
+    ("curl/", "curl"),
+];
+
+// Android and iOS user agents also mention Linux and Mac OS X.
+const OPERATING_SYSTEMS: [(&str, &str); 6] = [
+    ("Android", "Android"),
+    ("iPhone", "iOS"),
+    ("iPad", "iPadOS"),
+    ("Windows", "Windows"),
+    ("Mac OS X", "macOS"),
+    ("Linux", "Linux"),
+];
+
+pub(crate) fn device_label(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent else {
+        return UNKNOWN_DEVICE_LABEL.to_string();
+    };
+
+    let find = |names: &[(&str, &'static str)]| names
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| *name);
+
+    match (find(&BROWSERS), find(&OPERATING_SYSTEMS)) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => UNKNOWN_DEVICE_LABEL.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_label() {
+        let cases = [
+            ("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0", "Firefox on Windows"),
+            ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36", "Chrome on macOS"),
+            ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0", "Edge on Windows"),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1", "Safari on iOS"),
+            ("Mozilla/5.0 (Linux; Android 14) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36", "Chrome on Android"),
+            ("curl/8.5.0", "curl"),
+            ("something else", UNKNOWN_DEVICE_LABEL),
+        ];
+
+        for (user_agent, expected) in cases {
+            assert_eq!(device_label(Some(user_agent)), expected, "{}", user_agent);
+        }
+        assert_eq!(device_label(None), UNKNOWN_DEVICE_LABEL);
+    }
+
+    #[test]
+    fn test_session_id_parse() {
+        let id = SessionId::default();
+        assert_eq!(SessionId::parse(id.as_ref().to_string()).unwrap(), id);
+        assert!(SessionId::parse("not-a-session-id".to_string()).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use crate::domain::FromDbString;
+
+/// Number of random bytes in a freshly generated secret.
+/// RFC 4226 recommends 160 bits, which is also what authenticator apps expect for SHA-1.
+const TOTP_SECRET_BYTES: usize = 20;
+/// `totp-rs` refuses secrets shorter than 128 bits.
+const MIN_TOTP_SECRET_BYTES: usize = 16;
+
+/// Base32 encoded shared secret for RFC 6238 TOTP codes.
+#[derive(Debug, Clone)]
+pub struct TotpSecret {
+    secret: Secret<String>,
+}
+
+impl TotpSecret {
+    pub fn parse(s: Secret<String>) -> Result<Self> {
+        let bytes = totp_rs::Secret::Encoded(s.expose_secret().to_owned())
+            .to_bytes()
+            .map_err(|_| eyre!("TOTP secret is not valid base32"))?;
+
+        if bytes.len() < MIN_TOTP_SECRET_BYTES {
+            return Err(eyre!("TOTP secret is too short"));
+        }
+
+        Ok(Self { secret: s })
+    }
+
+    /// Returns the raw secret bytes used to compute codes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        totp_rs::Secret::Encoded(self.secret.expose_secret().to_owned())
+            .to_bytes()
+            .map_err(|_| eyre!("TOTP secret is not valid base32"))
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let bytes: [u8; TOTP_SECRET_BYTES] = rand::random();
+        Self {
+            secret: Secret::new(totp_rs::Secret::Raw(bytes.to_vec()).to_encoded().to_string()),
+        }
+    }
+}
+
+impl PartialEq for TotpSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.secret.expose_secret() == other.secret.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for TotpSecret {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.secret
+    }
+}
+
+impl FromDbString for TotpSecret {
+    fn from_db_string(s: &str) -> Self {
+        Self {
+            secret: Secret::new(s.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+    use super::TotpSecret;
+
+    #[test]
+    fn generated_secret_is_parsed_successfully() {
+        let secret = TotpSecret::default();
+        let parsed = TotpSecret::parse(secret.as_ref().clone()).unwrap();
+        assert_eq!(parsed, secret);
+        assert_eq!(parsed.to_bytes().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn generated_secrets_are_unique() {
+        let first = TotpSecret::default();
+        let second = TotpSecret::default();
+        assert_ne!(first.as_ref().expose_secret(), second.as_ref().expose_secret());
+    }
+
+    #[test]
+    fn invalid_base32_is_rejected() {
+        assert!(TotpSecret::parse(Secret::new("not base32!".to_string())).is_err());
+    }
+
+    #[test]
+    fn short_secret_is_rejected() {
+        // 10 bytes of base32
+        assert!(TotpSecret::parse(Secret::new("GEZDGNBVGY3TQOJQ".to_string())).is_err());
+    }
+}
@@ -1,18 +1,63 @@
-use crate::domain::{AuthAPIError, Password, Email};
+use color_eyre::eyre::{eyre, Result};
+use crate::domain::{AuthAPIError, Password, Email, Locale, TotpSecret};
+
+/// How a user proves the second factor when logging in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TwoFAMethod {
+    #[default]
+    None,
+    /// A six-digit code is emailed on every login.
+    Email,
+    /// An RFC 6238 code from an authenticator app.
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            _ => Err(eyre!("Invalid 2FA method: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Email => "email",
+            Self::Totp => "totp",
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    /// Set as soon as TOTP enrollment starts, but only used once `two_fa_method` is `Totp`.
+    pub totp_secret: Option<TotpSecret>,
+    pub verified: bool,
+    /// Language the user asked for at signup. Emails fall back to the request's `Accept-Language`.
+    pub locale: Option<Locale>,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Result<User, AuthAPIError> {
+    /// New users start out unverified until they confirm their email address.
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> Result<User, AuthAPIError> {
         Ok(Self {
             email,
             password,
-            requires_2fa,
+            two_fa_method,
+            totp_secret: None,
+            verified: false,
+            locale: None,
         })
     }
-}
\ No newline at end of file
+
+    /// The language to email the user in, given the one the current request asked for.
+    pub fn email_locale(&self, requested: Option<Locale>) -> Locale {
+        self.locale.or(requested).unwrap_or_default()
+    }
+}
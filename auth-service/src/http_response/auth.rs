@@ -1,5 +1,6 @@
 use std::error::Error;
-use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderValue, StatusCode};
 use axum::Json;
 use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
@@ -9,8 +10,21 @@ pub enum AuthMessage {
     UserCreated,
     UserLoggedIn,
     UserLoggedOut,
+    UserLoggedOutEverywhere,
     User2FAVerified,
     UserTokenVerified,
+    PasswordResetRequested,
+    PasswordReset,
+    PasswordChanged,
+    EmailVerified,
+    VerificationEmailSent,
+    PasskeyRegistered,
+    MagicLinkSent,
+    TokenRefreshed,
+    SessionRevoked,
+    AccountUnlocked,
+    TwoFACodeResent,
+    LoginReported,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -24,8 +38,21 @@ impl IntoResponse for AuthMessage {
             AuthMessage::UserCreated => (StatusCode::CREATED, "User created successfully!"),
             AuthMessage::UserLoggedIn => (StatusCode::OK, "User logged in successfully!"),
             AuthMessage::UserLoggedOut => (StatusCode::OK, "User logged out successfully!"),
+            AuthMessage::UserLoggedOutEverywhere => (StatusCode::OK, "User logged out of every session!"),
             AuthMessage::User2FAVerified => (StatusCode::OK, "2FA verified successfully!"),
             AuthMessage::UserTokenVerified => (StatusCode::OK, "Token verified successfully!"),
+            AuthMessage::PasswordResetRequested => (StatusCode::OK, "If the account exists, a password reset email has been sent."),
+            AuthMessage::PasswordReset => (StatusCode::OK, "Password reset successfully!"),
+            AuthMessage::PasswordChanged => (StatusCode::OK, "Password changed successfully!"),
+            AuthMessage::EmailVerified => (StatusCode::OK, "Email verified successfully!"),
+            AuthMessage::VerificationEmailSent => (StatusCode::OK, "If the account needs verification, a verification email has been sent."),
+            AuthMessage::PasskeyRegistered => (StatusCode::CREATED, "Passkey registered successfully!"),
+            AuthMessage::MagicLinkSent => (StatusCode::OK, "If the account exists, a login link has been sent."),
+            AuthMessage::TokenRefreshed => (StatusCode::OK, "Token refreshed successfully!"),
+            AuthMessage::SessionRevoked => (StatusCode::OK, "Session revoked successfully!"),
+            AuthMessage::AccountUnlocked => (StatusCode::OK, "Account unlocked successfully!"),
+            AuthMessage::TwoFACodeResent => (StatusCode::OK, "2FA code resent successfully!"),
+            AuthMessage::LoginReported => (StatusCode::OK, "Login reported. Every session was logged out, please reset your password."),
         };
         let body = Json(AuthMessageResponse {
             message_body: body.to_string(),
@@ -43,12 +70,25 @@ impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
 
+        let retry_after = match &self {
+            AuthAPIError::TooManyLoginAttempts(seconds) => Some(*seconds),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
             AuthAPIError::MalformedRequest => (StatusCode::BAD_REQUEST, "Malformed request"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthAPIError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthAPIError::TwoFAAlreadyEnabled => (StatusCode::CONFLICT, "2FA already enabled"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::TooManyLoginAttempts(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many failed login attempts, try again later"),
+            AuthAPIError::TooManyTwoFAAttempts => (StatusCode::TOO_MANY_REQUESTS, "Too many failed 2FA attempts, log in again"),
+            AuthAPIError::NoNextSigningKey => (StatusCode::CONFLICT, "No next signing key is configured"),
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -56,7 +96,11 @@ impl IntoResponse for AuthAPIError {
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
@@ -1,10 +1,14 @@
 use std::error::Error;
+use std::net::SocketAddr;
 use axum::{
-    routing::post,
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    routing::{delete, get, post},
     serve::Serve,
     Router,
 };
 use axum::http::Method;
+use axum::middleware::AddExtension;
+use redis::aio::ConnectionManager;
 use redis::{Client, RedisResult};
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
@@ -21,13 +25,19 @@ pub mod http_response;
 pub mod utils;
 
 use app_state::AppState;
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{BannedTokenStore, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TwoFACodeStore, UserStore};
+use crate::services::EmailOutboxWorker;
 use crate::utils::{make_span_with_request_id, on_request, on_response};
+use crate::utils::constants::{REDIS_CONNECTION_TIMEOUT, REDIS_RECONNECT_RETRIES, REDIS_RESPONSE_TIMEOUT};
 
 // This struct encapsulates our application-related logic.
 #[derive(Debug)]
 pub struct Application {
-    server: Serve<TcpListener, Router, Router>,
+    server: Serve<
+        TcpListener,
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     address: String,
@@ -43,6 +53,20 @@ pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     Client::open(redis_url)
 }
 
+/// A multiplexed Redis connection. Clones share it, so the stores can all use one.
+/// It reconnects by itself when the connection drops.
+pub async fn get_redis_connection(redis_hostname: String) -> RedisResult<ConnectionManager> {
+    let client = get_redis_client(redis_hostname)?;
+    ConnectionManager::new_with_backoff_and_timeouts(
+        client,
+        2,
+        100,
+        REDIS_RECONNECT_RETRIES,
+        *REDIS_RESPONSE_TIMEOUT,
+        *REDIS_CONNECTION_TIMEOUT,
+    ).await
+}
+
 impl Application
 {
     /// We have to implement the generic trait `UserStore` for the `Application` struct.
@@ -52,12 +76,21 @@ impl Application
     /// `UserStore` + `Clone` + `Send` + `Sync` + `'static`
     ///
     /// **see also [app_state.rs](crate::app_state::AppState)**
-    pub async fn build<T, U, V, W>(app_state: AppState<T, U, V, W>, address: &str) -> Result<Self, Box<dyn Error>>
+    pub async fn build<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(app_state: AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>, address: &str) -> Result<Self, Box<dyn Error>>
     where
         T: UserStore,
         U: BannedTokenStore,
         V: TwoFACodeStore,
-        W: EmailClient
+        W: EmailClient,
+        X: PasswordResetTokenStore,
+        Y: EmailVerificationTokenStore,
+        Z: RecoveryCodeStore,
+        A: PasskeyStore,
+        B: RefreshTokenStore,
+        C: SessionStore,
+        D: LoginAttemptStore,
+        E: EmailOutboxStore,
+        F: KnownDeviceStore
     {
 
         let allowed_origins = [
@@ -69,18 +102,55 @@ impl Application
             ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Emails the routes queue are sent from here, outside of any request.
+        let email_outbox_worker = EmailOutboxWorker::new(
+            app_state.email_outbox.clone(),
+            app_state.email_client.clone(),
+            app_state.email_outbox_wakeup.clone(),
+        );
+        tokio::spawn(email_outbox_worker.run());
+
         let router = Router::new()
             .fallback_service(serve_dir)
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
             .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/resend-2fa", post(routes::resend_2fa))
             .route("/verify-token", post(routes::verify_token))
             .route("/refresh-token", post(routes::refresh_token))
+            .route("/password-reset/request", post(routes::request_password_reset))
+            .route("/password-reset/confirm", post(routes::confirm_password_reset))
+            .route("/change-password", post(routes::change_password))
+            .route("/verify-email", post(routes::verify_email))
+            .route("/resend-verification", post(routes::resend_verification))
+            .route("/enroll-totp", post(routes::enroll_totp))
+            .route("/confirm-totp", post(routes::confirm_totp))
+            .route("/regenerate-recovery-codes", post(routes::regenerate_recovery_codes))
+            .route("/passkey/register/start", post(routes::start_passkey_registration))
+            .route("/passkey/register/finish", post(routes::finish_passkey_registration))
+            .route("/passkey/login/start", post(routes::start_passkey_login))
+            .route("/passkey/login/finish", post(routes::finish_passkey_login))
+            .route("/login/magic-link", post(routes::request_magic_link))
+            .route("/login/magic-link/consume", post(routes::consume_magic_link))
+            .route("/unlock-account", post(routes::unlock_account))
+            .route("/report-login", post(routes::report_login))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/{id}", delete(routes::revoke_session))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route("/admin/rotate-signing-key", post(routes::rotate_signing_key))
+            .route("/admin/logout-all", post(routes::admin_logout_all))
+            .route("/admin/email-deliveries", get(routes::email_deliveries));
+
+        #[cfg(feature = "dev-outbox")]
+        let router = router.route("/dev/outbox", get(routes::dev_outbox));
+
+        let router = router
             .with_state(app_state)
             .layer(cors)
             .layer(
@@ -92,7 +162,8 @@ impl Application
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // the peer address is recorded on new sessions
+        let server = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>());
 
         Ok(
             Self {
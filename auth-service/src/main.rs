@@ -1,11 +1,17 @@
 use std::sync::Arc;
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
 use tokio::sync::RwLock;
 
 use auth_service::app_state::AppState;
-use auth_service::services::{HashmapTwoFACodeStore, MockEmailClient, PostgresUserStore, RedisBannedTokenStore};
-use auth_service::{Application, get_postgres_pool, get_redis_client};
-use auth_service::utils::constants::{DATABASE_URL, REDIS_HOST_NAME};
+use auth_service::domain::{BannedTokenStore, BannedTokenStoreBackend, EmailClient, LoginAttemptStore, LoginAttemptStoreBackend, TwoFACodeStore, TwoFACodeStoreBackend};
+#[cfg(feature = "dev-outbox")]
+use auth_service::services::RecordingEmailClient;
+#[cfg(not(feature = "dev-outbox"))]
+use auth_service::services::MockEmailClient;
+use auth_service::services::{ExpiredRecordPurger, HashmapTwoFACodeStore, PostgresBannedTokenStore, PostgresEmailOutboxStore, PostgresEmailVerificationTokenStore, PostgresKnownDeviceStore, PostgresLoginAttemptStore, PostgresPasskeyStore, PostgresPasswordResetTokenStore, PostgresRecoveryCodeStore, PostgresRefreshTokenStore, PostgresSessionStore, PostgresTwoFACodeStore, PostgresUserStore, RedisBannedTokenStore, RedisLoginAttemptStore, RedisTwoFACodeStore, SmtpEmailClient};
+use auth_service::{Application, get_postgres_pool, get_redis_connection};
+use auth_service::utils::constants::{BANNED_TOKEN_STORE_BACKEND, DATABASE_URL, LOGIN_ATTEMPT_STORE_BACKEND, REDIS_HOST_NAME, SMTP_SETTINGS, TWO_FA_CODE_STORE_BACKEND};
 use auth_service::utils::constants::prod;
 use auth_service::utils::init_tracing;
 
@@ -16,13 +22,105 @@ async fn main() {
 
     init_tracing().expect("Failed to initialize tracing");
 
+    match SMTP_SETTINGS.as_ref() {
+        Some(settings) => {
+            let email_client = SmtpEmailClient::new(settings)
+                .expect("Failed to create SMTP email client");
+            run(email_client).await
+        },
+        #[cfg(feature = "dev-outbox")]
+        None => {
+            tracing::warn!("SMTP_HOST is not set, emails are kept for /dev/outbox");
+            run(RecordingEmailClient::default()).await
+        },
+        #[cfg(not(feature = "dev-outbox"))]
+        None => {
+            tracing::warn!("SMTP_HOST is not set, emails will only be logged");
+            run(MockEmailClient).await
+        },
+    }
+}
+
+async fn run<W: EmailClient>(email_client: W) {
     let pg_pool = configure_postgresql().await;
+    let redis_conn = configure_redis().await;
+
+    tokio::spawn(ExpiredRecordPurger::new(pg_pool.clone()).run());
+
+    match *BANNED_TOKEN_STORE_BACKEND {
+        BannedTokenStoreBackend::Redis => {
+            let banned_token_store = RedisBannedTokenStore::new(redis(&redis_conn), pg_pool.clone());
+            run_with_banned_token_store(email_client, banned_token_store, pg_pool, redis_conn).await
+        },
+        BannedTokenStoreBackend::Postgres => {
+            let banned_token_store = PostgresBannedTokenStore::new(pg_pool.clone());
+            run_with_banned_token_store(email_client, banned_token_store, pg_pool, redis_conn).await
+        },
+    }
+}
+
+async fn run_with_banned_token_store<W: EmailClient, U: BannedTokenStore>(
+    email_client: W,
+    banned_token_store: U,
+    pg_pool: PgPool,
+    redis_conn: Option<ConnectionManager>,
+) {
+    match *TWO_FA_CODE_STORE_BACKEND {
+        TwoFACodeStoreBackend::Redis => {
+            let two_fa_code_store = RedisTwoFACodeStore::new(redis(&redis_conn));
+            run_with_two_fa_code_store(email_client, banned_token_store, two_fa_code_store, pg_pool, redis_conn).await
+        },
+        TwoFACodeStoreBackend::Postgres => {
+            let two_fa_code_store = PostgresTwoFACodeStore::new(pg_pool.clone());
+            run_with_two_fa_code_store(email_client, banned_token_store, two_fa_code_store, pg_pool, redis_conn).await
+        },
+        TwoFACodeStoreBackend::Memory => {
+            tracing::warn!("2FA codes are kept in memory, they are lost on restart and not shared between instances");
+            run_with_two_fa_code_store(email_client, banned_token_store, HashmapTwoFACodeStore::default(), pg_pool, redis_conn).await
+        },
+    }
+}
 
+async fn run_with_two_fa_code_store<W: EmailClient, U: BannedTokenStore, V: TwoFACodeStore>(
+    email_client: W,
+    banned_token_store: U,
+    two_fa_code_store: V,
+    pg_pool: PgPool,
+    redis_conn: Option<ConnectionManager>,
+) {
+    match *LOGIN_ATTEMPT_STORE_BACKEND {
+        LoginAttemptStoreBackend::Redis => {
+            let login_attempt_store = RedisLoginAttemptStore::new(redis(&redis_conn));
+            serve(email_client, banned_token_store, two_fa_code_store, login_attempt_store, pg_pool).await
+        },
+        LoginAttemptStoreBackend::Postgres => {
+            let login_attempt_store = PostgresLoginAttemptStore::new(pg_pool.clone());
+            serve(email_client, banned_token_store, two_fa_code_store, login_attempt_store, pg_pool).await
+        },
+    }
+}
+
+async fn serve<W: EmailClient, U: BannedTokenStore, V: TwoFACodeStore, D: LoginAttemptStore>(
+    email_client: W,
+    banned_token_store: U,
+    two_fa_code_store: V,
+    login_attempt_store: D,
+    pg_pool: PgPool,
+) {
     let app_state = AppState::new(
-        Arc::new(RwLock::new(PostgresUserStore::new(pg_pool))),
-        Arc::new(RwLock::new(RedisBannedTokenStore::new(Arc::new(RwLock::new(configure_redis()))))),
-        Arc::new(RwLock::new(HashmapTwoFACodeStore::default())),
-        Arc::new(RwLock::new(MockEmailClient::default())),
+        Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(banned_token_store)),
+        Arc::new(RwLock::new(two_fa_code_store)),
+        Arc::new(RwLock::new(email_client)),
+        Arc::new(RwLock::new(PostgresPasswordResetTokenStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresEmailVerificationTokenStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresRefreshTokenStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(login_attempt_store)),
+        Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool.clone()))),
+        Arc::new(RwLock::new(PostgresKnownDeviceStore::new(pg_pool))),
     );
 
     let app = Application::build(app_state, prod::APP_ADDRESS)
@@ -45,9 +143,21 @@ async fn configure_postgresql() -> PgPool {
 
     pg_pool
 }
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+/// Only connects when a store is configured to use Redis, so deployments that keep everything in Postgres don't need it.
+async fn configure_redis() -> Option<ConnectionManager> {
+    let uses_redis = *BANNED_TOKEN_STORE_BACKEND == BannedTokenStoreBackend::Redis
+        || *TWO_FA_CODE_STORE_BACKEND == TwoFACodeStoreBackend::Redis
+        || *LOGIN_ATTEMPT_STORE_BACKEND == LoginAttemptStoreBackend::Redis;
+    if !uses_redis {
+        return None;
+    }
+
+    let redis_conn = get_redis_connection(REDIS_HOST_NAME.to_owned())
+        .await
+        .expect("Failed to get Redis connection");
+    Some(redis_conn)
+}
+
+fn redis(redis_conn: &Option<ConnectionManager>) -> ConnectionManager {
+    redis_conn.clone().expect("Redis is connected whenever a store uses it")
 }
\ No newline at end of file
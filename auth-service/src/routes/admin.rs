@@ -0,0 +1,112 @@
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
+use axum::Json;
+use axum::response::IntoResponse;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore,
+    UserStoreError
+};
+use crate::http_response::AuthMessage;
+use crate::routes::revoke_all_sessions;
+use crate::utils::constants::{ADMIN_API_KEY, JWT_KEYRING};
+
+#[derive(Debug, Deserialize)]
+pub struct AdminLogoutAllRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateSigningKeyResponse {
+    #[serde(rename = "keyId")]
+    pub key_id: String,
+}
+
+/// Signs new tokens with the configured next key.
+///
+/// Tokens signed with the previous key keep verifying until they expire,
+/// so nobody is logged out by a rotation.
+#[tracing::instrument(name = "Rotate Signing Key", skip_all)]
+pub async fn rotate_signing_key(headers: HeaderMap) -> Result<impl IntoResponse, AuthAPIError> {
+    require_admin(&headers)?;
+
+    let key_id = JWT_KEYRING.rotate().ok_or(AuthAPIError::NoNextSigningKey)?;
+
+    tracing::info!("rotated JWT signing key, new kid: {}", key_id);
+
+    Ok(Json(RotateSigningKeyResponse { key_id }))
+}
+
+/// Logs a user out of every session, e.g. after their account was compromised.
+#[tracing::instrument(name = "Admin Logout All", skip_all)]
+pub async fn admin_logout_all<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    headers: HeaderMap,
+    Json(request): Json<AdminLogoutAllRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    require_admin(&headers)?;
+
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    state.user_store.read().await
+        .get_user(&email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    revoke_all_sessions(&state, &email).await?;
+
+    Ok(AuthMessage::UserLoggedOutEverywhere.into_response())
+}
+
+/// Checks for an `Authorization: Bearer <ADMIN_API_KEY>` header.
+pub(crate) fn require_admin(headers: &HeaderMap) -> Result<(), AuthAPIError> {
+    let api_key = headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    let admin_api_key = ADMIN_API_KEY.as_ref().ok_or(AuthAPIError::InvalidToken)?;
+
+    // Comparing digests keeps the response time from revealing how much of the key matched.
+    if Sha256::digest(api_key.as_bytes()) != Sha256::digest(admin_api_key.expose_secret().as_bytes()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(())
+}
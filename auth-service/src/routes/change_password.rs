@@ -0,0 +1,92 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    Password,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::routes::revoke_all_sessions;
+use crate::utils::auth::validate_token;
+use crate::utils::constants::JWT_COOKIE_NAME;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+/// Changes the password of the logged-in user.
+///
+/// Every token issued to the user before the change is revoked, including the one used
+/// for this request, so the auth cookie is removed and the user has to log in again.
+#[tracing::instrument(name = "Change Password", skip_all)]
+pub async fn change_password<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let cookie = jar.get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .clone();
+
+    let claims = validate_token(cookie.value(), state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let current_password = Password::parse(request.current_password)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let new_password = Password::parse(request.new_password)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let mut user_store = state.user_store.write().await;
+    user_store.validate_user(&email, &current_password)
+        .await
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    user_store.update_password(&email, new_password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    revoke_all_sessions(&state, &email).await?;
+
+    let jar = jar.remove(cookie);
+
+    Ok((jar, AuthMessage::PasswordChanged.into_response()))
+}
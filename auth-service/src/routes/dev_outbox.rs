@@ -0,0 +1,88 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use axum::response::IntoResponse;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+
+#[derive(Debug, Deserialize)]
+pub struct OutboxQuery {
+    pub email: Option<Secret<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxResponse {
+    pub emails: Vec<OutboxEmail>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    /// RFC 3339 timestamp.
+    #[serde(rename = "sentAt")]
+    pub sent_at: String,
+}
+
+/// Lists the emails the service has sent, optionally only those to `?email=`.
+///
+/// Only built with the `dev-outbox` feature, for local development without a mail server.
+/// It has no authentication, so it must never be enabled in production.
+#[tracing::instrument(name = "Dev Outbox", skip_all)]
+pub async fn dev_outbox<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    Query(query): Query<OutboxQuery>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let recipient = query.email
+        .map(Email::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let emails = state.email_client.read().await
+        .sent_emails(recipient.as_ref())
+        .await
+        .into_iter()
+        .map(|email| OutboxEmail {
+            recipient: email.recipient.as_ref().expose_secret().clone(),
+            subject: email.message.subject,
+            text: email.message.text,
+            html: email.message.html,
+            sent_at: email.sent_at.to_rfc3339(),
+        })
+        .collect();
+
+    Ok(Json(OutboxResponse { emails }))
+}
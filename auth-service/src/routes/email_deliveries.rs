@@ -0,0 +1,143 @@
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use axum::response::IntoResponse;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    DeliveryStatus,
+    Email,
+    EmailClient,
+    EmailMessage,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    QueuedEmail,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::routes::require_admin;
+
+#[derive(Debug, Deserialize)]
+pub struct EmailDeliveriesQuery {
+    pub email: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailDeliveriesResponse {
+    pub deliveries: Vec<EmailDelivery>,
+}
+
+/// Where an email is in the outbox. Timestamps are RFC 3339.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailDelivery {
+    pub id: String,
+    pub subject: String,
+    /// `pending`, `sent` or `dead_lettered`.
+    pub status: String,
+    pub attempts: u32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// Only set while the email is pending.
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: Option<String>,
+    #[serde(rename = "sentAt")]
+    pub sent_at: Option<String>,
+}
+
+impl From<QueuedEmail> for EmailDelivery {
+    fn from(email: QueuedEmail) -> Self {
+        Self {
+            id: email.id.as_ref().to_string(),
+            subject: email.message.subject,
+            status: email.status.as_str().to_string(),
+            attempts: email.attempts,
+            last_error: email.last_error,
+            created_at: email.created_at.to_rfc3339(),
+            next_attempt_at: (email.status == DeliveryStatus::Pending).then(|| email.next_attempt_at.to_rfc3339()),
+            sent_at: email.sent_at.map(|sent_at| sent_at.to_rfc3339()),
+        }
+    }
+}
+
+/// Lists the emails queued for `?email=`, newest first, so support staff can see whether they went out.
+///
+/// The contents aren't returned, since they hold codes and tokens.
+#[tracing::instrument(name = "Email Deliveries", skip_all)]
+pub async fn email_deliveries<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    headers: HeaderMap,
+    Query(query): Query<EmailDeliveriesQuery>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    require_admin(&headers)?;
+
+    let email = Email::parse(query.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let deliveries = state.email_outbox.read().await
+        .get_emails(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(EmailDelivery::from)
+        .collect();
+
+    Ok(Json(EmailDeliveriesResponse { deliveries }))
+}
+
+/// Puts the email in the outbox and wakes the worker that delivers it.
+/// Returns as soon as the email is stored, without waiting for the mail server.
+#[tracing::instrument(name = "Queue Email", skip_all)]
+pub(crate) async fn queue_email<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    recipient: &Email,
+    message: EmailMessage,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    state.email_outbox.write().await
+        .enqueue(QueuedEmail::new(recipient.clone(), message))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state.email_outbox_wakeup.notify_one();
+
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use axum::Json;
+use axum::response::IntoResponse;
+use crate::utils::constants::JWT_KEYRING;
+
+/// Publishes the public keys tokens are signed with, so other services can verify them offline.
+/// Keys retired by a rotation stay listed until the tokens they signed have expired.
+/// Empty when tokens are signed with the shared HS256 secret.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks() -> impl IntoResponse {
+    Json(JWT_KEYRING.jwks())
+}
@@ -1,9 +1,9 @@
-use std::str::FromStr;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::Json;
 use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use secrecy::Secret;
 use serde::{Deserialize, Serialize};
 use crate::app_state::AppState;
@@ -12,13 +12,32 @@ use crate::domain::{
     BannedTokenStore,
     Email,
     EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    Locale,
     LoginAttemptId,
+    LoginAttemptKey,
+    LoginAttemptStore,
+    LoginFailures,
+    LoginThrottle,
+    PasskeyStore,
     Password,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
     TwoFACode,
     TwoFACodeStore,
-    UserStore
+    TwoFAMethod,
+    UserStore,
+    UserStoreError
 };
-use crate::utils::auth::generate_auth_cookie;
+use crate::routes::{queue_email, ClientInfo};
+use crate::routes::refresh_token::start_session;
+use crate::utils::auth::{generate_two_fa_pending_cookie, generate_unlock_token};
+use crate::utils::email_templates::EmailTemplate;
+use crate::utils::constants::{LOGIN_THROTTLE_BY_EMAIL, LOGIN_THROTTLE_BY_IP_ADDRESS, UNLOCK_ACCOUNT_URL};
 
 #[derive(serde::Deserialize)]
 pub struct LoginRequest {
@@ -40,51 +59,284 @@ pub struct TwoFactorAuthResponse {
     pub login_attempt_id: String,
 }
 
+/// Logs the user in with their password.
+///
+/// Failed attempts are counted per email and per client IP. Past a few failures each attempt has
+/// to wait longer after the previous one, and too many lock the account for a while and email the
+/// user a link to unlock it. While an attempt isn't allowed, the response is 429 with `Retry-After`.
 #[tracing::instrument(name = "Login", skip_all)]
-pub async fn login<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn login<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
     let email = Email::parse(request.email)
         .map_err(|_| AuthAPIError::InvalidCredentials)?;
 
+    let attempt_keys = login_attempt_keys(&email, &client);
+    check_login_allowed(&state, &attempt_keys).await?;
+
+    let password = Password::parse(request.password)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Checking the password is slow, so the attempt is counted first, or concurrent ones would all get past the check above.
+    let reserved = reserve_login_attempt(&state, &attempt_keys).await?;
+
     let user_store = state.user_store.write().await;
-    user_store.validate_user(&email, &Password::parse(request.password)
-        .map_err(|_| AuthAPIError::InvalidCredentials)?)
-        .await
-        .map_err(|_| {
-            eprintln!("User validation failed");
-            AuthAPIError::InvalidCredentials
-        })?;
+    if user_store.validate_user(&email, &password).await.is_err() {
+        eprintln!("User validation failed");
+        drop(user_store);
+        if reserved.locks_account {
+            send_unlock_email(&state, &email, client.locale).await?;
+        }
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    release_login_attempt(&state, &attempt_keys, &reserved).await?;
 
     let user = user_store.get_user(&email).await
         .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    // Starting the session looks the user up again, for the new login alert.
+    drop(user_store);
+
+    if !user.verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&email, &state, &client, jar).await,
+        TwoFAMethod::Email | TwoFAMethod::Totp => {
+            let locale = user.email_locale(client.locale);
+            handle_2fa(&email, user.two_fa_method, locale, &state, jar).await
+        },
+    }
+}
+
+// Failed logins count against the account and, when it's known, the client's IP.
+fn login_attempt_keys(email: &Email, client: &ClientInfo) -> Vec<(LoginAttemptKey, LoginThrottle)> {
+    let mut keys = vec![(LoginAttemptKey::Email(email.clone()), *LOGIN_THROTTLE_BY_EMAIL)];
+    if let Some(ip_address) = &client.ip_address {
+        keys.push((LoginAttemptKey::IpAddress(ip_address.clone()), *LOGIN_THROTTLE_BY_IP_ADDRESS));
+    }
+
+    keys
+}
+
+#[tracing::instrument(name = "Check Login Allowed", skip_all)]
+async fn check_login_allowed<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    attempt_keys: &[(LoginAttemptKey, LoginThrottle)],
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let login_attempt_store = state.login_attempt_store.read().await;
+    let now = Utc::now();
+
+    let mut retry_after = None;
+    for (key, throttle) in attempt_keys {
+        let failures = login_attempt_store.get_failures(key)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        if let Some(failures) = failures {
+            retry_after = retry_after.max(throttle.retry_after(&failures, now));
+        }
+    }
+
+    match retry_after {
+        Some(seconds) => Err(AuthAPIError::TooManyLoginAttempts(seconds)),
+        None => Ok(()),
+    }
+}
+
+/// What counting a login attempt before checking its password found.
+struct ReservedLoginAttempt {
+    /// Whether this attempt locks the account unless it succeeds.
+    locks_account: bool,
+    /// The failures of each attempt key from before this attempt, to go back to if it's taken back.
+    previous: Vec<Option<LoginFailures>>,
+}
+
+#[tracing::instrument(name = "Reserve Login Attempt", skip_all)]
+async fn reserve_login_attempt<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    attempt_keys: &[(LoginAttemptKey, LoginThrottle)],
+) -> Result<ReservedLoginAttempt, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let mut login_attempt_store = state.login_attempt_store.write().await;
+    let now = Utc::now();
+
+    let mut reserved = ReservedLoginAttempt { locks_account: false, previous: Vec::new() };
+    let mut retry_after = None;
+    for (key, throttle) in attempt_keys {
+        let previous = login_attempt_store.reserve_attempt(key)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        let count = previous.map_or(0, |previous| previous.count) + 1;
+        if matches!(key, LoginAttemptKey::Email(_)) && count == throttle.lockout_failures {
+            reserved.locks_account = true;
+        }
+        if let Some(previous) = previous {
+            retry_after = retry_after.max(throttle.retry_after(&previous, now));
+        }
+        reserved.previous.push(previous);
+    }
+
+    // An attempt that came too early is taken back, so it doesn't count as a failure or push back the next
+    // allowed one. Only counted attempts reach the lockout threshold, so only one of them sends the unlock email.
+    if let Some(seconds) = retry_after {
+        for ((key, _), previous) in attempt_keys.iter().zip(&reserved.previous) {
+            login_attempt_store.release_attempt(key, *previous)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+        return Err(AuthAPIError::TooManyLoginAttempts(seconds));
+    }
+
+    Ok(reserved)
+}
 
-    match user.requires_2fa {
-        true => handle_2fa(&email, &state, jar).await,
-        false => handle_no_2fa(&email, jar).await,
+/// Takes back the attempt once the password turned out right.
+/// The IP's earlier failures stay, or logging in to one account would wipe out failures on others,
+/// and so does the time of its last one, or the login would restart the backoff of every account behind the IP.
+#[tracing::instrument(name = "Release Login Attempt", skip_all)]
+async fn release_login_attempt<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    attempt_keys: &[(LoginAttemptKey, LoginThrottle)],
+    reserved: &ReservedLoginAttempt,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let mut login_attempt_store = state.login_attempt_store.write().await;
+
+    for ((key, _), previous) in attempt_keys.iter().zip(&reserved.previous) {
+        match key {
+            LoginAttemptKey::Email(_) => login_attempt_store.reset_failures(key).await,
+            LoginAttemptKey::IpAddress(_) => login_attempt_store.release_attempt(key, *previous).await,
+        }
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
     }
+
+    Ok(())
+}
+
+/// Emails a link to [unlock_account](crate::routes::unlock_account), if the account exists.
+#[tracing::instrument(name = "Send Unlock Email", skip_all)]
+async fn send_unlock_email<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+    requested_locale: Option<Locale>,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let user = match state.user_store.read().await.get_user(email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let token = generate_unlock_token(email)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let link = format!("{}?token={}", UNLOCK_ACCOUNT_URL.as_str(), token);
+    let message = EmailTemplate::AccountLocked {
+        link: &link,
+        unlocks_in_minutes: LOGIN_THROTTLE_BY_EMAIL.lockout_seconds / 60,
+    }
+        .render(user.email_locale(requested_locale))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(state, email, message).await
 }
 
 #[tracing::instrument(name = "Handle 2FA", skip_all)]
-async fn handle_2fa<T, U, V, W>(
+pub(crate) async fn handle_2fa<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
     email: &Email,
-    state: &AppState<T, U, V, W>,
+    two_fa_method: TwoFAMethod,
+    locale: Locale,
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError>
 where T: UserStore + Clone + Send + Sync + 'static,
       U: BannedTokenStore + Clone + Send + Sync + 'static,
       V: TwoFACodeStore + Clone + Send + Sync + 'static,
       W: EmailClient + Clone + Send + Sync + 'static,
+      X: PasswordResetTokenStore + Clone + Send + Sync + 'static,
+      Y: EmailVerificationTokenStore + Clone + Send + Sync + 'static,
+      Z: RecoveryCodeStore + Clone + Send + Sync + 'static,
+      A: PasskeyStore + Clone + Send + Sync + 'static,
+      B: RefreshTokenStore + Clone + Send + Sync + 'static,
+      C: SessionStore + Clone + Send + Sync + 'static,
+      D: LoginAttemptStore + Clone + Send + Sync + 'static,
+      E: EmailOutboxStore + Clone + Send + Sync + 'static,
+      F: KnownDeviceStore + Clone + Send + Sync + 'static,
 {
 
     let login_attempt_id = LoginAttemptId::default();
+    // TOTP users are checked against their authenticator app instead,
+    // but the login attempt still has to be stored for `verify_2fa`.
     let two_fa_code = TwoFACode::default();
 
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
@@ -93,18 +345,22 @@ where T: UserStore + Clone + Send + Sync + 'static,
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    state.email_client.write().await
-        .send_email(email, "2 factor auth code", two_fa_code.to_string().as_str()).await
-        .map_err(|_| AuthAPIError::MalformedRequest)?;
+    if two_fa_method == TwoFAMethod::Email {
+        let message = EmailTemplate::TwoFACode { code: &two_fa_code }
+            .render(locale)
+            .map_err(AuthAPIError::UnexpectedError)?;
+        queue_email(state, email, message).await?;
+    }
 
     let response = TwoFactorAuthResponse {
         message: "2FA required".to_string(),
         login_attempt_id: login_attempt_id.as_ref().to_string(),
     };
-    let auth_cookie = generate_auth_cookie(&email)
-        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    // The auth cookie is only set once `verify_2fa` succeeds. Until then this cookie can't be used for anything else.
+    let pending_cookie = generate_two_fa_pending_cookie(email)
+        .map_err(AuthAPIError::UnexpectedError)?;
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(pending_cookie);
 
     let json_response = Json(LoginResponse::TwoFactorAuth(response));
 
@@ -112,14 +368,27 @@ where T: UserStore + Clone + Send + Sync + 'static,
 }
 
 #[tracing::instrument(name = "Handle no 2FA", skip_all)]
-async fn handle_no_2fa(
+pub(crate) async fn handle_no_2fa<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
     email: &Email,
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    client: &ClientInfo,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
-    let auth_cookie = generate_auth_cookie(&email)
-        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = start_session(state, email, client, jar).await?;
 
     let status = StatusCode::OK;
     let json_response = Json(LoginResponse::RegularAuth);
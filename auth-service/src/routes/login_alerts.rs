@@ -0,0 +1,130 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use chrono::Utc;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    DeviceFingerprint,
+    DeviceSighting,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{queue_email, revoke_all_sessions, ClientInfo};
+use crate::utils::auth::{generate_report_login_token, validate_report_login_token};
+use crate::utils::constants::REPORT_LOGIN_URL;
+use crate::utils::email_templates::EmailTemplate;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ReportLoginRequest {
+    pub token: String,
+}
+
+/// Handles the "this wasn't me" link from a new login alert: every session of the user is logged out,
+/// and their known devices are forgotten.
+///
+/// The password is left as is, so whoever logged in can still do so until it's reset.
+#[tracing::instrument(name = "Report Login", skip_all)]
+pub async fn report_login<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    Json(request): Json<ReportLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let claims = validate_report_login_token(&request.token, &*state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Revoking every token of the user revokes the link's token too, so it can't be used again.
+    revoke_all_sessions(&state, &email).await?;
+
+    state.known_device_store.write().await
+        .forget_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(AuthMessage::LoginReported.into_response())
+}
+
+/// Remembers the device a login came from, and emails the user when they hadn't logged in from it before.
+///
+/// Nothing is sent for the first device a user logs in from.
+#[tracing::instrument(name = "Alert If New Device", skip_all)]
+pub(crate) async fn alert_if_new_device<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+    client: &ClientInfo,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let fingerprint = DeviceFingerprint::new(client.user_agent.as_deref(), client.ip_address.as_deref());
+    let sighting = state.known_device_store.write().await
+        .add_device(email, &fingerprint)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if sighting != DeviceSighting::New {
+        return Ok(());
+    }
+
+    let user = state.user_store.read().await
+        .get_user(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let token = generate_report_login_token(email)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let link = format!("{}?token={}", REPORT_LOGIN_URL.as_str(), token);
+    let time = Utc::now().format("%Y-%m-%d %H:%M UTC").to_string();
+    let message = EmailTemplate::NewLoginAlert {
+        device: &fingerprint.device_label,
+        ip_address: client.ip_address.as_deref(),
+        time: &time,
+        link: &link,
+    }
+        .render(user.email_locale(client.locale))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(state, email, message).await
+}
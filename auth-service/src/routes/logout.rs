@@ -2,26 +2,38 @@ use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
+use secrecy::Secret;
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{AuthAPIError, BannedTokenStore, Email, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshToken, RefreshTokenStore, SessionId, SessionStore, SessionStoreError, TwoFACodeStore, UserStore};
+use crate::http_response::AuthMessage;
 use crate::utils::auth::validate_token;
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
 
 #[tracing::instrument(name = "Logout", skip_all)]
-pub async fn logout<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn logout<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
     jar: CookieJar) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
     let jar_binding = jar.to_owned();
     // get the jwt cookie from the cookie jar
-    let cookie = match jar_binding.get("jwt") {
+    let (cookie, claims) = match jar_binding.get("jwt") {
         Some(cookie) => {
             // validate the jwt token
             match validate_token(cookie.value(), state.banned_token_store.clone().read().await).await {
-                Ok(_) => cookie,
+                Ok(claims) => (cookie, claims),
                 // if the token is invalid, return an error
                 Err(_) => return Err(AuthAPIError::InvalidToken),
             }
@@ -30,14 +42,115 @@ where T: UserStore,
         None =>  return Err(AuthAPIError::MissingToken),
     };
 
-    // add the token to the banned token store
+    // add the token's ID to the banned token store
     let mut banned_token_store = state.banned_token_store.write().await;
-    let token = cookie.value();
-    banned_token_store.add_banned_token(token.to_string()).await
+    banned_token_store.add_banned_token(claims.jti).await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(banned_token_store);
+
+    // forget the session, so it's no longer listed on the user's devices
+    if let Some(session_id) = claims.sid.and_then(|sid| SessionId::parse(sid).ok()) {
+        match state.session_store.write().await.remove_session(&session_id).await {
+            Ok(()) | Err(SessionStoreError::SessionNotFound) => {},
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    }
+
+    // end the refresh token family too, so the session can't be refreshed
+    let jar = match jar.get(REFRESH_COOKIE_NAME).cloned() {
+        Some(refresh_cookie) => {
+            if let Ok(refresh_token) = RefreshToken::parse(refresh_cookie.value().to_string()) {
+                state.refresh_token_store.write().await
+                    .revoke_family(&refresh_token)
+                    .await
+                    .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+            }
+            jar.remove(refresh_cookie)
+        },
+        None => jar,
+    };
 
     // remove the jwt cookie from the cookie jar
     let jar = jar.remove(cookie.to_string());
 
     Ok((jar, StatusCode::OK.into_response()))
 }
+
+/// Logs the user out of every session, on every device.
+#[tracing::instrument(name = "Logout All", skip_all)]
+pub async fn logout_all<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let cookie = jar.get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .clone();
+
+    let claims = validate_token(cookie.value(), state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    revoke_all_sessions(&state, &email).await?;
+
+    let jar = jar.remove(cookie);
+    let jar = match jar.get(REFRESH_COOKIE_NAME).cloned() {
+        Some(refresh_cookie) => jar.remove(refresh_cookie),
+        None => jar,
+    };
+
+    Ok((jar, AuthMessage::UserLoggedOutEverywhere.into_response()))
+}
+
+/// Ends every session of the user. Tokens issued so far stop verifying and no session can be refreshed.
+pub(crate) async fn revoke_all_sessions<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    state.banned_token_store.write().await
+        .revoke_all_tokens(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state.refresh_token_store.write().await
+        .revoke_all_families(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state.session_store.write().await
+        .remove_all_sessions(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    TwoFAMethod,
+    UserStore,
+    UserStoreError
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{queue_email, ClientInfo};
+use crate::routes::login::{handle_2fa, handle_no_2fa};
+use crate::utils::auth::{generate_magic_link_token, validate_magic_link_token};
+use crate::utils::constants::MAGIC_LINK_URL;
+use crate::utils::email_templates::EmailTemplate;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ConsumeMagicLinkRequest {
+    pub token: String,
+}
+
+/// Emails the user a short-lived login link.
+///
+/// The response is the same whether or not the account exists.
+#[tracing::instrument(name = "Request Magic Link", skip_all)]
+pub async fn request_magic_link<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    client: ClientInfo,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(AuthMessage::MagicLinkSent.into_response()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let token = generate_magic_link_token(&email)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let link = format!("{}?token={}", MAGIC_LINK_URL.as_str(), token);
+
+    let message = EmailTemplate::MagicLink { link: &link }
+        .render(user.email_locale(client.locale))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(&state, &email, message).await?;
+
+    Ok(AuthMessage::MagicLinkSent.into_response())
+}
+
+/// Logs the user in with a token from [request_magic_link].
+///
+/// The link stands in for the password only, so 2FA users still get a login attempt to verify.
+#[tracing::instrument(name = "Consume Magic Link", skip_all)]
+pub async fn consume_magic_link<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<ConsumeMagicLinkRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let claims = validate_magic_link_token(&request.token, &*state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Other instances don't share our lock, so the store decides which request gets to use the link.
+    let claimed = state.banned_token_store.write().await
+        .claim_token(claims.jti)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !claimed {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let user = state.user_store.read().await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if !user.verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&email, &state, &client, jar).await,
+        TwoFAMethod::Email | TwoFAMethod::Totp => {
+            let locale = user.email_locale(client.locale);
+            handle_2fa(&email, user.two_fa_method, locale, &state, jar).await
+        },
+    }
+}
@@ -4,6 +4,22 @@ mod signup;
 mod verify_2fa;
 mod verify_token;
 mod refresh_token;
+mod password_reset;
+mod change_password;
+mod verify_email;
+mod totp;
+mod recovery_codes;
+mod passkey;
+mod magic_link;
+mod jwks;
+mod admin;
+mod sessions;
+mod unlock_account;
+mod resend_2fa;
+mod email_deliveries;
+mod login_alerts;
+#[cfg(feature = "dev-outbox")]
+mod dev_outbox;
 
 // re-export items from sub-modules
 pub use login::*;
@@ -11,4 +27,20 @@ pub use logout::*;
 pub use signup::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
-pub use refresh_token::*;
\ No newline at end of file
+pub use refresh_token::*;
+pub use password_reset::*;
+pub use change_password::*;
+pub use verify_email::*;
+pub use totp::*;
+pub use recovery_codes::*;
+pub use passkey::*;
+pub use magic_link::*;
+pub use jwks::*;
+pub use admin::*;
+pub use sessions::*;
+pub use unlock_account::*;
+pub use resend_2fa::*;
+pub use email_deliveries::*;
+pub use login_alerts::*;
+#[cfg(feature = "dev-outbox")]
+pub use dev_outbox::*;
\ No newline at end of file
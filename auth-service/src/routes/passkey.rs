@@ -0,0 +1,411 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyChallenge,
+    PasskeyChallengeId,
+    PasskeyCredential,
+    PasskeyStore,
+    PasskeyStoreError,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{authenticated_email, ClientInfo};
+use crate::routes::refresh_token::start_session;
+use crate::utils::constants::{PASSKEY_CHALLENGE_TTL_SECONDS, WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME};
+use crate::utils::webauthn::{verify_authentication, verify_registration, COSE_ALG_ES256};
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+/// Options for `navigator.credentials.create()`. Binary values are base64url encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyRegistrationOptions {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialCreationOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: PasskeyUser,
+    pub pub_key_cred_params: Vec<PublicKeyCredentialParameters>,
+    pub timeout: i64,
+    pub attestation: String,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub exclude_credentials: Vec<PublicKeyCredentialDescriptor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyUser {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyCredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyCredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+/// Options for `navigator.credentials.get()`. Binary values are base64url encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyLoginOptions {
+    /// Has to be sent back with the assertion.
+    #[serde(rename = "challengeId")]
+    pub challenge_id: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialRequestOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialRequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: i64,
+    pub user_verification: String,
+    pub allow_credentials: Vec<PublicKeyCredentialDescriptor>,
+}
+
+/// The `PublicKeyCredential` returned by `navigator.credentials.create()`, base64url encoded.
+#[derive(Debug, Deserialize)]
+pub struct PasskeyRegistrationRequest {
+    pub id: String,
+    pub response: AuthenticatorAttestationResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticatorAttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasskeyLoginRequest {
+    pub email: Secret<String>,
+    #[serde(rename = "challengeId")]
+    pub challenge_id: String,
+    /// The `PublicKeyCredential` returned by `navigator.credentials.get()`, base64url encoded.
+    pub credential: PasskeyAssertion,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasskeyAssertion {
+    pub id: String,
+    pub response: AuthenticatorAssertionResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticatorAssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+/// Issues a registration challenge for the logged-in user.
+#[tracing::instrument(name = "Start Passkey Registration", skip_all)]
+pub async fn start_passkey_registration<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = authenticated_email(&state, &jar).await?;
+    let challenge = PasskeyChallenge::default();
+
+    let mut passkey_store = state.passkey_store.write().await;
+    let existing_credentials = passkey_store.get_credentials(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    passkey_store.add_registration_challenge(&email, challenge.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let email = email.as_ref().expose_secret().to_string();
+    let options = PasskeyRegistrationOptions {
+        public_key: PublicKeyCredentialCreationOptions {
+            challenge: challenge.as_ref().to_string(),
+            rp: RelyingParty {
+                id: WEBAUTHN_RP_ID.to_string(),
+                name: WEBAUTHN_RP_NAME.to_string(),
+            },
+            user: PasskeyUser {
+                // The user handle shouldn't contain personal information, so the email is hashed.
+                id: BASE64_URL.encode(Sha256::digest(email.as_bytes())),
+                name: email.clone(),
+                display_name: email,
+            },
+            pub_key_cred_params: vec![PublicKeyCredentialParameters {
+                credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_string(),
+                alg: COSE_ALG_ES256,
+            }],
+            timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+            attestation: "none".to_string(),
+            authenticator_selection: AuthenticatorSelection {
+                // Logins don't list the user's credentials, so the authenticator has to find them by itself.
+                resident_key: "required".to_string(),
+                user_verification: "required".to_string(),
+            },
+            // Stops the same authenticator from being registered twice.
+            exclude_credentials: to_descriptors(existing_credentials),
+        },
+    };
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+/// Verifies the new credential against the registration challenge and stores it.
+#[tracing::instrument(name = "Finish Passkey Registration", skip_all)]
+pub async fn finish_passkey_registration<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    Json(request): Json<PasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = authenticated_email(&state, &jar).await?;
+
+    let mut passkey_store = state.passkey_store.write().await;
+    let challenge = passkey_store.consume_registration_challenge(&email)
+        .await
+        .map_err(|e| match e {
+            PasskeyStoreError::ChallengeNotFound => AuthAPIError::InvalidCredentials,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let registered = verify_registration(
+        &challenge,
+        &request.response.client_data_json,
+        &request.response.attestation_object,
+    )
+        .map_err(|e| {
+            tracing::warn!("Passkey registration failed: {:?}", e);
+            AuthAPIError::InvalidCredentials
+        })?;
+
+    if PasskeyCredential::parse_credential_id(&request.id).ok() != Some(registered.credential_id.clone()) {
+        return Err(AuthAPIError::MalformedRequest);
+    }
+
+    passkey_store.add_credential(PasskeyCredential {
+        credential_id: registered.credential_id,
+        email,
+        public_key: registered.public_key,
+        sign_count: registered.sign_count,
+    })
+        .await
+        .map_err(|e| match e {
+            PasskeyStoreError::CredentialAlreadyExists => AuthAPIError::MalformedRequest,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    Ok(AuthMessage::PasskeyRegistered.into_response())
+}
+
+/// Issues a login challenge, under an ID the client sends back with the assertion.
+///
+/// The options are the same for everyone. No credentials are listed, the authenticator offers
+/// the passkeys it holds for this site, so the response doesn't reveal which accounts have one.
+#[tracing::instrument(name = "Start Passkey Login", skip_all)]
+pub async fn start_passkey_login<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let challenge_id = PasskeyChallengeId::default();
+    let challenge = PasskeyChallenge::default();
+
+    state.passkey_store.write().await
+        .add_login_challenge(&challenge_id, challenge.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let options = PasskeyLoginOptions {
+        challenge_id: challenge_id.as_ref().to_string(),
+        public_key: PublicKeyCredentialRequestOptions {
+            challenge: challenge.as_ref().to_string(),
+            rp_id: WEBAUTHN_RP_ID.to_string(),
+            timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+            user_verification: "required".to_string(),
+            allow_credentials: vec![],
+        },
+    };
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+/// Verifies the assertion against the login challenge and issues the auth cookie.
+///
+/// A passkey already proves possession of a device and user verification,
+/// so it replaces both the password and the second factor.
+#[tracing::instrument(name = "Finish Passkey Login", skip_all)]
+pub async fn finish_passkey_login<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<PasskeyLoginRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+    let challenge_id = PasskeyChallengeId::parse(request.challenge_id)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+    let credential_id = PasskeyCredential::parse_credential_id(&request.credential.id)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let mut passkey_store = state.passkey_store.write().await;
+    let challenge = passkey_store.consume_login_challenge(&challenge_id)
+        .await
+        .map_err(|e| match e {
+            PasskeyStoreError::ChallengeNotFound => AuthAPIError::InvalidCredentials,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let credential = passkey_store.get_credentials(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .into_iter()
+        .find(|credential| credential.credential_id == credential_id)
+        .ok_or(AuthAPIError::InvalidCredentials)?;
+
+    let response = &request.credential.response;
+    let sign_count = verify_authentication(
+        &challenge,
+        &credential,
+        &response.client_data_json,
+        &response.authenticator_data,
+        &response.signature,
+    )
+        .map_err(|e| {
+            tracing::warn!("Passkey login failed: {:?}", e);
+            AuthAPIError::InvalidCredentials
+        })?;
+
+    passkey_store.update_sign_count(&credential.credential_id, sign_count)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(passkey_store);
+
+    let user = state.user_store.read().await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if !user.verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    let jar = start_session(&state, &email, &client, jar).await?;
+
+    Ok((jar, AuthMessage::UserLoggedIn.into_response()))
+}
+
+fn to_descriptors(credentials: Vec<PasskeyCredential>) -> Vec<PublicKeyCredentialDescriptor> {
+    credentials
+        .into_iter()
+        .map(|credential| PublicKeyCredentialDescriptor {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_string(),
+            id: credential.credential_id,
+        })
+        .collect()
+}
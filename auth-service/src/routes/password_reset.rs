@@ -0,0 +1,152 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    Password,
+    PasswordResetToken,
+    PasswordResetTokenStore,
+    PasswordResetTokenStoreError,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore,
+    UserStoreError
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{queue_email, revoke_all_sessions, ClientInfo};
+use crate::utils::constants::PASSWORD_RESET_COOLDOWN_SECONDS;
+use crate::utils::email_templates::EmailTemplate;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+/// Sends a single-use password reset token to the user's email, at most once per
+/// [PASSWORD_RESET_COOLDOWN_SECONDS] for each user.
+///
+/// The response is the same whether or not the account exists and whether or not the email was sent,
+/// so this route can't be used to find out which emails are registered.
+#[tracing::instrument(name = "Request Password Reset", skip_all)]
+pub async fn request_password_reset<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    client: ClientInfo,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(AuthMessage::PasswordResetRequested.into_response()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let issued_at = state.password_reset_token_store.read().await
+        .get_token_issued_at(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if let Some(issued_at) = issued_at {
+        let cooldown = Duration::try_seconds(PASSWORD_RESET_COOLDOWN_SECONDS)
+            .ok_or(AuthAPIError::UnexpectedError(eyre!("failed to create reset cooldown")))?;
+        if Utc::now() < issued_at + cooldown {
+            return Ok(AuthMessage::PasswordResetRequested.into_response());
+        }
+    }
+
+    let token = PasswordResetToken::default();
+
+    state.password_reset_token_store.write().await
+        .add_token(&email, token.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let message = EmailTemplate::PasswordReset { token: token.as_ref() }
+        .render(user.email_locale(client.locale))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(&state, &email, message).await?;
+
+    Ok(AuthMessage::PasswordResetRequested.into_response())
+}
+
+/// Sets a new password using a token from [request_password_reset].
+///
+/// Every token issued to the user before the reset is revoked.
+#[tracing::instrument(name = "Confirm Password Reset", skip_all)]
+pub async fn confirm_password_reset<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let token = PasswordResetToken::parse(request.token)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+    let password = Password::parse(request.new_password)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let email = state.password_reset_token_store.write().await
+        .consume_token(&token)
+        .await
+        .map_err(|e| match e {
+            PasswordResetTokenStoreError::TokenNotFound => AuthAPIError::InvalidToken,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    state.user_store.write().await
+        .update_password(&email, password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    revoke_all_sessions(&state, &email).await?;
+
+    Ok(AuthMessage::PasswordReset.into_response())
+}
@@ -0,0 +1,114 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCode,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    TwoFAMethod,
+    UserStore
+};
+use crate::routes::authenticated_email;
+use crate::utils::constants::RECOVERY_CODE_COUNT;
+
+/// Success response for every route that hands out a new set of recovery codes.
+/// This is the only time the plaintext codes are ever shown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub message_body: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+impl RecoveryCodesResponse {
+    pub(crate) fn new(message: &str, codes: Vec<RecoveryCode>) -> Self {
+        Self {
+            message_body: message.to_string(),
+            recovery_codes: codes.iter().map(|code| code.as_ref().to_string()).collect(),
+        }
+    }
+}
+
+/// Replaces the logged-in user's recovery codes with a fresh set, invalidating the old ones.
+#[tracing::instrument(name = "Regenerate Recovery Codes", skip_all)]
+pub async fn regenerate_recovery_codes<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+) -> Result<Response, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = authenticated_email(&state, &jar).await?;
+
+    let user = state.user_store.read().await
+        .get_user(&email).await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Recovery codes only stand in for a second factor, so there's nothing to recover without one.
+    if user.two_fa_method == TwoFAMethod::None {
+        return Err(AuthAPIError::MalformedRequest);
+    }
+
+    let codes = issue_recovery_codes(&state, &email).await?;
+
+    let response = RecoveryCodesResponse::new("Recovery codes regenerated successfully!", codes);
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+/// Generates and stores a new set of recovery codes for the user, replacing any previous set.
+pub(crate) async fn issue_recovery_codes<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+) -> Result<Vec<RecoveryCode>, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect();
+
+    state.recovery_code_store.write().await
+        .replace_codes(email, &codes)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(codes)
+}
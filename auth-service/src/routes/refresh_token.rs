@@ -1,61 +1,118 @@
-use std::str::FromStr;
 use axum::extract::State;
-use axum::http::StatusCode;
-use axum::Json;
+use axum::response::IntoResponse;
 use axum_extra::extract::CookieJar;
-use secrecy::Secret;
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, BannedTokenStore, Email, EmailClient, TwoFACodeStore, UserStore};
-use crate::utils::auth::{generate_auth_cookie, validate_token};
-use crate::utils::constants::JWT_COOKIE_NAME;
-
-#[derive(Debug, serde::Deserialize)]
-pub struct RefreshTokenRequest {
-    pub email: Secret<String>,
-    pub token: String,
-}
+use crate::domain::{AuthAPIError, BannedTokenStore, Email, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshToken, RefreshTokenStore, RefreshTokenStoreError, Session, SessionStore, SessionStoreError, TwoFACodeStore, UserStore};
+use crate::http_response::AuthMessage;
+use crate::routes::{alert_if_new_device, ClientInfo};
+use crate::utils::auth::{create_refresh_cookie, generate_auth_cookie};
+use crate::utils::constants::REFRESH_COOKIE_NAME;
 
+/// Trades the refresh token cookie for a new access token, rotating the refresh token as well.
+/// The session's last seen time is updated, and refreshing a revoked session fails.
+///
+/// Replaying a refresh token that was already rotated ends that login on every device holding it.
 #[tracing::instrument(name = "Refresh Token", skip_all)]
-pub async fn refresh_token<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn refresh_token<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
     jar: CookieJar,
-    Json(request): Json<RefreshTokenRequest>,
-) -> Result<(CookieJar, StatusCode), AuthAPIError>
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
+    let cookie = jar.get(REFRESH_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+    let current = RefreshToken::parse(cookie.value().to_string())
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let next = RefreshToken::default();
 
-    let token = request.token;
-
-    let email = Email::parse(request.email)
-        .map_err(|_| AuthAPIError::InvalidCredentials)?;
-
-    state.user_store.read().await
-        .get_user(&email).await
-        .map_err(|_| AuthAPIError::InvalidCredentials)?;
-
-
-    validate_token(&token, state.banned_token_store.clone().read().await)
+    let (email, session_id) = state.refresh_token_store.write().await
+        .rotate_token(&current, &next)
         .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+        .map_err(|e| match e {
+            RefreshTokenStoreError::TokenNotFound => AuthAPIError::InvalidToken,
+            RefreshTokenStoreError::TokenReused => {
+                tracing::warn!("Refresh token reused, revoked its family");
+                AuthAPIError::InvalidToken
+            },
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
 
-    let mut banned_token_store = state.banned_token_store.write().await;
-    banned_token_store.add_banned_token(token.clone()).await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    // The refresh token outlives a session that was revoked, so it has to be checked here.
+    match state.session_store.write().await.touch_session(&session_id).await {
+        Ok(()) => {},
+        Err(SessionStoreError::SessionNotFound) => {
+            state.refresh_token_store.write().await
+                .revoke_family(&next)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+            return Err(AuthAPIError::InvalidToken);
+        },
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
 
-    let prev_cookie = jar.get(JWT_COOKIE_NAME)
-        .ok_or(AuthAPIError::InvalidToken)?
-        .clone();
+    let auth_cookie = generate_auth_cookie(&email, Some(&session_id))
+        .map_err(AuthAPIError::UnexpectedError)?;
 
-    let updated_jar = jar.remove(prev_cookie);
+    let jar = jar
+        .add(auth_cookie)
+        .add(create_refresh_cookie(&next));
+
+    Ok((jar, AuthMessage::TokenRefreshed.into_response()))
+}
 
+/// Records a session for a completed login and sets its access and refresh token cookies.
+/// The user is emailed if the login came from a device they hadn't used before.
+pub(crate) async fn start_session<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+    client: &ClientInfo,
+    jar: CookieJar,
+) -> Result<CookieJar, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let session = Session::new(email.clone(), client.user_agent.clone(), client.ip_address.clone());
+    let session_id = session.id.clone();
+    state.session_store.write().await
+        .add_session(session)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    let auth_cookie = generate_auth_cookie(&email)
+    let refresh_token = RefreshToken::default();
+    state.refresh_token_store.write().await
+        .create_family(email, &session_id, &refresh_token)
+        .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
-    let updated_jar = updated_jar.add(auth_cookie);
+    alert_if_new_device(state, email, client).await?;
 
-    Ok((updated_jar, StatusCode::OK))
-}
\ No newline at end of file
+    let auth_cookie = generate_auth_cookie(email, Some(&session_id))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(jar
+        .add(auth_cookie)
+        .add(create_refresh_cookie(&refresh_token)))
+}
@@ -0,0 +1,118 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::{ExposeSecret, Secret};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptId,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACode,
+    TwoFACodeStore,
+    TwoFAMethod,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{queue_email, ClientInfo};
+use crate::utils::auth::validate_two_fa_pending_token;
+use crate::utils::email_templates::EmailTemplate;
+use crate::utils::constants::{MAX_TWO_FA_RESENDS, TWO_FA_PENDING_COOKIE_NAME, TWO_FA_RESEND_COOLDOWN_SECONDS};
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Resend2FARequest {
+    email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    login_attempt_id: String,
+}
+
+/// Emails a fresh 2FA code for a pending login attempt.
+///
+/// The new code replaces the old one, but the attempt keeps its expiry and failed attempts.
+/// Codes can be resent once every [TWO_FA_RESEND_COOLDOWN_SECONDS], at most [MAX_TWO_FA_RESENDS] times per attempt.
+#[tracing::instrument(name = "Resend 2FA", skip_all)]
+pub async fn resend_2fa<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let pending_cookie = jar.get(TWO_FA_PENDING_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+    let pending_claims = validate_two_fa_pending_token(pending_cookie.value(), &*state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if &pending_claims.sub != email.as_ref().expose_secret() {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let user = state.user_store.read().await
+        .get_user(&email).await
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // TOTP codes come from the user's authenticator app, so there's nothing to resend.
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::MalformedRequest);
+    }
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let (stored_login_attempt_id, _) = two_fa_code_store.get_code(&email).await
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if stored_login_attempt_id != login_attempt_id {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let (resends, sent_at) = two_fa_code_store.get_resends(&email).await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let cooldown = Duration::try_seconds(TWO_FA_RESEND_COOLDOWN_SECONDS)
+        .ok_or(AuthAPIError::UnexpectedError(eyre!("failed to create resend cooldown")))?;
+    if resends >= MAX_TWO_FA_RESENDS || Utc::now() < sent_at + cooldown {
+        return Err(AuthAPIError::TooManyRequests);
+    }
+
+    let two_fa_code = TwoFACode::default();
+    two_fa_code_store.replace_code(&email, two_fa_code.clone()).await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(two_fa_code_store);
+
+    let message = EmailTemplate::TwoFACode { code: &two_fa_code }
+        .render(user.email_locale(client.locale))
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(&state, &email, message).await?;
+
+    Ok(AuthMessage::TwoFACodeResent.into_response())
+}
@@ -0,0 +1,200 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use axum::extract::{ConnectInfo, FromRequestParts, Path, State};
+use axum::http::header::{ACCEPT_LANGUAGE, USER_AGENT};
+use axum::http::request::Parts;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    Locale,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    Session,
+    SessionId,
+    SessionStore,
+    SessionStoreError,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::routes::authenticated_claims;
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+
+/// The device a request came from, recorded when it starts a session.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// The supported language the client ranks highest in `Accept-Language`.
+    pub locale: Option<Locale>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts.headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let ip_address = parts.extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+        let locale = parts.headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Locale::from_accept_language);
+
+        Ok(Self { user_agent, ip_address, locale })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "deviceLabel")]
+    pub device_label: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    /// RFC 3339 timestamp.
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// RFC 3339 timestamp.
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: String,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: Option<&str>) -> Self {
+        Self {
+            current: current_session_id == Some(session.id.as_ref()),
+            id: session.id.as_ref().to_string(),
+            device_label: session.device_label,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at.to_rfc3339(),
+            last_seen_at: session.last_seen_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Lists the devices the user is logged in on, most recently seen first.
+#[tracing::instrument(name = "List Sessions", skip_all)]
+pub async fn list_sessions<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let claims = authenticated_claims(&state, &jar).await?;
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let sessions = state.session_store.read().await
+        .get_sessions(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|session| SessionResponse::new(session, claims.sid.as_deref()))
+        .collect();
+
+    Ok(Json(SessionsResponse { sessions }))
+}
+
+/// Logs the user out on one device.
+///
+/// The session's access tokens stop verifying and its refresh token can't be used anymore.
+/// Revoking the current session removes the auth cookies too.
+#[tracing::instrument(name = "Revoke Session", skip_all)]
+pub async fn revoke_session<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let claims = authenticated_claims(&state, &jar).await?;
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id = SessionId::parse(id)
+        .map_err(|_| AuthAPIError::SessionNotFound)?;
+
+    let mut session_store = state.session_store.write().await;
+    let session = session_store.get_session(&session_id)
+        .await
+        .map_err(|e| match e {
+            SessionStoreError::SessionNotFound => AuthAPIError::SessionNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    // Other users' sessions look the same as ones that don't exist.
+    if session.email != email {
+        return Err(AuthAPIError::SessionNotFound);
+    }
+
+    session_store.remove_session(&session_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(session_store);
+
+    state.banned_token_store.write().await
+        .add_banned_token(session_id.as_ref().to_string())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let jar = if claims.sid.as_deref() == Some(session_id.as_ref()) {
+        let jar = jar.remove(JWT_COOKIE_NAME);
+        jar.remove(REFRESH_COOKIE_NAME)
+    } else {
+        jar
+    };
+
+    Ok((jar, AuthMessage::SessionRevoked.into_response()))
+}
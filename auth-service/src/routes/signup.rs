@@ -1,6 +1,7 @@
 use axum::{
     Json,
-    response::IntoResponse,
+    http::StatusCode,
+    response::{IntoResponse, Response},
     extract::State,
 };
 use secrecy::Secret;
@@ -9,13 +10,15 @@ use crate::{
     app_state::AppState,
     domain::{
         User,
-        AuthAPIError
+        AuthAPIError,
+        TwoFAMethod,
     },
     http_response::{
         AuthMessage
     },
+    routes::{issue_recovery_codes, send_verification_email, ClientInfo, RecoveryCodesResponse},
 };
-use crate::domain::{BannedTokenStore, Email, EmailClient, Password, TwoFACodeStore, UserStore};
+use crate::domain::{BannedTokenStore, Email, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, Locale, LoginAttemptStore, PasskeyStore, Password, PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TwoFACodeStore, UserStore};
 
 #[derive(Deserialize, Debug)]
 pub struct SignupRequest {
@@ -23,6 +26,9 @@ pub struct SignupRequest {
     pub password: Secret<String>,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    /// Language for the user's emails. Without one, each email follows the request's `Accept-Language`.
+    #[serde(rename = "preferredLanguage")]
+    pub preferred_language: Option<String>,
 }
 
 /// signup route handler
@@ -32,22 +38,43 @@ pub struct SignupRequest {
 ///
 /// - see also [app_state.rs](crate::app_state::AppState)
 #[tracing::instrument(name = "Signup", skip_all)]
-pub async fn signup<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn signup<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    client: ClientInfo,
     Json(request): Json<SignupRequest>,
-) -> Result<impl IntoResponse, AuthAPIError>
+) -> Result<Response, AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
     let email = Email::parse(request.email)
         .map_err(|_| AuthAPIError::MalformedRequest)?;
     let password = Password::parse(request.password)
         .map_err(|_| AuthAPIError::MalformedRequest)?;
+    let locale = request.preferred_language
+        .map(|language| Locale::parse(&language))
+        .transpose()
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    // Authenticator app 2FA needs its own enrollment, so signup can only opt into emailed codes.
+    let two_fa_method = match request.requires_2fa {
+        true => TwoFAMethod::Email,
+        false => TwoFAMethod::None,
+    };
 
     // Create a new `User` instance using data in the `request`
-    let user = User::new(email, password, request.requires_2fa)?;
+    let mut user = User::new(email, password, two_fa_method)?;
+    user.locale = locale;
 
     let mut user_store = state.user_store.write().await;
 
@@ -55,10 +82,22 @@ where T: UserStore,
         return Err(AuthAPIError::UserAlreadyExists);
     }
 
-    match user_store.add_user(user).await {
-        Ok(_) => {
-            Ok(AuthMessage::UserCreated.into_response())
-        },
-        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    let email = user.email.clone();
+    let email_locale = user.email_locale(client.locale);
+
+    user_store.add_user(user).await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(user_store);
+
+    // The user can't log in until they follow the link in this email.
+    send_verification_email(&state, &email, email_locale).await?;
+
+    if two_fa_method == TwoFAMethod::None {
+        return Ok(AuthMessage::UserCreated.into_response());
     }
+
+    // 2FA users get recovery codes up front, in case they lose access to their inbox.
+    let codes = issue_recovery_codes(&state, &email).await?;
+    let response = RecoveryCodesResponse::new("User created successfully!", codes);
+    Ok((StatusCode::CREATED, Json(response)).into_response())
 }
\ No newline at end of file
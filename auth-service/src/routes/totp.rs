@@ -0,0 +1,209 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::IntoResponse;
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TotpSecret,
+    TwoFACode,
+    TwoFACodeStore,
+    TwoFAMethod,
+    UserStore
+};
+use crate::routes::{issue_recovery_codes, RecoveryCodesResponse};
+use crate::utils::auth::{validate_token, Claims};
+use crate::utils::constants::JWT_COOKIE_NAME;
+use crate::utils::totp::{generate_otpauth_uri, verify_totp_code};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmTotpRequest {
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
+
+/// Starts authenticator app enrollment for the logged-in user.
+///
+/// A new secret is generated on every call, replacing any unconfirmed one,
+/// but the user keeps their current 2FA method until [confirm_totp] succeeds.
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = authenticated_email(&state, &jar).await?;
+
+    let mut user_store = state.user_store.write().await;
+    let user = user_store.get_user(&email).await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Replacing an active secret would break the user's authenticator app before they re-enroll.
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    let secret = TotpSecret::default();
+    let otpauth_uri = generate_otpauth_uri(&secret, &email)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    user_store.set_totp_secret(&email, secret.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = TotpEnrollmentResponse {
+        secret: secret.as_ref().expose_secret().to_string(),
+        otpauth_uri,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Finishes enrollment by checking a code from the authenticator app,
+/// then switches the user's 2FA method to TOTP and issues a new set of recovery codes.
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = authenticated_email(&state, &jar).await?;
+
+    let two_fa_code = TwoFACode::parse(request.two_fa_code)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let mut user_store = state.user_store.write().await;
+    let user = user_store.get_user(&email).await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    // Confirming without enrolling first is a client error, not a wrong code.
+    let secret = user.totp_secret
+        .ok_or(AuthAPIError::MalformedRequest)?;
+
+    let code_is_valid = match verify_totp_code(&secret, &email, &two_fa_code)
+        .map_err(AuthAPIError::UnexpectedError)? {
+        // Used up, so the code can't log in right after it confirmed the app.
+        Some(step) => user_store.accept_totp_step(&email, step)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?,
+        None => false,
+    };
+    if !code_is_valid {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    user_store.set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(user_store);
+
+    let codes = issue_recovery_codes(&state, &email).await?;
+
+    let response = RecoveryCodesResponse::new("Authenticator app 2FA enabled successfully!", codes);
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Returns the email of the user the request's auth cookie was issued to.
+pub(crate) async fn authenticated_email<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    jar: &CookieJar,
+) -> Result<Email, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let claims = authenticated_claims(state, jar).await?;
+
+    Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)
+}
+
+/// Returns the validated claims of the request's auth cookie.
+pub(crate) async fn authenticated_claims<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    jar: &CookieJar,
+) -> Result<Claims, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let cookie = jar.get(JWT_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    validate_token(cookie.value(), state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)
+}
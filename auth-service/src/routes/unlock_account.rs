@@ -0,0 +1,74 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationTokenStore,
+    KnownDeviceStore,
+    LoginAttemptKey,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore
+};
+use crate::http_response::AuthMessage;
+use crate::utils::auth::validate_unlock_token;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: String,
+}
+
+/// Ends a login lockout early, with the token emailed when the account was locked.
+///
+/// Only the account's failed logins are forgotten. The client IP's are not.
+#[tracing::instrument(name = "Unlock Account", skip_all)]
+pub async fn unlock_account<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    // Hold the write lock between checking and banning the token, so it can't be used twice.
+    let mut banned_token_store = state.banned_token_store.write().await;
+    let claims = validate_unlock_token(&request.token, &*banned_token_store)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    banned_token_store.add_banned_token(claims.jti)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(banned_token_store);
+
+    let email = Email::parse(Secret::new(claims.sub))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    state.login_attempt_store.write().await
+        .reset_failures(&LoginAttemptKey::Email(email))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(AuthMessage::AccountUnlocked.into_response())
+}
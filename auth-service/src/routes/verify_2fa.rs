@@ -1,9 +1,14 @@
-use std::str::FromStr;
 use axum::{http::StatusCode, response::IntoResponse, Json};
 use axum::extract::State;
-use secrecy::Secret;
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, BannedTokenStore, Email, EmailClient, LoginAttemptId, TwoFACode, TwoFACodeStore, UserStore};
+use crate::domain::{AuthAPIError, BannedTokenStore, Email, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptId, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError, RefreshTokenStore, SessionStore, TwoFACode, TwoFACodeStore, TwoFAMethod, UserStore};
+use crate::routes::ClientInfo;
+use crate::routes::refresh_token::start_session;
+use crate::utils::auth::validate_two_fa_pending_token;
+use crate::utils::constants::{MAX_TWO_FA_ATTEMPTS, TWO_FA_PENDING_COOKIE_NAME};
+use crate::utils::totp::verify_totp_code;
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Verify2FARequest {
@@ -14,15 +19,37 @@ pub struct Verify2FARequest {
     two_fac_code: String,
 }
 
+/// What the user sent in the `2FACode` field.
+enum SecondFactor {
+    Code(TwoFACode),
+    /// Accepted in place of a code for any 2FA method, and only once.
+    RecoveryCode(RecoveryCode),
+}
+
+/// Completes a login with the second factor for its login attempt.
+///
+/// The 2FA pending cookie set by the login is exchanged for the auth and refresh token cookies.
+/// After [MAX_TWO_FA_ATTEMPTS] wrong codes the login attempt is dropped, so the user has to log in again.
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
-pub async fn verify_2fa<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn verify_2fa<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<Verify2FARequest>
-) -> Result<impl IntoResponse, AuthAPIError>
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
     let email = Email::parse(request.email)
         .map_err(|_| AuthAPIError::MalformedRequest)?;
@@ -30,8 +57,26 @@ where T: UserStore,
     let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
         .map_err(|_| AuthAPIError::MalformedRequest)?;
 
-    let two_fac_code = TwoFACode::parse(request.two_fac_code)
-        .map_err(|_| AuthAPIError::MalformedRequest)?;
+    let second_factor = match TwoFACode::parse(request.two_fac_code.clone()) {
+        Ok(code) => SecondFactor::Code(code),
+        Err(_) => RecoveryCode::parse(request.two_fac_code)
+            .map(SecondFactor::RecoveryCode)
+            .map_err(|_| AuthAPIError::MalformedRequest)?,
+    };
+
+    let pending_cookie = jar.get(TWO_FA_PENDING_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?
+        .clone();
+    let pending_claims = validate_two_fa_pending_token(pending_cookie.value(), &*state.banned_token_store.read().await)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if &pending_claims.sub != email.as_ref().expose_secret() {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let user = state.user_store.read().await
+        .get_user(&email).await
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
 
     let mut two_fac_code_store = state.two_fa_code_store.write().await;
     let stored_code_tuple = two_fac_code_store.get_code(&email).await
@@ -39,16 +84,68 @@ where T: UserStore,
 
     return match stored_code_tuple {
         Ok((logon_attempt, tfa_code)) => {
-            if logon_attempt == login_attempt_id && tfa_code == two_fac_code {
+            // Check the login attempt first so a recovery code isn't used up on a stale attempt.
+            if logon_attempt != login_attempt_id {
+                return Err(AuthAPIError::InvalidCredentials);
+            }
+
+            let code_is_valid = match second_factor {
+                SecondFactor::RecoveryCode(recovery_code) => {
+                    match state.recovery_code_store.write().await
+                        .consume_code(&email, &recovery_code).await {
+                        Ok(()) => true,
+                        Err(RecoveryCodeStoreError::CodeNotFound) => false,
+                        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+                    }
+                },
+                // TOTP codes come from the user's authenticator app, not the stored code.
+                SecondFactor::Code(two_fac_code) if user.two_fa_method == TwoFAMethod::Totp => {
+                    let secret = user.totp_secret
+                        .ok_or(AuthAPIError::InvalidCredentials)?;
+                    match verify_totp_code(&secret, &email, &two_fac_code)
+                        .map_err(AuthAPIError::UnexpectedError)? {
+                        Some(step) => state.user_store.write().await
+                            .accept_totp_step(&email, step)
+                            .await
+                            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?,
+                        None => false,
+                    }
+                },
+                SecondFactor::Code(two_fac_code) => tfa_code == two_fac_code,
+            };
+
+            if code_is_valid {
                 // Remove the code from the store, so it can't be used again.
                 two_fac_code_store.remove_code(&email).await
                     .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+                drop(two_fac_code_store);
+
+                // Claiming is one step, so of concurrent requests with the pending token only one starts a session.
+                let claimed = state.banned_token_store.write().await
+                    .claim_token(pending_claims.jti)
+                    .await
+                    .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+                if !claimed {
+                    return Err(AuthAPIError::InvalidToken);
+                }
+
+                let jar = jar.remove(pending_cookie);
+                let jar = start_session(&state, &email, &client, jar).await?;
 
-                Ok(StatusCode::OK)
+                Ok((jar, StatusCode::OK))
             } else {
+                let failed_attempts = two_fac_code_store.record_failed_attempt(&email).await
+                    .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+                if failed_attempts >= MAX_TWO_FA_ATTEMPTS {
+                    two_fac_code_store.remove_code(&email).await
+                        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+                    return Err(AuthAPIError::TooManyTwoFAAttempts);
+                }
+
                 Err(AuthAPIError::InvalidCredentials)
             }
         },
         Err(_) => Err(AuthAPIError::InvalidCredentials)
     };
-}
\ No newline at end of file
+}
@@ -0,0 +1,166 @@
+use axum::extract::State;
+use axum::Json;
+use axum::response::IntoResponse;
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::Secret;
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError,
+    BannedTokenStore,
+    Email,
+    EmailClient,
+    EmailOutboxStore,
+    EmailVerificationToken,
+    EmailVerificationTokenStore,
+    EmailVerificationTokenStoreError,
+    KnownDeviceStore,
+    Locale,
+    LoginAttemptStore,
+    PasskeyStore,
+    PasswordResetTokenStore,
+    RecoveryCodeStore,
+    RefreshTokenStore,
+    SessionStore,
+    TwoFACodeStore,
+    UserStore,
+    UserStoreError
+};
+use crate::http_response::AuthMessage;
+use crate::routes::{queue_email, ClientInfo};
+use crate::utils::constants::EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS;
+use crate::utils::email_templates::EmailTemplate;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: Secret<String>,
+}
+
+#[tracing::instrument(name = "Verify Email", skip_all)]
+pub async fn verify_email<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let token = EmailVerificationToken::parse(request.token)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let email = state.email_verification_token_store.write().await
+        .consume_token(&token)
+        .await
+        .map_err(|e| match e {
+            EmailVerificationTokenStoreError::TokenNotFound => AuthAPIError::InvalidToken,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    state.user_store.write().await
+        .mark_email_verified(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(AuthMessage::EmailVerified.into_response())
+}
+
+/// Sends a new verification email, at most once per
+/// [EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS] for each user.
+///
+/// Unknown and already verified emails, and resends during the cooldown, get the same response
+/// as a successful resend, so it doesn't tell which accounts exist.
+#[tracing::instrument(name = "Resend Verification", skip_all)]
+pub async fn resend_verification<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
+    client: ClientInfo,
+    Json(request): Json<ResendVerificationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let email = Email::parse(request.email)
+        .map_err(|_| AuthAPIError::MalformedRequest)?;
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if !user.verified => user,
+        Ok(_) | Err(UserStoreError::UserNotFound) => return Ok(AuthMessage::VerificationEmailSent.into_response()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let issued_at = state.email_verification_token_store.read().await
+        .get_token_issued_at(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if let Some(issued_at) = issued_at {
+        let cooldown = Duration::try_seconds(EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS)
+            .ok_or(AuthAPIError::UnexpectedError(eyre!("failed to create resend cooldown")))?;
+        if Utc::now() < issued_at + cooldown {
+            return Ok(AuthMessage::VerificationEmailSent.into_response());
+        }
+    }
+
+    send_verification_email(&state, &email, user.email_locale(client.locale)).await?;
+
+    Ok(AuthMessage::VerificationEmailSent.into_response())
+}
+
+#[tracing::instrument(name = "Send Verification Email", skip_all)]
+pub(crate) async fn send_verification_email<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    state: &AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>,
+    email: &Email,
+    locale: Locale,
+) -> Result<(), AuthAPIError>
+where T: UserStore,
+      U: BannedTokenStore,
+      V: TwoFACodeStore,
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
+{
+    let token = EmailVerificationToken::default();
+
+    state.email_verification_token_store.write().await
+        .add_token(email, token.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let message = EmailTemplate::EmailVerification { token: token.as_ref() }
+        .render(locale)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    queue_email(state, email, message).await
+}
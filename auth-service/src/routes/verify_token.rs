@@ -2,7 +2,7 @@ use axum::extract::State;
 use axum::http::StatusCode;
 use axum::Json;
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{AuthAPIError, BannedTokenStore, EmailClient, EmailOutboxStore, EmailVerificationTokenStore, KnownDeviceStore, LoginAttemptStore, PasskeyStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TwoFACodeStore, UserStore};
 use crate::utils;
 
 #[derive(Debug, serde::Deserialize)]
@@ -11,14 +11,23 @@ pub struct VerifyTokenRequest {
 }
 
 #[tracing::instrument(name = "Verify Token", skip_all)]
-pub async fn verify_token<T, U, V, W>(
-    State(state): State<AppState<T, U, V, W>>,
+pub async fn verify_token<T, U, V, W, X, Y, Z, A, B, C, D, E, F>(
+    State(state): State<AppState<T, U, V, W, X, Y, Z, A, B, C, D, E, F>>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<StatusCode, AuthAPIError>
 where T: UserStore,
       U: BannedTokenStore,
       V: TwoFACodeStore,
-      W: EmailClient
+      W: EmailClient,
+      X: PasswordResetTokenStore,
+      Y: EmailVerificationTokenStore,
+      Z: RecoveryCodeStore,
+      A: PasskeyStore,
+      B: RefreshTokenStore,
+      C: SessionStore,
+      D: LoginAttemptStore,
+      E: EmailOutboxStore,
+      F: KnownDeviceStore
 {
     let token = request.token;
 
@@ -1,36 +1,59 @@
-use crate::domain::{BannedTokenStore, UserStoreError};
-use std::collections::HashSet;
+use crate::domain::{BannedTokenStore, Email};
+use std::collections::{HashMap, HashSet};
+use chrono::Utc;
 use color_eyre::eyre::eyre;
 use crate::services::BannedTokenStoreError;
 
 #[derive(Debug, Default, Clone)]
 pub struct HashSetBannedTokenStore {
     banned_tokens: HashSet<String>,
+    tokens_revoked_at: HashMap<Email, usize>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashSetBannedTokenStore {
-    async fn add_banned_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        if self.banned_tokens.contains(&token) {
+    async fn add_banned_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        if self.banned_tokens.contains(&jti) {
             return Err(BannedTokenStoreError::UnexpectedError(eyre!("Token already banned")));
         }
-        self.banned_tokens.insert(token);
+        self.banned_tokens.insert(jti);
         Ok(())
     }
 
-    async fn is_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.banned_tokens.contains(token))
+    async fn claim_token(&mut self, jti: String) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.banned_tokens.insert(jti))
+    }
+
+    async fn is_banned(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.banned_tokens.contains(jti))
+    }
+
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        let now = Utc::now().timestamp_millis() as usize;
+        self.tokens_revoked_at.insert(email.clone(), now);
+        Ok(())
+    }
+
+    async fn get_tokens_revoked_at(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError> {
+        Ok(self.tokens_revoked_at.get(email).copied())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use secrecy::Secret;
+    use crate::services::data_stores::behavior_tests;
     use super::*;
 
     fn create_banned_token_store() -> HashSetBannedTokenStore {
         HashSetBannedTokenStore::default()
     }
 
+    #[tokio::test]
+    async fn test_behaves_like_a_banned_token_store() {
+        behavior_tests::banned_token_store_behaves(create_banned_token_store(), &behavior_tests::random_email()).await;
+    }
+
     #[tokio::test]
     async fn test_add_banned_token() {
         let mut store = create_banned_token_store();
@@ -46,4 +69,19 @@ mod tests {
         store.banned_tokens.insert(token.clone());
         assert!(store.is_banned(&token).await.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_revoke_all_tokens() {
+        let mut store = create_banned_token_store();
+        let email = Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email");
+        assert_eq!(store.get_tokens_revoked_at(&email).await.unwrap(), None);
+
+        let before = Utc::now().timestamp_millis() as usize;
+        store.revoke_all_tokens(&email).await.unwrap();
+
+        let revoked_at = store.get_tokens_revoked_at(&email).await.unwrap()
+            .expect("Revocation time should be set");
+        assert!(revoked_at >= before);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,174 @@
+//! Checks every implementation of a store trait has to pass, so they can be swapped for one another.
+//!
+//! Stores backed by Redis or Postgres share them with other tests and runs,
+//! so everything here uses fresh emails and tokens.
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    BannedTokenStore,
+    Email,
+    LoginAttemptId,
+    LoginAttemptKey,
+    LoginAttemptStore,
+    LoginFailures,
+    Password,
+    TwoFACode,
+    TwoFACodeStore,
+    TwoFACodeStoreError,
+    TwoFAMethod,
+    User,
+    UserStore
+};
+use crate::get_postgres_pool;
+use crate::services::PostgresUserStore;
+use crate::utils::constants::DATABASE_URL;
+
+pub fn random_email() -> Email {
+    Email::parse(Secret::new(format!("{}@somedomain.com", Uuid::new_v4())))
+        .expect("Failed to create Email")
+}
+
+pub fn random_jti() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// A pool for the database at DATABASE_URL, with every migration run.
+pub async fn get_test_pool() -> PgPool {
+    let pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool");
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+    pool
+}
+
+/// Token revocations reference the user, so Postgres needs one to revoke tokens of.
+pub async fn add_test_user(pool: &PgPool) -> Email {
+    let email = random_email();
+    let password = Password::parse(Secret::new("password".to_string()))
+        .expect("Failed to create Password");
+    let user = User::new(email.clone(), password, TwoFAMethod::None)
+        .expect("Failed to create User");
+    PostgresUserStore::new(pool.clone())
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+    email
+}
+
+/// `email` has to be one whose tokens the store can revoke.
+pub async fn banned_token_store_behaves<S: BannedTokenStore>(mut store: S, email: &Email) {
+    let jti = random_jti();
+    assert!(!store.is_banned(&jti).await.unwrap());
+
+    store.add_banned_token(jti.clone()).await.unwrap();
+    assert!(store.is_banned(&jti).await.unwrap());
+    assert!(!store.is_banned(&random_jti()).await.unwrap());
+
+    let jti = random_jti();
+    assert!(store.claim_token(jti.clone()).await.unwrap());
+    assert!(store.is_banned(&jti).await.unwrap());
+    assert!(!store.claim_token(jti).await.unwrap());
+
+    assert_eq!(store.get_tokens_revoked_at(email).await.unwrap(), None);
+
+    let before = Utc::now().timestamp_millis() as usize;
+    store.revoke_all_tokens(email).await.unwrap();
+
+    let revoked_at = store.get_tokens_revoked_at(email).await.unwrap()
+        .expect("Revocation time should be set");
+    assert!(revoked_at >= before);
+    assert_eq!(store.get_tokens_revoked_at(&random_email()).await.unwrap(), None);
+}
+
+pub async fn two_fa_code_store_behaves<S: TwoFACodeStore>(mut store: S) {
+    let email = random_email();
+    let login_attempt_id = LoginAttemptId::default();
+    let code = TwoFACode::parse("123456".to_string()).unwrap();
+
+    assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.replace_code(&email, TwoFACode::default()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+    store.add_code(&email, login_attempt_id.clone(), code.clone())
+        .await.expect("Failed to add code");
+    assert_eq!(store.get_code(&email).await, Ok((login_attempt_id.clone(), code)));
+    assert_eq!(store.get_code(&random_email()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+    assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+    let (resends, first_sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+    assert_eq!(resends, 0);
+
+    // Replacing the code keeps the attempt and its failed attempts.
+    let new_code = TwoFACode::parse("654321".to_string()).unwrap();
+    store.replace_code(&email, new_code.clone())
+        .await.expect("Failed to replace code");
+    assert_eq!(store.get_code(&email).await, Ok((login_attempt_id, new_code)));
+    let (resends, sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+    assert_eq!(resends, 1);
+    assert!(sent_at >= first_sent_at);
+    assert_eq!(store.record_failed_attempt(&email).await, Ok(3));
+
+    // A new login attempt starts over.
+    let login_attempt_id = LoginAttemptId::default();
+    store.add_code(&email, login_attempt_id.clone(), TwoFACode::default())
+        .await.expect("Failed to add code");
+    assert_eq!(store.get_code(&email).await.map(|(id, _)| id), Ok(login_attempt_id));
+    assert_eq!(store.get_resends(&email).await.map(|(resends, _)| resends), Ok(0));
+    assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+
+    store.remove_code(&email)
+        .await.expect("Failed to remove code");
+    assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    assert_eq!(store.get_resends(&email).await.map(|(resends, _)| resends), Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+}
+
+fn count(failures: Option<LoginFailures>) -> Option<u32> {
+    failures.map(|failures| failures.count)
+}
+
+// Reserves an attempt at a later time than the last one, even in stores that keep milliseconds.
+async fn reserve_attempt_later<S: LoginAttemptStore>(store: &mut S, key: &LoginAttemptKey) -> Option<LoginFailures> {
+    tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    store.reserve_attempt(key).await.unwrap()
+}
+
+pub async fn login_attempt_store_behaves<S: LoginAttemptStore>(mut store: S) {
+    let key = LoginAttemptKey::Email(random_email());
+
+    assert_eq!(store.get_failures(&key).await, Ok(None));
+    assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+    assert_eq!(count(store.reserve_attempt(&key).await.unwrap()), Some(1));
+    assert_eq!(count(store.get_failures(&key).await.unwrap()), Some(2));
+    assert_eq!(store.get_failures(&LoginAttemptKey::Email(random_email())).await, Ok(None));
+
+    // A released attempt is taken back along with its time, but the failures before it stay until they're reset.
+    let previous = store.get_failures(&key).await.unwrap();
+    let reserved = reserve_attempt_later(&mut store, &key).await;
+    assert_eq!(reserved, previous);
+    store.release_attempt(&key, reserved).await.unwrap();
+    assert_eq!(store.get_failures(&key).await, Ok(previous));
+
+    // Once another attempt was counted in between, only the count goes down, keeping the other attempt's time.
+    let reserved = reserve_attempt_later(&mut store, &key).await;
+    let other = reserve_attempt_later(&mut store, &key).await.unwrap();
+    store.release_attempt(&key, reserved).await.unwrap();
+    let failures = store.get_failures(&key).await.unwrap().unwrap();
+    assert_eq!(failures.count, 3);
+    assert!(failures.last_failure_at > other.last_failure_at);
+
+    // The first attempt of a key leaves no failures behind.
+    let first_key = LoginAttemptKey::Email(random_email());
+    let reserved = store.reserve_attempt(&first_key).await.unwrap();
+    store.release_attempt(&first_key, reserved).await.unwrap();
+    assert_eq!(count(store.get_failures(&first_key).await.unwrap()).unwrap_or(0), 0);
+
+    store.reset_failures(&key).await.unwrap();
+    assert_eq!(store.get_failures(&key).await, Ok(None));
+    assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+}
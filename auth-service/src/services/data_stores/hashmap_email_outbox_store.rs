@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::domain::{DeliveryStatus, Email, EmailOutboxStore, EmailOutboxStoreError, QueuedEmail, QueuedEmailId};
+use crate::utils::constants::EMAIL_OUTBOX_LEASE_SECONDS;
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapEmailOutboxStore {
+    // Oldest first.
+    emails: Vec<QueuedEmail>,
+}
+
+impl HashmapEmailOutboxStore {
+    fn get_email_mut(&mut self, id: &QueuedEmailId) -> Result<&mut QueuedEmail, EmailOutboxStoreError> {
+        self.emails
+            .iter_mut()
+            .find(|email| &email.id == id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)
+    }
+}
+
+// Codes and tokens in the message aren't needed once there's nothing left to deliver.
+fn drop_content(email: &mut QueuedEmail) {
+    email.message.text.clear();
+    email.message.html.clear();
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for HashmapEmailOutboxStore {
+    async fn enqueue(&mut self, email: QueuedEmail) -> Result<(), EmailOutboxStoreError> {
+        self.emails.push(email);
+        Ok(())
+    }
+
+    async fn claim_due(&mut self, limit: u32) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError> {
+        let now = Utc::now();
+        let lease_ends_at = now + Duration::seconds(EMAIL_OUTBOX_LEASE_SECONDS);
+
+        let claimed = self.emails
+            .iter_mut()
+            .filter(|email| email.status == DeliveryStatus::Pending && email.next_attempt_at <= now)
+            .take(limit as usize)
+            .map(|email| {
+                let claimed = email.clone();
+                email.next_attempt_at = lease_ends_at;
+                claimed
+            })
+            .collect();
+
+        Ok(claimed)
+    }
+
+    async fn mark_sent(&mut self, id: &QueuedEmailId) -> Result<(), EmailOutboxStoreError> {
+        let email = self.get_email_mut(id)?;
+        email.status = DeliveryStatus::Sent;
+        email.sent_at = Some(Utc::now());
+        drop_content(email);
+        Ok(())
+    }
+
+    async fn record_failure(
+        &mut self,
+        id: &QueuedEmailId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let email = self.get_email_mut(id)?;
+        email.attempts += 1;
+        email.last_error = Some(error.to_string());
+        match retry_at {
+            Some(retry_at) => email.next_attempt_at = retry_at,
+            None => {
+                email.status = DeliveryStatus::DeadLettered;
+                drop_content(email);
+            },
+        }
+        Ok(())
+    }
+
+    async fn get_emails(&self, recipient: &Email) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError> {
+        let mut emails: Vec<QueuedEmail> = self.emails
+            .iter()
+            .filter(|email| &email.recipient == recipient)
+            .cloned()
+            .collect();
+        emails.sort_by_key(|email| Reverse(email.created_at));
+        Ok(emails)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::EmailMessage;
+    use super::*;
+
+    fn create_email(email: &str) -> QueuedEmail {
+        let recipient = Email::parse(Secret::new(email.to_string()))
+            .expect("Failed to create Email");
+        QueuedEmail::new(recipient, EmailMessage {
+            subject: "Subject".to_string(),
+            text: "123456".to_string(),
+            html: "<p>123456</p>".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_leases_emails() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let email = create_email("someemail@somedomain.com");
+        store.enqueue(email.clone()).await.unwrap();
+
+        let claimed = store.claim_due(10).await.unwrap();
+        assert_eq!(claimed, vec![email]);
+
+        // Leased until the worker reports back.
+        assert!(store.claim_due(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_respects_limit_and_order() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let first = create_email("first@somedomain.com");
+        let second = create_email("second@somedomain.com");
+        store.enqueue(first.clone()).await.unwrap();
+        store.enqueue(second.clone()).await.unwrap();
+
+        assert_eq!(store.claim_due(1).await.unwrap(), vec![first]);
+        assert_eq!(store.claim_due(1).await.unwrap(), vec![second]);
+    }
+
+    #[tokio::test]
+    async fn test_mark_sent_drops_content() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let email = create_email("someemail@somedomain.com");
+        store.enqueue(email.clone()).await.unwrap();
+
+        store.mark_sent(&email.id).await.unwrap();
+
+        let stored = store.get_emails(&email.recipient).await.unwrap().remove(0);
+        assert_eq!(stored.status, DeliveryStatus::Sent);
+        assert!(stored.sent_at.is_some());
+        assert_eq!(stored.message.subject, "Subject");
+        assert!(stored.message.text.is_empty() && stored.message.html.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_failure_schedules_retry_or_dead_letters() {
+        let mut store = HashmapEmailOutboxStore::default();
+        let email = create_email("someemail@somedomain.com");
+        store.enqueue(email.clone()).await.unwrap();
+
+        let retry_at = Utc::now() - Duration::seconds(1);
+        store.record_failure(&email.id, "connection refused", Some(retry_at)).await.unwrap();
+
+        let retried = store.claim_due(10).await.unwrap().remove(0);
+        assert_eq!(retried.attempts, 1);
+        assert_eq!(retried.last_error.as_deref(), Some("connection refused"));
+
+        store.record_failure(&email.id, "mailbox unavailable", None).await.unwrap();
+
+        let stored = store.get_emails(&email.recipient).await.unwrap().remove(0);
+        assert_eq!(stored.status, DeliveryStatus::DeadLettered);
+        assert_eq!(stored.attempts, 2);
+        assert!(stored.message.text.is_empty());
+        assert!(store.claim_due(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_email() {
+        let mut store = HashmapEmailOutboxStore::default();
+
+        assert_eq!(store.mark_sent(&QueuedEmailId::default()).await, Err(EmailOutboxStoreError::EmailNotFound));
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+
+use crate::domain::{Email, EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError};
+use crate::utils::constants::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+struct StoredToken {
+    email: Email,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapEmailVerificationTokenStore {
+    tokens: HashMap<String, StoredToken>,
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for HashmapEmailVerificationTokenStore {
+    async fn add_token(&mut self, email: &Email, token: EmailVerificationToken) -> Result<(), EmailVerificationTokenStoreError> {
+        let ttl = Duration::try_seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)
+            .ok_or(EmailVerificationTokenStoreError::UnexpectedError(eyre!("failed to create token ttl")))?;
+        let now = Utc::now();
+
+        // Only the most recently sent token should be usable.
+        self.tokens.retain(|_, stored| &stored.email != email);
+        self.tokens.insert(token.as_ref().to_string(), StoredToken {
+            email: email.clone(),
+            issued_at: now,
+            expires_at: now + ttl,
+        });
+        Ok(())
+    }
+
+    async fn consume_token(&mut self, token: &EmailVerificationToken) -> Result<Email, EmailVerificationTokenStoreError> {
+        match self.tokens.remove(token.as_ref()) {
+            Some(stored) if stored.expires_at > Utc::now() => Ok(stored.email),
+            _ => Err(EmailVerificationTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn get_token_issued_at(&self, email: &Email) -> Result<Option<DateTime<Utc>>, EmailVerificationTokenStoreError> {
+        Ok(self.tokens.values()
+            .find(|stored| &stored.email == email)
+            .map(|stored| stored.issued_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email() -> Email {
+        Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email")
+    }
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = create_email();
+        let token = EmailVerificationToken::default();
+
+        store.add_token(&email, token.clone())
+            .await.expect("Failed to add token");
+        let result = store.consume_token(&token)
+            .await.expect("Failed to consume token");
+
+        assert_eq!(result, email);
+        assert_eq!(store.consume_token(&token).await, Err(EmailVerificationTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_new_token_replaces_old_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = create_email();
+        let old_token = EmailVerificationToken::default();
+        let new_token = EmailVerificationToken::default();
+
+        store.add_token(&email, old_token.clone())
+            .await.expect("Failed to add token");
+        store.add_token(&email, new_token.clone())
+            .await.expect("Failed to add token");
+
+        assert_eq!(store.consume_token(&old_token).await, Err(EmailVerificationTokenStoreError::TokenNotFound));
+        assert_eq!(store.consume_token(&new_token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_issued_at() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = create_email();
+        assert_eq!(store.get_token_issued_at(&email).await, Ok(None));
+
+        let before = Utc::now();
+        store.add_token(&email, EmailVerificationToken::default())
+            .await.expect("Failed to add token");
+
+        let issued_at = store.get_token_issued_at(&email)
+            .await.expect("Failed to get issued at")
+            .expect("Token should have an issued at time");
+        assert!(issued_at >= before);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_rejected() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let token = EmailVerificationToken::default();
+        let issued_at = Utc::now() - Duration::try_days(2).unwrap();
+
+        store.tokens.insert(token.as_ref().to_string(), StoredToken {
+            email: create_email(),
+            issued_at,
+            expires_at: issued_at + Duration::try_days(1).unwrap(),
+        });
+
+        assert_eq!(store.consume_token(&token).await, Err(EmailVerificationTokenStoreError::TokenNotFound));
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{DeviceFingerprint, DeviceSighting, Email, KnownDeviceStore, KnownDeviceStoreError};
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapKnownDeviceStore {
+    devices: HashMap<Email, HashSet<DeviceFingerprint>>,
+}
+
+#[async_trait::async_trait]
+impl KnownDeviceStore for HashmapKnownDeviceStore {
+    async fn add_device(
+        &mut self,
+        email: &Email,
+        fingerprint: &DeviceFingerprint,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError> {
+        let devices = self.devices.entry(email.clone()).or_default();
+        let first = devices.is_empty();
+
+        Ok(match devices.insert(fingerprint.clone()) {
+            false => DeviceSighting::Known,
+            true if first => DeviceSighting::First,
+            true => DeviceSighting::New,
+        })
+    }
+
+    async fn forget_devices(&mut self, email: &Email) -> Result<(), KnownDeviceStoreError> {
+        self.devices.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email(email: &str) -> Email {
+        Email::parse(Secret::new(email.to_string()))
+            .expect("Failed to create Email")
+    }
+
+    #[tokio::test]
+    async fn test_add_device() {
+        let mut store = HashmapKnownDeviceStore::default();
+        let email = create_email("someemail@somedomain.com");
+        let laptop = DeviceFingerprint::new(Some("curl/8.5.0"), Some("203.0.113.7"));
+        let phone = DeviceFingerprint::new(Some("curl/8.5.0"), Some("198.51.100.7"));
+
+        assert_eq!(store.add_device(&email, &laptop).await.unwrap(), DeviceSighting::First);
+        assert_eq!(store.add_device(&email, &laptop).await.unwrap(), DeviceSighting::Known);
+        assert_eq!(store.add_device(&email, &phone).await.unwrap(), DeviceSighting::New);
+        assert_eq!(store.add_device(&email, &phone).await.unwrap(), DeviceSighting::Known);
+
+        // Devices are per user.
+        let other_email = create_email("other@somedomain.com");
+        assert_eq!(store.add_device(&other_email, &laptop).await.unwrap(), DeviceSighting::First);
+    }
+
+    #[tokio::test]
+    async fn test_forget_devices() {
+        let mut store = HashmapKnownDeviceStore::default();
+        let email = create_email("someemail@somedomain.com");
+        let laptop = DeviceFingerprint::new(Some("curl/8.5.0"), Some("203.0.113.7"));
+        store.add_device(&email, &laptop).await.unwrap();
+
+        store.forget_devices(&email).await.unwrap();
+
+        assert_eq!(store.add_device(&email, &laptop).await.unwrap(), DeviceSighting::First);
+    }
+}
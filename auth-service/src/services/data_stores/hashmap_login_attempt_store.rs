@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use chrono::{Duration, Utc};
+
+use crate::domain::{LoginAttemptKey, LoginAttemptStore, LoginAttemptStoreError, LoginFailures};
+use crate::utils::constants::LOGIN_LOCKOUT_SECONDS;
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapLoginAttemptStore {
+    failures: HashMap<LoginAttemptKey, LoginFailures>,
+}
+
+fn is_current(failures: &LoginFailures) -> bool {
+    failures.last_failure_at + Duration::seconds(*LOGIN_LOCKOUT_SECONDS) > Utc::now()
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for HashmapLoginAttemptStore {
+    async fn get_failures(&self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        Ok(self.failures
+            .get(key)
+            .filter(|failures| is_current(failures))
+            .copied())
+    }
+
+    async fn reserve_attempt(&mut self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let previous = self.get_failures(key).await?;
+        let failures = LoginFailures {
+            count: previous.map_or(0, |failures| failures.count) + 1,
+            last_failure_at: Utc::now(),
+        };
+
+        self.failures.insert(key.clone(), failures);
+        Ok(previous)
+    }
+
+    async fn release_attempt(&mut self, key: &LoginAttemptKey, previous: Option<LoginFailures>) -> Result<(), LoginAttemptStoreError> {
+        let Some(failures) = self.failures.get_mut(key) else {
+            return Ok(());
+        };
+
+        if failures.count == previous.map_or(0, |previous| previous.count) + 1 {
+            match previous {
+                Some(previous) => *failures = previous,
+                None => failures.count = 0,
+            }
+        } else {
+            failures.count = failures.count.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    async fn reset_failures(&mut self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        self.failures.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::Email;
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    fn email_key(email: &str) -> LoginAttemptKey {
+        LoginAttemptKey::Email(Email::parse(Secret::new(email.to_string())).expect("Failed to create Email"))
+    }
+
+    fn count(failures: Option<LoginFailures>) -> Option<u32> {
+        failures.map(|failures| failures.count)
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_a_login_attempt_store() {
+        behavior_tests::login_attempt_store_behaves(HashmapLoginAttemptStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_reserve_attempt_counts_up() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let key = email_key("someemail@somedomain.com");
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+        assert_eq!(count(store.reserve_attempt(&key).await.unwrap()), Some(1));
+        assert_eq!(count(store.get_failures(&key).await.unwrap()), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_release_attempt() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let key = email_key("someemail@somedomain.com");
+        store.reserve_attempt(&key).await.unwrap();
+        let previous = store.reserve_attempt(&key).await.unwrap();
+
+        store.release_attempt(&key, previous).await.unwrap();
+
+        assert_eq!(store.get_failures(&key).await, Ok(previous));
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_counted_separately() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let key = email_key("someemail@somedomain.com");
+        let ip_key = LoginAttemptKey::IpAddress("127.0.0.1".to_string());
+
+        store.reserve_attempt(&key).await.unwrap();
+
+        assert_eq!(store.get_failures(&ip_key).await, Ok(None));
+        assert_eq!(store.get_failures(&email_key("other@somedomain.com")).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_reset_failures() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let key = email_key("someemail@somedomain.com");
+        store.reserve_attempt(&key).await.unwrap();
+
+        store.reset_failures(&key).await.unwrap();
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_old_failures_are_forgotten() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let key = email_key("someemail@somedomain.com");
+        store.failures.insert(key.clone(), LoginFailures {
+            count: 3,
+            last_failure_at: Utc::now() - Duration::seconds(*LOGIN_LOCKOUT_SECONDS),
+        });
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+    }
+}
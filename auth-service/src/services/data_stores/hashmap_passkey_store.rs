@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+
+use crate::domain::{Email, PasskeyChallenge, PasskeyChallengeId, PasskeyCredential, PasskeyStore, PasskeyStoreError};
+use crate::utils::constants::PASSKEY_CHALLENGE_TTL_SECONDS;
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapPasskeyStore {
+    credentials: HashMap<String, PasskeyCredential>,
+    registration_challenges: HashMap<Email, (PasskeyChallenge, DateTime<Utc>)>,
+    login_challenges: HashMap<PasskeyChallengeId, (PasskeyChallenge, DateTime<Utc>)>,
+}
+
+fn challenge_expires_at() -> Result<DateTime<Utc>, PasskeyStoreError> {
+    let ttl = Duration::try_seconds(PASSKEY_CHALLENGE_TTL_SECONDS)
+        .ok_or(PasskeyStoreError::UnexpectedError(eyre!("failed to create challenge ttl")))?;
+
+    Ok(Utc::now() + ttl)
+}
+
+fn unexpired(entry: Option<(PasskeyChallenge, DateTime<Utc>)>) -> Result<PasskeyChallenge, PasskeyStoreError> {
+    match entry {
+        Some((challenge, expires_at)) if expires_at > Utc::now() => Ok(challenge),
+        _ => Err(PasskeyStoreError::ChallengeNotFound),
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for HashmapPasskeyStore {
+    async fn add_credential(&mut self, credential: PasskeyCredential) -> Result<(), PasskeyStoreError> {
+        if self.credentials.contains_key(&credential.credential_id) {
+            return Err(PasskeyStoreError::CredentialAlreadyExists);
+        }
+        self.credentials.insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    async fn get_credentials(&self, email: &Email) -> Result<Vec<PasskeyCredential>, PasskeyStoreError> {
+        Ok(self.credentials
+            .values()
+            .filter(|credential| &credential.email == email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(&mut self, credential_id: &str, sign_count: u32) -> Result<(), PasskeyStoreError> {
+        match self.credentials.get_mut(credential_id) {
+            Some(credential) => {
+                credential.sign_count = sign_count;
+                Ok(())
+            }
+            None => Err(PasskeyStoreError::CredentialNotFound),
+        }
+    }
+
+    async fn add_registration_challenge(
+        &mut self,
+        email: &Email,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError> {
+        self.registration_challenges.insert(email.clone(), (challenge, challenge_expires_at()?));
+        Ok(())
+    }
+
+    async fn consume_registration_challenge(&mut self, email: &Email) -> Result<PasskeyChallenge, PasskeyStoreError> {
+        unexpired(self.registration_challenges.remove(email))
+    }
+
+    async fn add_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError> {
+        // Anyone can start a login, so expired challenges are dropped here instead of piling up.
+        let now = Utc::now();
+        self.login_challenges.retain(|_, (_, expires_at)| *expires_at > now);
+
+        self.login_challenges.insert(challenge_id.clone(), (challenge, challenge_expires_at()?));
+        Ok(())
+    }
+
+    async fn consume_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+    ) -> Result<PasskeyChallenge, PasskeyStoreError> {
+        unexpired(self.login_challenges.remove(challenge_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email() -> Email {
+        Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email")
+    }
+
+    fn create_credential(credential_id: &str) -> PasskeyCredential {
+        PasskeyCredential {
+            credential_id: credential_id.to_string(),
+            email: create_email(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credentials() {
+        let mut store = HashmapPasskeyStore::default();
+        store.add_credential(create_credential("first")).await.unwrap();
+        store.add_credential(create_credential("second")).await.unwrap();
+
+        let mut credentials = store.get_credentials(&create_email()).await.unwrap();
+        credentials.sort_by(|a, b| a.credential_id.cmp(&b.credential_id));
+        assert_eq!(credentials, vec![create_credential("first"), create_credential("second")]);
+    }
+
+    #[tokio::test]
+    async fn test_add_credential_already_exists() {
+        let mut store = HashmapPasskeyStore::default();
+        store.add_credential(create_credential("first")).await.unwrap();
+
+        assert_eq!(
+            store.add_credential(create_credential("first")).await,
+            Err(PasskeyStoreError::CredentialAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let mut store = HashmapPasskeyStore::default();
+        store.add_credential(create_credential("first")).await.unwrap();
+
+        assert_eq!(store.update_sign_count("first", 7).await, Ok(()));
+        assert_eq!(store.get_credentials(&create_email()).await.unwrap()[0].sign_count, 7);
+        assert_eq!(store.update_sign_count("unknown", 7).await, Err(PasskeyStoreError::CredentialNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_consume_registration_challenge() {
+        let mut store = HashmapPasskeyStore::default();
+        let email = create_email();
+        let challenge = PasskeyChallenge::default();
+        store.add_registration_challenge(&email, challenge.clone()).await.unwrap();
+
+        assert_eq!(
+            store.consume_login_challenge(&PasskeyChallengeId::default()).await,
+            Err(PasskeyStoreError::ChallengeNotFound)
+        );
+        assert_eq!(store.consume_registration_challenge(&email).await, Ok(challenge));
+        assert_eq!(
+            store.consume_registration_challenge(&email).await,
+            Err(PasskeyStoreError::ChallengeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_registration_challenge_replaces_previous() {
+        let mut store = HashmapPasskeyStore::default();
+        let email = create_email();
+        let new_challenge = PasskeyChallenge::default();
+        store.add_registration_challenge(&email, PasskeyChallenge::default()).await.unwrap();
+        store.add_registration_challenge(&email, new_challenge.clone()).await.unwrap();
+
+        assert_eq!(store.consume_registration_challenge(&email).await, Ok(new_challenge));
+    }
+
+    #[tokio::test]
+    async fn test_login_challenges_are_kept_apart() {
+        let mut store = HashmapPasskeyStore::default();
+        let (first_id, second_id) = (PasskeyChallengeId::default(), PasskeyChallengeId::default());
+        let (first, second) = (PasskeyChallenge::default(), PasskeyChallenge::default());
+        store.add_login_challenge(&first_id, first.clone()).await.unwrap();
+        store.add_login_challenge(&second_id, second.clone()).await.unwrap();
+
+        assert_eq!(store.consume_login_challenge(&first_id).await, Ok(first));
+        assert_eq!(store.consume_login_challenge(&first_id).await, Err(PasskeyStoreError::ChallengeNotFound));
+        assert_eq!(store.consume_login_challenge(&second_id).await, Ok(second));
+    }
+
+    #[tokio::test]
+    async fn test_expired_login_challenges_are_dropped() {
+        let mut store = HashmapPasskeyStore::default();
+        let expired_id = PasskeyChallengeId::default();
+        store.login_challenges.insert(expired_id.clone(), (PasskeyChallenge::default(), Utc::now()));
+
+        store.add_login_challenge(&PasskeyChallengeId::default(), PasskeyChallenge::default()).await.unwrap();
+
+        assert!(!store.login_challenges.contains_key(&expired_id));
+        assert_eq!(store.login_challenges.len(), 1);
+    }
+}
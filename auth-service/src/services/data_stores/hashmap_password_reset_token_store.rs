@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+
+use crate::domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError};
+use crate::utils::constants::PASSWORD_RESET_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+struct StoredToken {
+    email: Email,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: HashMap<String, StoredToken>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(&mut self, email: &Email, token: PasswordResetToken) -> Result<(), PasswordResetTokenStoreError> {
+        let ttl = Duration::try_seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS)
+            .ok_or(PasswordResetTokenStoreError::UnexpectedError(eyre!("failed to create token ttl")))?;
+        let now = Utc::now();
+
+        // Only the most recently requested token should be usable.
+        self.tokens.retain(|_, stored| &stored.email != email);
+        self.tokens.insert(token.as_ref().to_string(), StoredToken {
+            email: email.clone(),
+            issued_at: now,
+            expires_at: now + ttl,
+        });
+        Ok(())
+    }
+
+    async fn consume_token(&mut self, token: &PasswordResetToken) -> Result<Email, PasswordResetTokenStoreError> {
+        match self.tokens.remove(token.as_ref()) {
+            Some(stored) if stored.expires_at > Utc::now() => Ok(stored.email),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn get_token_issued_at(&self, email: &Email) -> Result<Option<DateTime<Utc>>, PasswordResetTokenStoreError> {
+        Ok(self.tokens.values()
+            .find(|stored| &stored.email == email)
+            .map(|stored| stored.issued_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email() -> Email {
+        Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email")
+    }
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = create_email();
+        let token = PasswordResetToken::default();
+
+        store.add_token(&email, token.clone())
+            .await.expect("Failed to add token");
+        let result = store.consume_token(&token)
+            .await.expect("Failed to consume token");
+
+        assert_eq!(result, email);
+    }
+
+    #[tokio::test]
+    async fn test_token_is_single_use() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = create_email();
+        let token = PasswordResetToken::default();
+
+        store.add_token(&email, token.clone())
+            .await.expect("Failed to add token");
+        store.consume_token(&token)
+            .await.expect("Failed to consume token");
+
+        assert_eq!(store.consume_token(&token).await, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_new_token_replaces_old_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = create_email();
+        let old_token = PasswordResetToken::default();
+        let new_token = PasswordResetToken::default();
+
+        store.add_token(&email, old_token.clone())
+            .await.expect("Failed to add token");
+        store.add_token(&email, new_token.clone())
+            .await.expect("Failed to add token");
+
+        assert_eq!(store.consume_token(&old_token).await, Err(PasswordResetTokenStoreError::TokenNotFound));
+        assert_eq!(store.consume_token(&new_token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_issued_at() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = create_email();
+        assert_eq!(store.get_token_issued_at(&email).await, Ok(None));
+
+        let before = Utc::now();
+        store.add_token(&email, PasswordResetToken::default())
+            .await.expect("Failed to add token");
+
+        let issued_at = store.get_token_issued_at(&email)
+            .await.expect("Failed to get issued at")
+            .expect("Token should have an issued at time");
+        assert!(issued_at >= before);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_rejected() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = create_email();
+        let token = PasswordResetToken::default();
+
+        let issued_at = Utc::now() - Duration::try_seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS + 1).unwrap();
+
+        store.tokens.insert(token.as_ref().to_string(), StoredToken {
+            email,
+            issued_at,
+            expires_at: issued_at + Duration::try_seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS).unwrap(),
+        });
+
+        assert_eq!(store.consume_token(&token).await, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapRecoveryCodeStore {
+    code_hashes: HashMap<Email, HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(&mut self, email: &Email, codes: &[RecoveryCode]) -> Result<(), RecoveryCodeStoreError> {
+        let code_hashes = codes.iter().map(RecoveryCode::hash).collect();
+        self.code_hashes.insert(email.clone(), code_hashes);
+        Ok(())
+    }
+
+    async fn consume_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError> {
+        let removed = self.code_hashes
+            .get_mut(email)
+            .is_some_and(|code_hashes| code_hashes.remove(&code.hash()));
+
+        match removed {
+            true => Ok(()),
+            false => Err(RecoveryCodeStoreError::CodeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email() -> Email {
+        Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email")
+    }
+
+    fn create_codes() -> Vec<RecoveryCode> {
+        (0..3).map(|_| RecoveryCode::default()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_consume_code() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = create_email();
+        let codes = create_codes();
+        store.replace_codes(&email, &codes).await.unwrap();
+
+        assert_eq!(store.consume_code(&email, &codes[0]).await, Ok(()));
+        assert_eq!(store.consume_code(&email, &codes[1]).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_consume_code_is_single_use() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = create_email();
+        let codes = create_codes();
+        store.replace_codes(&email, &codes).await.unwrap();
+
+        assert_eq!(store.consume_code(&email, &codes[0]).await, Ok(()));
+        assert_eq!(store.consume_code(&email, &codes[0]).await, Err(RecoveryCodeStoreError::CodeNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_replace_codes_invalidates_old_codes() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = create_email();
+        let old_codes = create_codes();
+        let new_codes = create_codes();
+        store.replace_codes(&email, &old_codes).await.unwrap();
+        store.replace_codes(&email, &new_codes).await.unwrap();
+
+        assert_eq!(store.consume_code(&email, &old_codes[0]).await, Err(RecoveryCodeStoreError::CodeNotFound));
+        assert_eq!(store.consume_code(&email, &new_codes[0]).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_consume_code_is_case_insensitive() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = create_email();
+        let codes = create_codes();
+        store.replace_codes(&email, &codes).await.unwrap();
+
+        let code = RecoveryCode::parse(codes[0].as_ref().to_uppercase()).unwrap();
+        assert_eq!(store.consume_code(&email, &code).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_consume_code_unknown_user() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let code = RecoveryCode::default();
+
+        assert_eq!(store.consume_code(&create_email(), &code).await, Err(RecoveryCodeStoreError::CodeNotFound));
+    }
+}
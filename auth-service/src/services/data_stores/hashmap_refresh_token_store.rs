@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+
+use crate::domain::{Email, RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId};
+use crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+struct StoredRefreshToken {
+    family_id: SessionId,
+    email: Email,
+    expires_at: DateTime<Utc>,
+    rotated: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapRefreshTokenStore {
+    // keyed by token hash
+    tokens: HashMap<String, StoredRefreshToken>,
+}
+
+impl HashmapRefreshTokenStore {
+    fn insert(&mut self, token: &RefreshToken, family_id: SessionId, email: Email) -> Result<(), RefreshTokenStoreError> {
+        let ttl = Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS)
+            .ok_or(RefreshTokenStoreError::UnexpectedError(eyre!("failed to create refresh token ttl")))?;
+
+        self.tokens.insert(token.hash(), StoredRefreshToken {
+            family_id,
+            email,
+            expires_at: Utc::now() + ttl,
+            rotated: false,
+        });
+        Ok(())
+    }
+
+    fn remove_family(&mut self, family_id: &SessionId) {
+        self.tokens.retain(|_, stored| &stored.family_id != family_id);
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn create_family(&mut self, email: &Email, session_id: &SessionId, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        self.insert(token, session_id.clone(), email.clone())
+    }
+
+    async fn rotate_token(&mut self, current: &RefreshToken, next: &RefreshToken) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let stored = match self.tokens.get_mut(&current.hash()) {
+            Some(stored) => stored,
+            None => return Err(RefreshTokenStoreError::TokenNotFound),
+        };
+
+        if stored.rotated {
+            let family_id = stored.family_id.clone();
+            self.remove_family(&family_id);
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        if stored.expires_at <= Utc::now() {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        stored.rotated = true;
+        let (family_id, email) = (stored.family_id.clone(), stored.email.clone());
+        self.insert(next, family_id.clone(), email.clone())?;
+        Ok((email, family_id))
+    }
+
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        if let Some(family_id) = self.tokens.get(&token.hash()).map(|stored| stored.family_id.clone()) {
+            self.remove_family(&family_id);
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.retain(|_, stored| &stored.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email() -> Email {
+        Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email")
+    }
+
+    #[tokio::test]
+    async fn test_rotate_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = create_email();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let third = RefreshToken::default();
+
+        let session_id = SessionId::default();
+
+        store.create_family(&email, &session_id, &first).await.unwrap();
+
+        assert_eq!(store.rotate_token(&first, &second).await, Ok((email.clone(), session_id.clone())));
+        assert_eq!(store.rotate_token(&second, &third).await, Ok((email, session_id)));
+    }
+
+    #[tokio::test]
+    async fn test_rotate_unknown_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+
+        let result = store.rotate_token(&RefreshToken::default(), &RefreshToken::default()).await;
+
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_reuse_revokes_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = create_email();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+
+        store.create_family(&email, &SessionId::default(), &first).await.unwrap();
+        store.rotate_token(&first, &second).await.unwrap();
+
+        let result = store.rotate_token(&first, &RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenReused));
+
+        // The legitimate holder's token stops working too.
+        let result = store.rotate_token(&second, &RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_reuse_leaves_other_families() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = create_email();
+        let stolen = RefreshToken::default();
+        let other = RefreshToken::default();
+
+        store.create_family(&email, &SessionId::default(), &stolen).await.unwrap();
+        let other_session_id = SessionId::default();
+        store.create_family(&email, &other_session_id, &other).await.unwrap();
+        store.rotate_token(&stolen, &RefreshToken::default()).await.unwrap();
+        let _ = store.rotate_token(&stolen, &RefreshToken::default()).await;
+
+        assert_eq!(store.rotate_token(&other, &RefreshToken::default()).await, Ok((email, other_session_id)));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = create_email();
+        let token = RefreshToken::default();
+
+        store.create_family(&email, &SessionId::default(), &token).await.unwrap();
+        store.revoke_family(&token).await.unwrap();
+
+        let result = store.rotate_token(&token, &RefreshToken::default()).await;
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_families() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = create_email();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+
+        store.create_family(&email, &SessionId::default(), &first).await.unwrap();
+        store.create_family(&email, &SessionId::default(), &second).await.unwrap();
+        store.revoke_all_families(&email).await.unwrap();
+
+        for token in [first, second] {
+            let result = store.rotate_token(&token, &RefreshToken::default()).await;
+            assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+        }
+    }
+}
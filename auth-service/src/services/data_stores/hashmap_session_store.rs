@@ -0,0 +1,144 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use chrono::{Duration, Utc};
+
+use crate::domain::{Email, Session, SessionId, SessionStore, SessionStoreError};
+use crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Default, Clone)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+}
+
+fn is_active(session: &Session) -> bool {
+    session.last_seen_at + Duration::seconds(REFRESH_TOKEN_TTL_SECONDS) > Utc::now()
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .filter(|session| is_active(session))
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self.sessions
+            .values()
+            .filter(|session| &session.email == email && is_active(session))
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let session = self.sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = Utc::now();
+        Ok(())
+    }
+
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        self.sessions
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| &session.email != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn create_email(email: &str) -> Email {
+        Email::parse(Secret::new(email.to_string()))
+            .expect("Failed to create Email")
+    }
+
+    fn create_session(email: &str) -> Session {
+        Session::new(create_email(email), Some("curl/8.5.0".to_string()), Some("127.0.0.1".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = create_session("someemail@somedomain.com");
+
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Ok(session));
+        assert_eq!(store.get_session(&SessionId::default()).await, Err(SessionStoreError::SessionNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_get_sessions_most_recent_first() {
+        let mut store = HashmapSessionStore::default();
+        let email = create_email("someemail@somedomain.com");
+        let older = create_session("someemail@somedomain.com");
+        let newer = create_session("someemail@somedomain.com");
+        store.add_session(older.clone()).await.unwrap();
+        store.add_session(newer.clone()).await.unwrap();
+        store.add_session(create_session("other@somedomain.com")).await.unwrap();
+
+        store.touch_session(&newer.id).await.unwrap();
+
+        let ids: Vec<SessionId> = store.get_sessions(&email).await.unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+        assert_eq!(ids, vec![newer.id, older.id]);
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_has_ended() {
+        let mut store = HashmapSessionStore::default();
+        let mut session = create_session("someemail@somedomain.com");
+        session.last_seen_at -= Duration::seconds(REFRESH_TOKEN_TTL_SECONDS);
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Err(SessionStoreError::SessionNotFound));
+        assert!(store.get_sessions(&session.email).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = create_session("someemail@somedomain.com");
+        store.add_session(session.clone()).await.unwrap();
+
+        store.remove_session(&session.id).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Err(SessionStoreError::SessionNotFound));
+        assert_eq!(store.remove_session(&session.id).await, Err(SessionStoreError::SessionNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_remove_all_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let email = create_email("someemail@somedomain.com");
+        let other = create_session("other@somedomain.com");
+        store.add_session(create_session("someemail@somedomain.com")).await.unwrap();
+        store.add_session(create_session("someemail@somedomain.com")).await.unwrap();
+        store.add_session(other.clone()).await.unwrap();
+
+        store.remove_all_sessions(&email).await.unwrap();
+
+        assert!(store.get_sessions(&email).await.unwrap().is_empty());
+        assert_eq!(store.get_session(&other.id).await, Ok(other));
+    }
+}
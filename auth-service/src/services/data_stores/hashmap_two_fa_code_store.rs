@@ -1,24 +1,46 @@
 use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
 
 use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::utils::constants::TWO_FA_CODE_TTL_SECONDS;
 
 #[derive(Debug, Clone)]
+struct StoredCode {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    failed_attempts: u32,
+    resends: u32,
+    sent_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<Email, StoredCode>,
 }
 
-impl Default for HashmapTwoFACodeStore {
-    fn default() -> Self {
-        Self {
-            codes: HashMap::new(),
-        }
+
+impl HashmapTwoFACodeStore {
+    fn get_stored_code(&mut self, email: &Email) -> Result<&mut StoredCode, TwoFACodeStoreError> {
+        self.codes
+            .get_mut(email)
+            .filter(|stored| stored.expires_at > Utc::now())
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
     }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(&mut self, email: &Email, login_attempt_id: LoginAttemptId, code: TwoFACode) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email.clone(), (login_attempt_id, code));
+        let now = Utc::now();
+        self.codes.insert(email.clone(), StoredCode {
+            login_attempt_id,
+            code,
+            failed_attempts: 0,
+            resends: 0,
+            sent_at: now,
+            expires_at: now + Duration::seconds(TWO_FA_CODE_TTL_SECONDS),
+        });
         Ok(())
     }
 
@@ -28,16 +50,48 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
     }
 
     async fn get_code(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        self.codes.get(email).cloned().ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        self.codes
+            .get(email)
+            .filter(|stored| stored.expires_at > Utc::now())
+            .map(|stored| (stored.login_attempt_id.clone(), stored.code.clone()))
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let stored = self.get_stored_code(email)?;
+        stored.failed_attempts += 1;
+        Ok(stored.failed_attempts)
+    }
+
+    async fn get_resends(&self, email: &Email) -> Result<(u32, DateTime<Utc>), TwoFACodeStoreError> {
+        self.codes
+            .get(email)
+            .filter(|stored| stored.expires_at > Utc::now())
+            .map(|stored| (stored.resends, stored.sent_at))
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn replace_code(&mut self, email: &Email, code: TwoFACode) -> Result<(), TwoFACodeStoreError> {
+        let stored = self.get_stored_code(email)?;
+        stored.code = code;
+        stored.resends += 1;
+        stored.sent_at = Utc::now();
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use secrecy::Secret;
+    use crate::services::data_stores::behavior_tests;
     use super::*;
     use crate::domain::{Email, LoginAttemptId, TwoFACode};
 
+    #[tokio::test]
+    async fn test_behaves_like_a_two_fa_code_store() {
+        behavior_tests::two_fa_code_store_behaves(HashmapTwoFACodeStore::default()).await;
+    }
+
     #[tokio::test]
     async fn test_add_code() {
         let mut store = HashmapTwoFACodeStore::default();
@@ -71,4 +125,66 @@ mod tests {
 
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_expired_code_is_not_found() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email");
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        store.codes.get_mut(&email).unwrap().expires_at = Utc::now();
+
+        assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+        assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email");
+
+        assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+
+        // A new login attempt starts counting again.
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email");
+        let login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(store.replace_code(&email, TwoFACode::default()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        store.add_code(&email, login_attempt_id.clone(), TwoFACode::parse("123456".to_string()).unwrap())
+            .await.expect("Failed to add code");
+        store.record_failed_attempt(&email).await.expect("Failed to record failed attempt");
+        let (resends, first_sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+        assert_eq!(resends, 0);
+        let expires_at = store.codes[&email].expires_at;
+
+        let new_code = TwoFACode::parse("654321".to_string()).unwrap();
+        store.replace_code(&email, new_code.clone())
+            .await.expect("Failed to replace code");
+
+        assert_eq!(store.get_code(&email).await, Ok((login_attempt_id, new_code)));
+        let (resends, sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+        assert_eq!(resends, 1);
+        assert!(sent_at >= first_sent_at);
+        // The attempt keeps its expiry and failed attempts.
+        assert_eq!(store.codes[&email].expires_at, expires_at);
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+    }
+}
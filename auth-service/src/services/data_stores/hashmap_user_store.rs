@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{Email, Password, TotpSecret, TwoFAMethod, User, UserStore, UserStoreError};
 
 
 pub fn user_store_error_to_string(error: &UserStoreError) -> String {
@@ -15,6 +15,7 @@ pub fn user_store_error_to_string(error: &UserStoreError) -> String {
 #[derive(Default, Debug, Clone)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    totp_last_steps: HashMap<Email, u64>,
 }
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
@@ -46,6 +47,58 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_totp_secret(&mut self, email: &Email, secret: TotpSecret) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.totp_secret = Some(secret);
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn accept_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if self.totp_last_steps.get(email).is_some_and(|&last_step| last_step >= step) {
+            return Ok(false);
+        }
+
+        self.totp_last_steps.insert(email.clone(), step);
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -63,7 +116,7 @@ mod tests {
             .unwrap();
         let password = Password::parse(Secret::new("password123".to_string()))
             .unwrap();
-        User::new(email, password, false)
+        User::new(email, password, TwoFAMethod::None)
     }
 
     async fn add_user_to_store(store: &mut HashmapUserStore, user: User) -> Result<(), UserStoreError> {
@@ -107,4 +160,90 @@ mod tests {
 
         assert_eq!(store.validate_user(&user.email, &user.password).await, Ok(()));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+        add_user_to_store(&mut store, user.clone()).await.unwrap();
+
+        let new_password = Password::parse(Secret::new("new_password123".to_string()))
+            .unwrap();
+        assert_eq!(store.update_password(&user.email, new_password.clone()).await, Ok(()));
+
+        assert_eq!(store.validate_user(&user.email, &user.password).await, Err(UserStoreError::InvalidCredentials));
+        assert_eq!(store.validate_user(&user.email, &new_password).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_update_password_user_not_found() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+
+        assert_eq!(store.update_password(&user.email, user.password.clone()).await, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_mark_email_verified() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+        add_user_to_store(&mut store, user.clone()).await.unwrap();
+        assert!(!store.get_user(&user.email).await.unwrap().verified);
+
+        assert_eq!(store.mark_email_verified(&user.email).await, Ok(()));
+
+        assert!(store.get_user(&user.email).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn test_set_totp_secret() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+        add_user_to_store(&mut store, user.clone()).await.unwrap();
+
+        let secret = TotpSecret::default();
+        assert_eq!(store.set_totp_secret(&user.email, secret.clone()).await, Ok(()));
+
+        let stored = store.get_user(&user.email).await.unwrap();
+        assert_eq!(stored.totp_secret, Some(secret));
+        assert_eq!(stored.two_fa_method, TwoFAMethod::None);
+    }
+
+    #[tokio::test]
+    async fn test_accept_totp_step() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+        add_user_to_store(&mut store, user.clone()).await.unwrap();
+
+        assert_eq!(store.accept_totp_step(&user.email, 100).await, Ok(true));
+        assert_eq!(store.accept_totp_step(&user.email, 100).await, Ok(false));
+        assert_eq!(store.accept_totp_step(&user.email, 99).await, Ok(false));
+        assert_eq!(store.accept_totp_step(&user.email, 101).await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+        add_user_to_store(&mut store, user.clone()).await.unwrap();
+
+        assert_eq!(store.set_two_fa_method(&user.email, TwoFAMethod::Totp).await, Ok(()));
+
+        assert_eq!(store.get_user(&user.email).await.unwrap().two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method_user_not_found() {
+        let mut store = create_user_store();
+        let user = create_test_user()
+            .expect("Failed to create test user");
+
+        assert_eq!(store.set_two_fa_method(&user.email, TwoFAMethod::Email).await, Err(UserStoreError::UserNotFound));
+    }
+}
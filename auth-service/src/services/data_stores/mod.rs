@@ -1,6 +1,31 @@
 pub mod hashmap_user_store;
 pub mod banned_token_store;
 pub mod hashmap_two_fa_code_store;
+pub mod hashmap_password_reset_token_store;
+pub mod hashmap_email_verification_token_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_passkey_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
+pub mod hashmap_login_attempt_store;
+pub mod hashmap_email_outbox_store;
+pub mod hashmap_known_device_store;
 pub mod postgres_user_store;
+pub mod postgres_password_reset_token_store;
+pub mod postgres_email_verification_token_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_passkey_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_session_store;
+pub mod postgres_email_outbox_store;
+pub mod postgres_known_device_store;
+pub mod postgres_banned_token_store;
+pub mod postgres_two_fa_code_store;
+pub mod postgres_login_attempt_store;
 pub mod redis_banned_token_store;
-mod redis_two_fa_code_store;
\ No newline at end of file
+pub mod redis_password_reset_token_store;
+pub mod redis_session_store;
+pub mod redis_login_attempt_store;
+pub mod redis_two_fa_code_store;
+#[cfg(test)]
+mod behavior_tests;
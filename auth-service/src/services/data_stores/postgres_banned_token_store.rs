@@ -0,0 +1,185 @@
+use chrono::{Duration, Utc};
+use color_eyre::eyre::Context;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{BannedTokenStore, Email};
+use crate::services::BannedTokenStoreError;
+use crate::utils::auth::BANNED_TOKEN_TTL_SECONDS;
+
+/// Keeps banned tokens in Postgres, for deployments without Redis.
+/// Bans expire with the token, and [purge_expired](PostgresBannedTokenStore::purge_expired) deletes them afterward.
+#[derive(Debug, Clone)]
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the bans of tokens that have expired by now, and returns how many there were.
+    #[tracing::instrument(name = "Purging expired banned tokens from PostgreSQL", skip_all)]
+    pub async fn purge_expired(&self) -> Result<u64, BannedTokenStoreError> {
+        let purged = sqlx::query!(
+            r#"
+            DELETE FROM banned_tokens
+            WHERE expires_at <= $1
+            "#,
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to purge expired banned tokens from PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?
+            .rows_affected();
+
+        Ok(purged)
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+
+    #[tracing::instrument(name = "Adding banned token to PostgreSQL", skip_all)]
+    async fn add_banned_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(BANNED_TOKEN_TTL_SECONDS);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO banned_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            "#,
+            jti,
+            expires_at
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to store banned token in PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Claiming token in PostgreSQL", skip_all)]
+    async fn claim_token(&mut self, jti: String) -> Result<bool, BannedTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(BANNED_TOKEN_TTL_SECONDS);
+
+        // A ban that already exists is left alone, so only the first insert affects a row.
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO banned_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+            jti,
+            expires_at
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to claim token in PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?
+            .rows_affected() > 0;
+
+        Ok(claimed)
+    }
+
+    #[tracing::instrument(name = "Checking if token is banned in PostgreSQL", skip_all)]
+    async fn is_banned(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM banned_tokens WHERE jti = $1 AND expires_at > $2) AS "exists!"
+            "#,
+            jti,
+            Utc::now()
+        )
+            .fetch_one(&self.pool)
+            .await
+            .wrap_err("failed to check if token is banned in PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Revoking all user tokens in PostgreSQL", skip_all)]
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO token_revocations (email, revoked_at_ms)
+            VALUES ($1, $2)
+            ON CONFLICT (email) DO UPDATE SET revoked_at_ms = EXCLUDED.revoked_at_ms
+            "#,
+            email.as_ref().expose_secret(),
+            Utc::now().timestamp_millis()
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to store token revocation time in PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting token revocation time from PostgreSQL", skip_all)]
+    async fn get_tokens_revoked_at(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError> {
+        let revoked_at = sqlx::query_scalar!(
+            r#"
+            SELECT revoked_at_ms
+            FROM token_revocations
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .wrap_err("failed to get token revocation time from PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        revoked_at
+            .map(|revoked_at| revoked_at.try_into())
+            .transpose()
+            .wrap_err("failed to cast token revocation time to usize")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_behaves_like_a_banned_token_store() {
+        let pool = behavior_tests::get_test_pool().await;
+        let email = behavior_tests::add_test_user(&pool).await;
+
+        behavior_tests::banned_token_store_behaves(PostgresBannedTokenStore::new(pool), &email).await;
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresBannedTokenStore::new(pool.clone());
+        let expired = behavior_tests::random_jti();
+        let live = behavior_tests::random_jti();
+
+        store.add_banned_token(expired.clone()).await.unwrap();
+        store.add_banned_token(live.clone()).await.unwrap();
+        sqlx::query("UPDATE banned_tokens SET expires_at = NOW() WHERE jti = $1")
+            .bind(&expired)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(!store.is_banned(&expired).await.unwrap());
+
+        assert!(store.purge_expired().await.unwrap() >= 1);
+
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT jti FROM banned_tokens WHERE jti = $1 OR jti = $2")
+            .bind(&expired)
+            .bind(&live)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![live]);
+    }
+}
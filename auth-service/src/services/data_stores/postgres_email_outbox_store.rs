@@ -0,0 +1,208 @@
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{
+    DeliveryStatus,
+    Email,
+    EmailMessage,
+    EmailOutboxStore,
+    EmailOutboxStoreError,
+    FromDbString,
+    QueuedEmail,
+    QueuedEmailId
+};
+use crate::utils::constants::EMAIL_OUTBOX_LEASE_SECONDS;
+
+#[derive(Debug, Clone)]
+pub struct PostgresEmailOutboxStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutboxStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn parse_status(status: &str) -> Result<DeliveryStatus, EmailOutboxStoreError> {
+    DeliveryStatus::parse(status).map_err(EmailOutboxStoreError::UnexpectedError)
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for PostgresEmailOutboxStore {
+
+    #[tracing::instrument(name = "Adding email to PostgreSQL outbox", skip_all)]
+    async fn enqueue(&mut self, email: QueuedEmail) -> Result<(), EmailOutboxStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox (id, recipient, subject, text_content, html_content, status, attempts, last_error, created_at, next_attempt_at, sent_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            email.id.as_ref(),
+            email.recipient.as_ref().expose_secret().to_string(),
+            email.message.subject,
+            email.message.text,
+            email.message.html,
+            email.status.as_str(),
+            email.attempts as i32,
+            email.last_error,
+            email.created_at,
+            email.next_attempt_at,
+            email.sent_at
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Claiming due emails from PostgreSQL outbox", skip_all)]
+    async fn claim_due(&mut self, limit: u32) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError> {
+        let now = Utc::now();
+
+        // SKIP LOCKED lets several instances claim batches at once without sending an email twice.
+        let mut emails = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = $2
+            WHERE id IN (
+                SELECT id
+                FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= $1
+                ORDER BY created_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, text_content, html_content, status, attempts, last_error, created_at, next_attempt_at, sent_at
+            "#,
+            now,
+            now + Duration::seconds(EMAIL_OUTBOX_LEASE_SECONDS),
+            limit as i64
+        )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|row| Ok(QueuedEmail {
+                id: QueuedEmailId::from_db_string(&row.id),
+                recipient: Email::from_db_string(&row.recipient),
+                message: EmailMessage {
+                    subject: row.subject,
+                    text: row.text_content,
+                    html: row.html_content,
+                },
+                status: parse_status(&row.status)?,
+                attempts: row.attempts as u32,
+                last_error: row.last_error,
+                created_at: row.created_at,
+                next_attempt_at: row.next_attempt_at,
+                sent_at: row.sent_at,
+            }))
+            .collect::<Result<Vec<_>, EmailOutboxStoreError>>()?;
+
+        // RETURNING doesn't keep the subquery's order.
+        emails.sort_by_key(|email| email.created_at);
+
+        Ok(emails)
+    }
+
+    #[tracing::instrument(name = "Marking email as sent in PostgreSQL outbox", skip_all)]
+    async fn mark_sent(&mut self, id: &QueuedEmailId) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = $2, sent_at = $3, text_content = '', html_content = ''
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+            DeliveryStatus::Sent.as_str(),
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording failed delivery in PostgreSQL outbox", skip_all)]
+    async fn record_failure(
+        &mut self,
+        id: &QueuedEmailId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let result = match retry_at {
+            Some(retry_at) => sqlx::query!(
+                r#"
+                UPDATE email_outbox
+                SET attempts = attempts + 1, last_error = $2, next_attempt_at = $3
+                WHERE id = $1
+                "#,
+                id.as_ref(),
+                error,
+                retry_at
+            )
+                .execute(&self.pool)
+                .await,
+            None => sqlx::query!(
+                r#"
+                UPDATE email_outbox
+                SET attempts = attempts + 1, last_error = $2, status = $3, text_content = '', html_content = ''
+                WHERE id = $1
+                "#,
+                id.as_ref(),
+                error,
+                DeliveryStatus::DeadLettered.as_str()
+            )
+                .execute(&self.pool)
+                .await,
+        }
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving emails from PostgreSQL outbox", skip_all)]
+    async fn get_emails(&self, recipient: &Email) -> Result<Vec<QueuedEmail>, EmailOutboxStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id, recipient, subject, text_content, html_content, status, attempts, last_error, created_at, next_attempt_at, sent_at
+            FROM email_outbox
+            WHERE recipient = $1
+            ORDER BY created_at DESC
+            "#,
+            recipient.as_ref().expose_secret().to_string()
+        )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| EmailOutboxStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|row| Ok(QueuedEmail {
+                id: QueuedEmailId::from_db_string(&row.id),
+                recipient: Email::from_db_string(&row.recipient),
+                message: EmailMessage {
+                    subject: row.subject,
+                    text: row.text_content,
+                    html: row.html_content,
+                },
+                status: parse_status(&row.status)?,
+                attempts: row.attempts as u32,
+                last_error: row.last_error,
+                created_at: row.created_at,
+                next_attempt_at: row.next_attempt_at,
+                sent_at: row.sent_at,
+            }))
+            .collect()
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError, FromDbString};
+use crate::utils::constants::EMAIL_VERIFICATION_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+pub struct PostgresEmailVerificationTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailVerificationTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for PostgresEmailVerificationTokenStore {
+
+    #[tracing::instrument(name = "Adding email verification token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let ttl = Duration::try_seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)
+            .ok_or(EmailVerificationTokenStoreError::UnexpectedError(eyre!("failed to create token ttl")))?;
+        let issued_at = Utc::now();
+        let expires_at = issued_at + ttl;
+
+        let mut transaction = self.pool.begin()
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?;
+
+        // Only the most recently sent token should be usable.
+        sqlx::query!(
+            r#"
+            DELETE FROM email_verification_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_tokens (token, email, issued_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            token.as_ref(),
+            email.as_ref().expose_secret().to_string(),
+            issued_at,
+            expires_at
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?;
+
+        transaction.commit()
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming email verification token from PostgreSQL", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError> {
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM email_verification_tokens
+            WHERE token = $1
+            RETURNING email, expires_at
+            "#,
+            token.as_ref()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?
+            .ok_or(EmailVerificationTokenStoreError::TokenNotFound)?;
+
+        if row.expires_at <= Utc::now() {
+            return Err(EmailVerificationTokenStoreError::TokenNotFound);
+        }
+
+        Ok(Email::from_db_string(&row.email))
+    }
+
+    #[tracing::instrument(name = "Getting email verification token issue time from PostgreSQL", skip_all)]
+    async fn get_token_issued_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<DateTime<Utc>>, EmailVerificationTokenStoreError> {
+        let issued_at = sqlx::query_scalar!(
+            r#"
+            SELECT issued_at
+            FROM email_verification_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| EmailVerificationTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(issued_at)
+    }
+}
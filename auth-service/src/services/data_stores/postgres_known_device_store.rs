@@ -0,0 +1,105 @@
+use chrono::Utc;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{DeviceFingerprint, DeviceSighting, Email, KnownDeviceStore, KnownDeviceStoreError};
+
+#[derive(Debug, Clone)]
+pub struct PostgresKnownDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresKnownDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl KnownDeviceStore for PostgresKnownDeviceStore {
+
+    #[tracing::instrument(name = "Adding known device to PostgreSQL", skip_all)]
+    async fn add_device(
+        &mut self,
+        email: &Email,
+        fingerprint: &DeviceFingerprint,
+    ) -> Result<DeviceSighting, KnownDeviceStoreError> {
+        let email = email.as_ref().expose_secret().to_string();
+        let now = Utc::now();
+
+        let mut transaction = self.pool
+            .begin()
+            .await
+            .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?;
+
+        let has_devices = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM known_devices WHERE email = $1) AS "exists!"
+            "#,
+            email
+        )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?;
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO known_devices (email, device_label, network, first_seen_at, last_seen_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (email, device_label, network) DO NOTHING
+            "#,
+            email,
+            fingerprint.device_label,
+            fingerprint.network,
+            now
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?
+            .rows_affected() > 0;
+
+        if !inserted {
+            sqlx::query!(
+                r#"
+                UPDATE known_devices
+                SET last_seen_at = $4
+                WHERE email = $1 AND device_label = $2 AND network = $3
+                "#,
+                email,
+                fingerprint.device_label,
+                fingerprint.network,
+                now
+            )
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(match (inserted, has_devices) {
+            (false, _) => DeviceSighting::Known,
+            (true, false) => DeviceSighting::First,
+            (true, true) => DeviceSighting::New,
+        })
+    }
+
+    #[tracing::instrument(name = "Forgetting known devices in PostgreSQL", skip_all)]
+    async fn forget_devices(&mut self, email: &Email) -> Result<(), KnownDeviceStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM known_devices
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| KnownDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
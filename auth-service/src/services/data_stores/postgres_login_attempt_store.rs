@@ -0,0 +1,263 @@
+use chrono::{Duration, Utc};
+use color_eyre::eyre::Context;
+use sqlx::PgPool;
+
+use crate::domain::{LoginAttemptKey, LoginAttemptStore, LoginAttemptStoreError, LoginFailures};
+use crate::utils::constants::LOGIN_LOCKOUT_SECONDS;
+
+/// Keeps failed login counts in Postgres, for deployments without Redis.
+/// A count expires [LOGIN_LOCKOUT_SECONDS] after the last failure, and
+/// [purge_expired](PostgresLoginAttemptStore::purge_expired) deletes it afterward.
+#[derive(Debug, Clone)]
+pub struct PostgresLoginAttemptStore {
+    pool: PgPool,
+}
+
+impl PostgresLoginAttemptStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the failure counts that have expired by now, and returns how many there were.
+    #[tracing::instrument(name = "Purging expired login failures from PostgreSQL", skip_all)]
+    pub async fn purge_expired(&self) -> Result<u64, LoginAttemptStoreError> {
+        let purged = sqlx::query!(
+            r#"
+            DELETE FROM login_attempts
+            WHERE expires_at <= $1
+            "#,
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to purge expired login failures from PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?
+            .rows_affected();
+
+        Ok(purged)
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for PostgresLoginAttemptStore {
+
+    #[tracing::instrument(name = "Retrieving login failures from PostgreSQL", skip_all)]
+    async fn get_failures(&self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT failures, last_failure_at
+            FROM login_attempts
+            WHERE attempt_key = $1 AND expires_at > $2
+            "#,
+            key.as_key(),
+            Utc::now()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .wrap_err("failed to get login failures from PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(row.map(|row| LoginFailures {
+            count: row.failures as u32,
+            last_failure_at: row.last_failure_at,
+        }))
+    }
+
+    #[tracing::instrument(name = "Reserving login attempt in PostgreSQL", skip_all)]
+    async fn reserve_attempt(&mut self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let key = key.as_key();
+        let now = Utc::now();
+
+        let mut transaction = self.pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        // The key needs a row to lock, so concurrent attempts wait for each other to be counted.
+        // An empty one is already expired, so it doesn't count as a failure.
+        sqlx::query!(
+            r#"
+            INSERT INTO login_attempts (attempt_key, failures, last_failure_at, expires_at)
+            VALUES ($1, 0, $2, $2)
+            ON CONFLICT (attempt_key) DO NOTHING
+            "#,
+            key,
+            now
+        )
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to add login failures in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        let row = sqlx::query!(
+            r#"
+            SELECT failures, last_failure_at, expires_at
+            FROM login_attempts
+            WHERE attempt_key = $1
+            FOR UPDATE
+            "#,
+            key
+        )
+            .fetch_one(&mut *transaction)
+            .await
+            .wrap_err("failed to lock login failures in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        let previous = (row.expires_at > now).then_some(LoginFailures {
+            count: row.failures as u32,
+            last_failure_at: row.last_failure_at,
+        });
+
+        sqlx::query!(
+            r#"
+            UPDATE login_attempts
+            SET failures = $2, last_failure_at = $3, expires_at = $4
+            WHERE attempt_key = $1
+            "#,
+            key,
+            previous.map_or(0, |failures| failures.count as i32) + 1,
+            now,
+            now + Duration::seconds(*LOGIN_LOCKOUT_SECONDS)
+        )
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to count login attempt in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(previous)
+    }
+
+    #[tracing::instrument(name = "Releasing login attempt in PostgreSQL", skip_all)]
+    async fn release_attempt(&mut self, key: &LoginAttemptKey, previous: Option<LoginFailures>) -> Result<(), LoginAttemptStoreError> {
+        // The failures are only restored if the count is still the one this attempt left.
+        let restored_failure_at = previous.map(|previous| previous.last_failure_at);
+        sqlx::query!(
+            r#"
+            UPDATE login_attempts
+            SET failures = failures - 1,
+                last_failure_at = CASE WHEN failures = $2 THEN COALESCE($3, last_failure_at) ELSE last_failure_at END,
+                expires_at = CASE WHEN failures = $2 THEN COALESCE($4, expires_at) ELSE expires_at END
+            WHERE attempt_key = $1 AND expires_at > $5 AND failures > 0
+            "#,
+            key.as_key(),
+            previous.map_or(0, |previous| previous.count as i32) + 1,
+            restored_failure_at,
+            restored_failure_at.map(|failure_at| failure_at + Duration::seconds(*LOGIN_LOCKOUT_SECONDS)),
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to release login attempt in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Resetting login failures in PostgreSQL", skip_all)]
+    async fn reset_failures(&mut self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM login_attempts
+            WHERE attempt_key = $1
+            "#,
+            key.as_key()
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to reset login failures in PostgreSQL")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    fn random_key() -> LoginAttemptKey {
+        LoginAttemptKey::Email(behavior_tests::random_email())
+    }
+
+    async fn expire_failures(pool: &PgPool, key: &LoginAttemptKey) {
+        sqlx::query("UPDATE login_attempts SET expires_at = NOW() WHERE attempt_key = $1")
+            .bind(key.as_key())
+            .execute(pool)
+            .await
+            .expect("Failed to expire login failures");
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_a_login_attempt_store() {
+        let pool = behavior_tests::get_test_pool().await;
+
+        behavior_tests::login_attempt_store_behaves(PostgresLoginAttemptStore::new(pool)).await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_attempts_are_each_counted() {
+        let pool = behavior_tests::get_test_pool().await;
+        let store = PostgresLoginAttemptStore::new(pool);
+        let key = random_key();
+
+        let attempts = (0..10).map(|_| {
+            let mut store = store.clone();
+            let key = key.clone();
+            tokio::spawn(async move { store.reserve_attempt(&key).await })
+        });
+        let mut previous_counts = HashSet::new();
+        for attempt in attempts {
+            let previous = attempt.await.unwrap().unwrap();
+            previous_counts.insert(previous.map_or(0, |failures| failures.count));
+        }
+
+        assert_eq!(previous_counts, (0..10).collect());
+        assert_eq!(store.get_failures(&key).await.unwrap().map(|failures| failures.count), Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_expired_failures_are_forgotten() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresLoginAttemptStore::new(pool.clone());
+        let key = random_key();
+
+        store.reserve_attempt(&key).await.unwrap();
+        store.reserve_attempt(&key).await.unwrap();
+        expire_failures(&pool, &key).await;
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(store.reserve_attempt(&key).await, Ok(None));
+        assert_eq!(store.get_failures(&key).await.unwrap().map(|failures| failures.count), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresLoginAttemptStore::new(pool.clone());
+        let expired = random_key();
+        let live = random_key();
+
+        store.reserve_attempt(&expired).await.unwrap();
+        store.reserve_attempt(&live).await.unwrap();
+        expire_failures(&pool, &expired).await;
+
+        assert!(store.purge_expired().await.unwrap() >= 1);
+
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT attempt_key FROM login_attempts WHERE attempt_key = $1 OR attempt_key = $2")
+            .bind(expired.as_key())
+            .bind(live.as_key())
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![live.as_key()]);
+    }
+}
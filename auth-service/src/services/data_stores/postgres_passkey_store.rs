@@ -0,0 +1,275 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, FromDbString, PasskeyChallenge, PasskeyChallengeId, PasskeyCredential, PasskeyStore, PasskeyStoreError};
+use crate::utils::constants::PASSKEY_CHALLENGE_TTL_SECONDS;
+
+/// Expired challenges are ignored, and [purge_expired](PostgresPasskeyStore::purge_expired) deletes them.
+#[derive(Debug, Clone)]
+pub struct PostgresPasskeyStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the challenges that have expired by now, and returns how many there were.
+    #[tracing::instrument(name = "Purging expired passkey challenges from PostgreSQL", skip_all)]
+    pub async fn purge_expired(&self) -> Result<u64, PasskeyStoreError> {
+        let now = Utc::now();
+
+        let purged_logins = sqlx::query!(
+            r#"
+            DELETE FROM passkey_login_challenges
+            WHERE expires_at <= $1
+            "#,
+            now
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+            .rows_affected();
+
+        let purged_registrations = sqlx::query!(
+            r#"
+            DELETE FROM passkey_registration_challenges
+            WHERE expires_at <= $1
+            "#,
+            now
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+            .rows_affected();
+
+        Ok(purged_logins + purged_registrations)
+    }
+}
+
+fn challenge_expires_at() -> Result<DateTime<Utc>, PasskeyStoreError> {
+    let ttl = Duration::try_seconds(PASSKEY_CHALLENGE_TTL_SECONDS)
+        .ok_or(PasskeyStoreError::UnexpectedError(eyre!("failed to create challenge ttl")))?;
+
+    Ok(Utc::now() + ttl)
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for PostgresPasskeyStore {
+
+    #[tracing::instrument(name = "Adding passkey credential to PostgreSQL", skip_all)]
+    async fn add_credential(&mut self, credential: PasskeyCredential) -> Result<(), PasskeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO passkey_credentials (credential_id, email, public_key, sign_count)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (credential_id) DO NOTHING
+            "#,
+            credential.credential_id,
+            credential.email.as_ref().expose_secret().to_string(),
+            credential.public_key,
+            i64::from(credential.sign_count)
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::CredentialAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkey credentials from PostgreSQL", skip_all)]
+    async fn get_credentials(&self, email: &Email) -> Result<Vec<PasskeyCredential>, PasskeyStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT credential_id, email, public_key, sign_count
+            FROM passkey_credentials
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|row| {
+                Ok(PasskeyCredential {
+                    credential_id: row.credential_id,
+                    email: Email::from_db_string(&row.email),
+                    public_key: row.public_key,
+                    sign_count: u32::try_from(row.sign_count)
+                        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating passkey sign count in PostgreSQL", skip_all)]
+    async fn update_sign_count(&mut self, credential_id: &str, sign_count: u32) -> Result<(), PasskeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE passkey_credentials
+            SET sign_count = $2
+            WHERE credential_id = $1
+            "#,
+            credential_id,
+            i64::from(sign_count)
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::CredentialNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Adding passkey registration challenge to PostgreSQL", skip_all)]
+    async fn add_registration_challenge(
+        &mut self,
+        email: &Email,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO passkey_registration_challenges (email, challenge, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (email)
+            DO UPDATE SET challenge = EXCLUDED.challenge, expires_at = EXCLUDED.expires_at
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            challenge.as_ref(),
+            challenge_expires_at()?
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming passkey registration challenge from PostgreSQL", skip_all)]
+    async fn consume_registration_challenge(&mut self, email: &Email) -> Result<PasskeyChallenge, PasskeyStoreError> {
+        // Deleting and returning in one statement makes sure a challenge can only be answered once.
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM passkey_registration_challenges
+            WHERE email = $1
+            RETURNING challenge, expires_at
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+            .ok_or(PasskeyStoreError::ChallengeNotFound)?;
+
+        if row.expires_at <= Utc::now() {
+            return Err(PasskeyStoreError::ChallengeNotFound);
+        }
+
+        Ok(PasskeyChallenge::from_db_string(&row.challenge))
+    }
+
+    #[tracing::instrument(name = "Adding passkey login challenge to PostgreSQL", skip_all)]
+    async fn add_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+        challenge: PasskeyChallenge,
+    ) -> Result<(), PasskeyStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO passkey_login_challenges (challenge_id, challenge, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            challenge_id.as_ref(),
+            challenge.as_ref(),
+            challenge_expires_at()?
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming passkey login challenge from PostgreSQL", skip_all)]
+    async fn consume_login_challenge(
+        &mut self,
+        challenge_id: &PasskeyChallengeId,
+    ) -> Result<PasskeyChallenge, PasskeyStoreError> {
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM passkey_login_challenges
+            WHERE challenge_id = $1
+            RETURNING challenge, expires_at
+            "#,
+            challenge_id.as_ref()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+            .ok_or(PasskeyStoreError::ChallengeNotFound)?;
+
+        if row.expires_at <= Utc::now() {
+            return Err(PasskeyStoreError::ChallengeNotFound);
+        }
+
+        Ok(PasskeyChallenge::from_db_string(&row.challenge))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_login_challenge() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresPasskeyStore::new(pool);
+        let (first_id, second_id) = (PasskeyChallengeId::default(), PasskeyChallengeId::default());
+        let (first, second) = (PasskeyChallenge::default(), PasskeyChallenge::default());
+
+        store.add_login_challenge(&first_id, first.clone()).await.unwrap();
+        store.add_login_challenge(&second_id, second.clone()).await.unwrap();
+
+        assert_eq!(store.consume_login_challenge(&first_id).await, Ok(first));
+        assert_eq!(store.consume_login_challenge(&first_id).await, Err(PasskeyStoreError::ChallengeNotFound));
+        assert_eq!(store.consume_login_challenge(&second_id).await, Ok(second));
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresPasskeyStore::new(pool.clone());
+        let (expired, live) = (PasskeyChallengeId::default(), PasskeyChallengeId::default());
+
+        store.add_login_challenge(&expired, PasskeyChallenge::default()).await.unwrap();
+        store.add_login_challenge(&live, PasskeyChallenge::default()).await.unwrap();
+        sqlx::query("UPDATE passkey_login_challenges SET expires_at = NOW() WHERE challenge_id = $1")
+            .bind(expired.as_ref())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(store.purge_expired().await.unwrap() >= 1);
+
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT challenge_id FROM passkey_login_challenges WHERE challenge_id = $1 OR challenge_id = $2")
+            .bind(expired.as_ref())
+            .bind(live.as_ref())
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![live.as_ref().to_string()]);
+    }
+}
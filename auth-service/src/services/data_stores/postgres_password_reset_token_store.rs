@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, FromDbString, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError};
+use crate::utils::constants::PASSWORD_RESET_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+pub struct PostgresPasswordResetTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresPasswordResetTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for PostgresPasswordResetTokenStore {
+
+    #[tracing::instrument(name = "Adding password reset token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let ttl = Duration::try_seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS)
+            .ok_or(PasswordResetTokenStoreError::UnexpectedError(eyre!("failed to create token ttl")))?;
+        let issued_at = Utc::now();
+        let expires_at = issued_at + ttl;
+
+        let mut transaction = self.pool.begin()
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        // Only the most recently requested token should be usable.
+        sqlx::query!(
+            r#"
+            DELETE FROM password_reset_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (token, email, issued_at, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            token.as_ref(),
+            email.as_ref().expose_secret().to_string(),
+            issued_at,
+            expires_at
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        transaction.commit()
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming password reset token from PostgreSQL", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        // Deleting and returning in one statement makes sure two concurrent requests can't both use the token.
+        let row = sqlx::query!(
+            r#"
+            DELETE FROM password_reset_tokens
+            WHERE token = $1
+            RETURNING email, expires_at
+            "#,
+            token.as_ref()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        if row.expires_at <= Utc::now() {
+            return Err(PasswordResetTokenStoreError::TokenNotFound);
+        }
+
+        Ok(Email::from_db_string(&row.email))
+    }
+
+    #[tracing::instrument(name = "Getting password reset token issue time from PostgreSQL", skip_all)]
+    async fn get_token_issued_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<DateTime<Utc>>, PasswordResetTokenStoreError> {
+        let issued_at = sqlx::query_scalar!(
+            r#"
+            SELECT issued_at
+            FROM password_reset_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(issued_at)
+    }
+}
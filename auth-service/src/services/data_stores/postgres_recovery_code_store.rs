@@ -0,0 +1,87 @@
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+#[derive(Debug, Clone)]
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let code_hashes: Vec<String> = codes.iter().map(RecoveryCode::hash).collect();
+
+        let mut transaction = self.pool.begin()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (email, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            &code_hashes
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        transaction.commit()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming recovery code from PostgreSQL", skip_all)]
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // A single DELETE makes sure two concurrent requests can't both use the code.
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1 AND code_hash = $2
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            code.hash()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::CodeNotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::domain::{Email, FromDbString, RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId};
+use crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the tokens that have expired by now, rotated or not, and returns how many there were.
+    /// Replaying an expired token is rejected either way, so there's no reuse left to detect with them.
+    #[tracing::instrument(name = "Purging expired refresh tokens from PostgreSQL", skip_all)]
+    pub async fn purge_expired(&self) -> Result<u64, RefreshTokenStoreError> {
+        let purged = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE expires_at <= $1
+            "#,
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?
+            .rows_affected();
+
+        Ok(purged)
+    }
+}
+
+async fn insert_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &RefreshToken,
+    family_id: &str,
+    email: &str,
+) -> Result<(), RefreshTokenStoreError> {
+    let ttl = Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS)
+        .ok_or(RefreshTokenStoreError::UnexpectedError(eyre!("failed to create refresh token ttl")))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, family_id, email, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        token.hash(),
+        family_id,
+        email,
+        Utc::now() + ttl
+    )
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+
+    #[tracing::instrument(name = "Creating refresh token family in PostgreSQL", skip_all)]
+    async fn create_family(&mut self, email: &Email, session_id: &SessionId, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let mut transaction = self.pool.begin()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        insert_token(
+            &mut transaction,
+            token,
+            session_id.as_ref(),
+            email.as_ref().expose_secret(),
+        ).await?;
+
+        transaction.commit()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Rotating refresh token in PostgreSQL", skip_all)]
+    async fn rotate_token(&mut self, current: &RefreshToken, next: &RefreshToken) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let mut transaction = self.pool.begin()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        // Locking the row makes concurrent refreshes with the same token take turns,
+        // so only the first one rotates it and the rest count as reuse.
+        let stored = sqlx::query!(
+            r#"
+            SELECT family_id, email, expires_at, rotated
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            current.hash()
+        )
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if stored.rotated {
+            sqlx::query!(
+                r#"
+                DELETE FROM refresh_tokens
+                WHERE family_id = $1
+                "#,
+                stored.family_id
+            )
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+            transaction.commit()
+                .await
+                .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+            return Err(RefreshTokenStoreError::TokenReused);
+        }
+
+        if stored.expires_at <= Utc::now() {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET rotated = TRUE
+            WHERE token_hash = $1
+            "#,
+            current.hash()
+        )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        insert_token(&mut transaction, next, &stored.family_id, &stored.email).await?;
+
+        transaction.commit()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok((Email::from_db_string(&stored.email), SessionId::from_db_string(&stored.family_id)))
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE family_id = (SELECT family_id FROM refresh_tokens WHERE token_hash = $1)
+            "#,
+            token.hash()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh token families in PostgreSQL", skip_all)]
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresRefreshTokenStore::new(pool.clone());
+        let email = behavior_tests::add_test_user(&pool).await;
+        let expired = RefreshToken::default();
+        let rotated = RefreshToken::default();
+        let live = RefreshToken::default();
+
+        store.create_family(&email, &SessionId::default(), &expired).await.unwrap();
+        store.create_family(&email, &SessionId::default(), &rotated).await.unwrap();
+        store.rotate_token(&rotated, &live).await.unwrap();
+        sqlx::query("UPDATE refresh_tokens SET expires_at = NOW() WHERE token_hash = $1 OR token_hash = $2")
+            .bind(expired.hash())
+            .bind(rotated.hash())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(store.purge_expired().await.unwrap() >= 2);
+
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT token_hash FROM refresh_tokens WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![live.hash()]);
+    }
+}
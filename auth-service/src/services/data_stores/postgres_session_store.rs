@@ -0,0 +1,167 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, FromDbString, Session, SessionId, SessionStore, SessionStoreError};
+use crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS;
+
+#[derive(Debug, Clone)]
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+// Sessions last seen before this have ended.
+fn active_since() -> Result<DateTime<Utc>, SessionStoreError> {
+    let ttl = Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS)
+        .ok_or(SessionStoreError::UnexpectedError(eyre!("failed to create session ttl")))?;
+
+    Ok(Utc::now() - ttl)
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (id, email, device_label, user_agent, ip_address, created_at, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            session.id.as_ref(),
+            session.email.as_ref().expose_secret().to_string(),
+            session.device_label,
+            session.user_agent,
+            session.ip_address,
+            session.created_at,
+            session.last_seen_at
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, email, device_label, user_agent, ip_address, created_at, last_seen_at
+            FROM sessions
+            WHERE id = $1 AND last_seen_at > $2
+            "#,
+            id.as_ref(),
+            active_since()?
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+            .ok_or(SessionStoreError::SessionNotFound)?;
+
+        Ok(Session {
+            id: SessionId::from_db_string(&row.id),
+            email: Email::from_db_string(&row.email),
+            device_label: row.device_label,
+            user_agent: row.user_agent,
+            ip_address: row.ip_address,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let sessions = sqlx::query!(
+            r#"
+            SELECT id, email, device_label, user_agent, ip_address, created_at, last_seen_at
+            FROM sessions
+            WHERE email = $1 AND last_seen_at > $2
+            ORDER BY last_seen_at DESC
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            active_since()?
+        )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+            .into_iter()
+            .map(|row| Session {
+                id: SessionId::from_db_string(&row.id),
+                email: Email::from_db_string(&row.email),
+                device_label: row.device_label,
+                user_agent: row.user_agent,
+                ip_address: row.ip_address,
+                created_at: row.created_at,
+                last_seen_at: row.last_seen_at,
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Updating session last seen time in PostgreSQL", skip_all)]
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = $2
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all sessions from PostgreSQL", skip_all)]
+    async fn remove_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::domain::{Email, FromDbString, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::utils::constants::TWO_FA_CODE_TTL_SECONDS;
+
+/// Keeps pending 2FA login attempts in Postgres, for deployments without Redis.
+/// Expired attempts are ignored, and [purge_expired](PostgresTwoFACodeStore::purge_expired) deletes them.
+#[derive(Debug, Clone)]
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Deletes the login attempts that have expired by now, and returns how many there were.
+    #[tracing::instrument(name = "Purging expired 2FA codes from PostgreSQL", skip_all)]
+    pub async fn purge_expired(&self) -> Result<u64, TwoFACodeStoreError> {
+        let purged = sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE expires_at <= $1
+            "#,
+            Utc::now()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+            .rows_affected();
+
+        Ok(purged)
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+
+    #[tracing::instrument(name = "Adding 2FA code to PostgreSQL", skip_all)]
+    async fn add_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO two_fa_codes (email, login_attempt_id, code, failed_attempts, resends, sent_at, expires_at)
+            VALUES ($1, $2, $3, 0, 0, $4, $5)
+            ON CONFLICT (email) DO UPDATE SET
+                login_attempt_id = EXCLUDED.login_attempt_id,
+                code = EXCLUDED.code,
+                failed_attempts = EXCLUDED.failed_attempts,
+                resends = EXCLUDED.resends,
+                sent_at = EXCLUDED.sent_at,
+                expires_at = EXCLUDED.expires_at
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            login_attempt_id.as_ref(),
+            code.to_string(),
+            now,
+            now + Duration::seconds(TWO_FA_CODE_TTL_SECONDS)
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from PostgreSQL", skip_all)]
+    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting 2FA code from PostgreSQL", skip_all)]
+    async fn get_code(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT login_attempt_id, code
+            FROM two_fa_codes
+            WHERE email = $1 AND expires_at > $2
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            Utc::now()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok((
+            LoginAttemptId::from_db_string(&row.login_attempt_id),
+            TwoFACode::parse(row.code)
+                .map_err(TwoFACodeStoreError::UnexpectedError)?
+        ))
+    }
+
+    #[tracing::instrument(name = "Recording failed 2FA attempt in PostgreSQL", skip_all)]
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+            UPDATE two_fa_codes
+            SET failed_attempts = failed_attempts + 1
+            WHERE email = $1 AND expires_at > $2
+            RETURNING failed_attempts
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            Utc::now()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok(failed_attempts as u32)
+    }
+
+    #[tracing::instrument(name = "Getting 2FA code resends from PostgreSQL", skip_all)]
+    async fn get_resends(&self, email: &Email) -> Result<(u32, DateTime<Utc>), TwoFACodeStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT resends, sent_at
+            FROM two_fa_codes
+            WHERE email = $1 AND expires_at > $2
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            Utc::now()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok((row.resends as u32, row.sent_at))
+    }
+
+    #[tracing::instrument(name = "Replacing 2FA code in PostgreSQL", skip_all)]
+    async fn replace_code(&mut self, email: &Email, code: TwoFACode) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now();
+
+        // The attempt keeps its expiry and failed attempts.
+        let replaced = sqlx::query!(
+            r#"
+            UPDATE two_fa_codes
+            SET code = $2, resends = resends + 1, sent_at = $3
+            WHERE email = $1 AND expires_at > $3
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            code.to_string(),
+            now
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+            .rows_affected() > 0;
+
+        if !replaced {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::data_stores::behavior_tests;
+    use super::*;
+
+    async fn expire_code(pool: &PgPool, email: &Email) {
+        sqlx::query("UPDATE two_fa_codes SET expires_at = NOW() WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .execute(pool)
+            .await
+            .expect("Failed to expire code");
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_a_two_fa_code_store() {
+        let pool = behavior_tests::get_test_pool().await;
+
+        behavior_tests::two_fa_code_store_behaves(PostgresTwoFACodeStore::new(pool)).await;
+    }
+
+    #[tokio::test]
+    async fn test_expired_code_is_not_found() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresTwoFACodeStore::new(pool.clone());
+        let email = behavior_tests::random_email();
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        expire_code(&pool, &email).await;
+
+        assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+        assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+        assert_eq!(store.replace_code(&email, TwoFACode::default()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let pool = behavior_tests::get_test_pool().await;
+        let mut store = PostgresTwoFACodeStore::new(pool.clone());
+        let expired = behavior_tests::random_email();
+        let live = behavior_tests::random_email();
+
+        store.add_code(&expired, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        store.add_code(&live, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        expire_code(&pool, &expired).await;
+
+        assert!(store.purge_expired().await.unwrap() >= 1);
+
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT email FROM two_fa_codes WHERE email = $1 OR email = $2")
+            .bind(expired.as_ref().expose_secret())
+            .bind(live.as_ref().expose_secret())
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![live.as_ref().expose_secret().to_string()]);
+    }
+}
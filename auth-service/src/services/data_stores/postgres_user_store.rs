@@ -1,4 +1,3 @@
-use std::error::Error;
 use argon2::{
     password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
@@ -6,8 +5,9 @@ use argon2::{
 
 use sqlx::PgPool;
 
-use crate::domain::{Email, User, UserStore, UserStoreError, Password, FromDbString};
-use color_eyre::eyre::{eyre, Context, Result};
+use crate::domain::{Email, User, UserStore, UserStoreError, Password, FromDbString, Locale, TotpSecret, TwoFAMethod};
+use crate::utils::totp::{decrypt_totp_secret, encrypt_totp_secret};
+use color_eyre::eyre::{Context, Result};
 use secrecy::{ExposeSecret, Secret};
 
 #[derive(Debug, Clone)]
@@ -28,21 +28,25 @@ impl UserStore for PostgresUserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
         let password_hash = compute_password_hash(user.password.as_ref().to_owned())
             .await
-            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+            .map_err(UserStoreError::UnexpectedError)?;
 
-        println!("email: {:?}", user.email.as_ref());
-        println!("password_hash: {:?}", password_hash);
-        println!("requires_2fa: {:?}", user.requires_2fa);
-        println!("--------------------------------------");
+        let totp_secret = user.totp_secret
+            .as_ref()
+            .map(encrypt_totp_secret)
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, two_fa_method, totp_secret, verified, locale)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
             user.email.as_ref().expose_secret().to_string(),
             &password_hash.expose_secret().to_string(),
-            user.requires_2fa
+            user.two_fa_method.as_str(),
+            totp_secret,
+            user.verified,
+            user.locale.map(|locale| locale.as_str())
         )
             .execute(&self.pool)
             .await
@@ -55,7 +59,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, two_fa_method, totp_secret, verified, locale
             FROM users
             WHERE email = $1
             "#,
@@ -65,13 +69,22 @@ impl UserStore for PostgresUserStore {
             .await
             .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
             .map(|row| {
-                println!("email: {:?}", &row.email);
-                println!("password_hash: {:?}", &row.password_hash);
-                println!("requires_2fa: {:?}", &row.requires_2fa);
                 Ok(User {
                     email: Email::from_db_string(&row.email),
                     password: Password::from_db_string(&row.password_hash),
-                    requires_2fa: row.requires_2fa,
+                    two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                        .map_err(UserStoreError::UnexpectedError)?,
+                    totp_secret: row.totp_secret
+                        .as_deref()
+                        .map(decrypt_totp_secret)
+                        .transpose()
+                        .map_err(UserStoreError::UnexpectedError)?,
+                    verified: row.verified,
+                    locale: row.locale
+                        .as_deref()
+                        .map(Locale::parse)
+                        .transpose()
+                        .map_err(UserStoreError::UnexpectedError)?,
                 })
             })
             .ok_or(UserStoreError::UserNotFound)?
@@ -92,6 +105,124 @@ impl UserStore for PostgresUserStore {
             .await
             .map_err(|_| UserStoreError::InvalidCredentials)
     }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            &password_hash.expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user email as verified in PostgreSQL", skip_all)]
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Storing TOTP secret in PostgreSQL", skip_all)]
+    async fn set_totp_secret(&mut self, email: &Email, secret: TotpSecret) -> Result<(), UserStoreError> {
+        let encrypted_secret = encrypt_totp_secret(&secret)
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            encrypted_secret
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA method in PostgreSQL", skip_all)]
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_method = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            method.as_str()
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Accepting TOTP step in PostgreSQL", skip_all)]
+    async fn accept_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        let step: i64 = step
+            .try_into()
+            .wrap_err("failed to cast TOTP step to i64")
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        // The condition is checked as the row is updated, so of concurrent requests with one code only one gets it accepted.
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_step = $2
+            WHERE email = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+            "#,
+            email.as_ref().expose_secret().to_string(),
+            step
+        )
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
 
 #[tracing::instrument(name = "Verify password hash", skip_all)]
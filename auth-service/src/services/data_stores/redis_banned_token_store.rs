@@ -1,13 +1,15 @@
-use std::sync::Arc;
+use chrono::Utc;
 use color_eyre::eyre::Context;
 use color_eyre::Report;
-use redis::{Commands, Connection};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
 use thiserror::Error;
-use tokio::sync::RwLock;
 
 use crate::{
-    domain::{BannedTokenStore},
-    utils::auth::TOKEN_TTL_SECONDS,
+    domain::{BannedTokenStore, Email},
+    utils::auth::{BANNED_TOKEN_TTL_SECONDS, TOKEN_TTL_SECONDS},
 };
 
 #[derive(Debug, Error)]
@@ -15,14 +17,41 @@ pub enum BannedTokenStoreError {
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
+/// Banned tokens live only in Redis, since they expire with the token anyway.
+/// Per-user revocations are written to Postgres as well and Redis caches them,
+/// so a Redis restart can't bring revoked tokens back.
+///
+/// The connection is multiplexed, so checks from concurrent requests don't wait on each other.
 #[derive(Clone)]
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    pool: PgPool,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager, pool: PgPool) -> Self {
+        Self { conn, pool }
+    }
+
+    async fn get_tokens_revoked_at_from_postgres(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError> {
+        let revoked_at = sqlx::query_scalar!(
+            r#"
+            SELECT revoked_at_ms
+            FROM token_revocations
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+            .fetch_optional(&self.pool)
+            .await
+            .wrap_err("failed to get token revocation time from PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        revoked_at
+            .map(|revoked_at| revoked_at.try_into())
+            .transpose()
+            .wrap_err("failed to cast token revocation time to usize")
+            .map_err(BannedTokenStoreError::UnexpectedError)
     }
 }
 
@@ -30,46 +59,160 @@ impl RedisBannedTokenStore {
 impl BannedTokenStore for RedisBannedTokenStore {
 
     #[tracing::instrument(name = "Adding banned token to Redis", skip_all)]
-    async fn add_banned_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        let token_key = get_key(token.as_str());
+    async fn add_banned_token(&mut self, jti: String) -> Result<(), BannedTokenStoreError> {
+        let token_key = get_key(jti.as_str());
 
         let value = true;
 
-        let ttl: u64 = TOKEN_TTL_SECONDS
+        let ttl: u64 = BANNED_TOKEN_TTL_SECONDS
             .try_into()
-            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64") // New!
+            .wrap_err("failed to cast BANNED_TOKEN_TTL_SECONDS to u64") // New!
             .map_err(BannedTokenStoreError::UnexpectedError)?; // Updated!
 
         let _: () = self
             .conn
-            .write()
-            .await
             .set_ex(&token_key, value, ttl)
+            .await
             .wrap_err("failed to set banned token in Redis") // New!
             .map_err(BannedTokenStoreError::UnexpectedError)?; // Updated!
 
         Ok(())
     }
 
+    #[tracing::instrument(name = "Claiming token in Redis", skip_all)]
+    async fn claim_token(&mut self, jti: String) -> Result<bool, BannedTokenStoreError> {
+        let ttl: u64 = BANNED_TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast BANNED_TOKEN_TTL_SECONDS to u64")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        // NX, so the token is only claimed by whichever request sets the key first.
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(ttl as usize));
+        let claimed: Option<()> = self
+            .conn
+            .set_options(get_key(&jti), true, options)
+            .await
+            .wrap_err("failed to claim token in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(claimed.is_some())
+    }
+
     #[tracing::instrument(name = "Checking if token is banned in Redis", skip_all)]
-    async fn is_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        let token_key = get_key(token);
+    async fn is_banned(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let token_key = get_key(jti);
 
         let is_banned: bool = self
             .conn
-            .write()
-            .await
+            .clone()
             .exists(&token_key)
+            .await
             .wrap_err("failed to check if token exists in Redis") // New!
             .map_err(BannedTokenStoreError::UnexpectedError)?; // Updated!
 
         Ok(is_banned)
     }
+
+    #[tracing::instrument(name = "Revoking all user tokens in Redis and PostgreSQL", skip_all)]
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        let now = Utc::now().timestamp_millis();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO token_revocations (email, revoked_at_ms)
+            VALUES ($1, $2)
+            ON CONFLICT (email) DO UPDATE SET revoked_at_ms = EXCLUDED.revoked_at_ms
+            "#,
+            email.as_ref().expose_secret(),
+            now
+        )
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to store token revocation time in PostgreSQL")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .set_ex(get_revoked_at_key(email), now, revoked_at_cache_ttl()?)
+            .await
+            .wrap_err("failed to set token revocation time in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting token revocation time from Redis", skip_all)]
+    async fn get_tokens_revoked_at(&self, email: &Email) -> Result<Option<usize>, BannedTokenStoreError> {
+        let key = get_revoked_at_key(email);
+
+        let mut conn = self.conn.clone();
+        let cached: redis::RedisResult<Option<usize>> = conn.get(&key).await;
+        match cached {
+            Ok(Some(NOT_REVOKED)) => return Ok(None),
+            Ok(Some(revoked_at)) => return Ok(Some(revoked_at)),
+            Ok(None) => {},
+            Err(e) => {
+                tracing::warn!("failed to get token revocation time from Redis, using PostgreSQL: {}", e);
+                return self.get_tokens_revoked_at_from_postgres(email).await;
+            },
+        }
+
+        let revoked_at = self.get_tokens_revoked_at_from_postgres(email).await?;
+
+        // NX, so a revocation made since the lookup isn't overwritten with an older value.
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(revoked_at_cache_ttl()? as usize));
+        let _: () = conn
+            .set_options(&key, revoked_at.unwrap_or(NOT_REVOKED), options)
+            .await
+            .wrap_err("failed to cache token revocation time in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(revoked_at)
+    }
+}
+
+// Cached for as long as a token lives, since older revocations can't reject anything.
+fn revoked_at_cache_ttl() -> Result<u64, BannedTokenStoreError> {
+    TOKEN_TTL_SECONDS
+        .try_into()
+        .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
+        .map_err(BannedTokenStoreError::UnexpectedError)
 }
 
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+// Revocations used to be cached in seconds under another prefix, which this one can't be mistaken for.
+const TOKENS_REVOKED_AT_KEY_PREFIX: &str = "tokens_revoked_at_ms:";
+// Cached for users who were never revoked, so they don't hit Postgres on every request.
+const NOT_REVOKED: usize = 0;
+
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
+
+fn get_revoked_at_key(email: &Email) -> String {
+    format!("{}{}", TOKENS_REVOKED_AT_KEY_PREFIX, email.as_ref().expose_secret())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::get_redis_connection;
+    use crate::services::data_stores::behavior_tests;
+    use crate::utils::constants::REDIS_HOST_NAME;
+    use super::*;
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+    #[tokio::test]
+    async fn test_behaves_like_a_banned_token_store() {
+        let conn = get_redis_connection(REDIS_HOST_NAME.to_owned())
+            .await
+            .expect("Failed to get Redis connection");
+        let pool = behavior_tests::get_test_pool().await;
+        let email = behavior_tests::add_test_user(&pool).await;
+
+        behavior_tests::banned_token_store_behaves(RedisBannedTokenStore::new(conn, pool), &email).await;
+    }
 }
@@ -0,0 +1,188 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::Context;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Script};
+
+use crate::domain::{LoginAttemptKey, LoginAttemptStore, LoginAttemptStoreError, LoginFailures};
+use crate::utils::constants::LOGIN_LOCKOUT_SECONDS;
+
+/// Each count is a hash that expires [LOGIN_LOCKOUT_SECONDS] after the last failure,
+/// so every replica of the service sees the same counts.
+#[derive(Clone)]
+pub struct RedisLoginAttemptStore {
+    conn: ConnectionManager,
+}
+
+impl RedisLoginAttemptStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+const LOGIN_FAILURES_KEY_PREFIX: &str = "login_failures:";
+const COUNT_FIELD: &str = "count";
+/// Milliseconds since the Unix epoch.
+const LAST_FAILURE_AT_FIELD: &str = "last_failure_at";
+
+// Only takes back from a count that's still there, so a reset or expired one isn't recreated without an expiry.
+// The time and expiry of the failures before the attempt come back only if no other attempt was counted since.
+const RELEASE_ATTEMPT_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    local count = redis.call('HINCRBY', KEYS[1], ARGV[1], -1)
+    if count == tonumber(ARGV[3]) and ARGV[4] ~= '' then
+        redis.call('HSET', KEYS[1], ARGV[2], ARGV[4])
+        redis.call('PEXPIREAT', KEYS[1], ARGV[5])
+    end
+end
+"#;
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for RedisLoginAttemptStore {
+
+    #[tracing::instrument(name = "Retrieving login failures from Redis", skip_all)]
+    async fn get_failures(&self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let (count, last_failure_at): (Option<u32>, Option<i64>) = self.conn.clone()
+            .hget(get_key(key), &[COUNT_FIELD, LAST_FAILURE_AT_FIELD])
+            .await
+            .wrap_err("failed to get login failures from Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(to_failures(count, last_failure_at))
+    }
+
+    #[tracing::instrument(name = "Reserving login attempt in Redis", skip_all)]
+    async fn reserve_attempt(&mut self, key: &LoginAttemptKey) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let key = get_key(key);
+
+        // The transaction reads the failures before counting this attempt, so no other attempt can get in between.
+        let ((count, last_failure_at),): ((Option<u32>, Option<i64>),) = redis::pipe()
+            .atomic()
+            .hget(&key, &[COUNT_FIELD, LAST_FAILURE_AT_FIELD])
+            .hincr(&key, COUNT_FIELD, 1)
+            .ignore()
+            .hset(&key, LAST_FAILURE_AT_FIELD, Utc::now().timestamp_millis())
+            .ignore()
+            .expire(&key, *LOGIN_LOCKOUT_SECONDS)
+            .ignore()
+            .query_async(&mut self.conn)
+            .await
+            .wrap_err("failed to reserve login attempt in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(to_failures(count, last_failure_at))
+    }
+
+    #[tracing::instrument(name = "Releasing login attempt in Redis", skip_all)]
+    async fn release_attempt(&mut self, key: &LoginAttemptKey, previous: Option<LoginFailures>) -> Result<(), LoginAttemptStoreError> {
+        let restored_failure_at = previous.map(|previous| previous.last_failure_at);
+        let _: () = Script::new(RELEASE_ATTEMPT_SCRIPT)
+            .key(get_key(key))
+            .arg(COUNT_FIELD)
+            .arg(LAST_FAILURE_AT_FIELD)
+            .arg(previous.map_or(0, |previous| previous.count))
+            .arg(restored_failure_at.map_or(String::new(), |failure_at| failure_at.timestamp_millis().to_string()))
+            .arg(restored_failure_at.map_or(0, |failure_at| (failure_at + Duration::seconds(*LOGIN_LOCKOUT_SECONDS)).timestamp_millis()))
+            .invoke_async(&mut self.conn)
+            .await
+            .wrap_err("failed to release login attempt in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Resetting login failures in Redis", skip_all)]
+    async fn reset_failures(&mut self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        let _: () = self.conn
+            .del(get_key(key))
+            .await
+            .wrap_err("failed to reset login failures in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn to_failures(count: Option<u32>, last_failure_at: Option<i64>) -> Option<LoginFailures> {
+    count.zip(last_failure_at).map(|(count, last_failure_at)| LoginFailures {
+        count,
+        last_failure_at: DateTime::from_timestamp_millis(last_failure_at).unwrap_or_default(),
+    })
+}
+
+fn get_key(key: &LoginAttemptKey) -> String {
+    format!("{}{}", LOGIN_FAILURES_KEY_PREFIX, key.as_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::get_redis_connection;
+    use crate::services::data_stores::behavior_tests;
+    use crate::utils::constants::REDIS_HOST_NAME;
+    use super::*;
+
+    async fn create_store() -> RedisLoginAttemptStore {
+        let conn = get_redis_connection(REDIS_HOST_NAME.to_owned())
+            .await
+            .expect("Failed to get Redis connection");
+        RedisLoginAttemptStore::new(conn)
+    }
+
+    // Each test uses its own key, since they share one Redis.
+    fn random_key() -> LoginAttemptKey {
+        LoginAttemptKey::Email(behavior_tests::random_email())
+    }
+
+    async fn get_ttl(store: &RedisLoginAttemptStore, key: &LoginAttemptKey) -> i64 {
+        store.conn.clone().ttl(get_key(key)).await.expect("Failed to get TTL")
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_a_login_attempt_store() {
+        behavior_tests::login_attempt_store_behaves(create_store().await).await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_attempts_are_each_counted() {
+        let store = create_store().await;
+        let key = random_key();
+
+        let attempts = (0..10).map(|_| {
+            let mut store = store.clone();
+            let key = key.clone();
+            tokio::spawn(async move { store.reserve_attempt(&key).await })
+        });
+        let mut previous_counts = HashSet::new();
+        for attempt in attempts {
+            let previous = attempt.await.unwrap().unwrap();
+            previous_counts.insert(previous.map_or(0, |failures| failures.count));
+        }
+
+        assert_eq!(previous_counts, (0..10).collect());
+        assert_eq!(store.get_failures(&key).await.unwrap().map(|failures| failures.count), Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_failures_expire_after_the_lockout() {
+        let mut store = create_store().await;
+        let key = random_key();
+
+        store.reserve_attempt(&key).await.unwrap();
+
+        let ttl = get_ttl(&store, &key).await;
+        assert!(ttl > 0 && ttl <= *LOGIN_LOCKOUT_SECONDS);
+    }
+
+    #[tokio::test]
+    async fn test_released_attempt_doesnt_recreate_reset_failures() {
+        let mut store = create_store().await;
+        let key = random_key();
+
+        let previous = store.reserve_attempt(&key).await.unwrap();
+        store.reset_failures(&key).await.unwrap();
+        store.release_attempt(&key, previous).await.unwrap();
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(get_ttl(&store, &key).await, -2);
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use secrecy::ExposeSecret;
+
+use crate::domain::{Email, FromDbString, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError};
+use crate::utils::constants::PASSWORD_RESET_TOKEN_TTL_SECONDS;
+
+#[derive(Clone)]
+pub struct RedisPasswordResetTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+const PASSWORD_RESET_EMAIL_PREFIX: &str = "password_reset_email:";
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+
+    #[tracing::instrument(name = "Adding password reset token to Redis", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let ttl: u64 = PASSWORD_RESET_TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast PASSWORD_RESET_TOKEN_TTL_SECONDS to u64")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let email_key = get_email_key(email);
+        let conn = &mut self.conn;
+
+        // Only the most recently requested token should be usable.
+        let previous_token: Option<String> = conn.get(&email_key)
+            .await
+            .wrap_err("failed to get previous password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        if let Some(previous_token) = previous_token {
+            let _: () = conn.del(get_token_key(&previous_token))
+                .await
+                .wrap_err("failed to delete previous password reset token from Redis")
+                .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        }
+
+        let _: () = conn.set_ex(get_token_key(token.as_ref()), email.as_ref().expose_secret(), ttl)
+            .await
+            .wrap_err("failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        let _: () = conn.set_ex(&email_key, token.as_ref(), ttl)
+            .await
+            .wrap_err("failed to set password reset token email in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming password reset token from Redis", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let conn = &mut self.conn;
+
+        // GETDEL makes sure two concurrent requests can't both use the token.
+        let email: Option<String> = conn.get_del(get_token_key(token.as_ref()))
+            .await
+            .wrap_err("failed to consume password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        let email = Email::from_db_string(&email.ok_or(PasswordResetTokenStoreError::TokenNotFound)?);
+
+        let _: () = conn.del(get_email_key(&email))
+            .await
+            .wrap_err("failed to delete password reset token email from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(email)
+    }
+
+    #[tracing::instrument(name = "Getting password reset token issue time from Redis", skip_all)]
+    async fn get_token_issued_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<DateTime<Utc>>, PasswordResetTokenStoreError> {
+        // The user's token key is set to expire PASSWORD_RESET_TOKEN_TTL_SECONDS after it was issued.
+        let remaining_ms: i64 = self.conn.clone()
+            .pttl(get_email_key(email))
+            .await
+            .wrap_err("failed to get password reset token expiry from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        if remaining_ms < 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Utc::now() + Duration::milliseconds(remaining_ms) - Duration::seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS)))
+    }
+}
+
+fn get_token_key(token: &str) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, token)
+}
+
+fn get_email_key(email: &Email) -> String {
+    format!("{}{}", PASSWORD_RESET_EMAIL_PREFIX, email.as_ref().expose_secret())
+}
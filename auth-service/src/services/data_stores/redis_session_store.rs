@@ -0,0 +1,206 @@
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Email, FromDbString, Session, SessionId, SessionStore, SessionStoreError};
+use crate::utils::auth::REFRESH_TOKEN_TTL_SECONDS;
+
+/// Each session is a JSON value that expires once it's gone unused for as long as a refresh token lasts,
+/// plus a set per user with the IDs of their sessions.
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+const SESSION_KEY_PREFIX: &str = "session:";
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+#[derive(Serialize, Deserialize)]
+struct StoredSession {
+    email: String,
+    device_label: String,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    /// Milliseconds since the Unix epoch, as chrono is built without serde support.
+    created_at: i64,
+    last_seen_at: i64,
+}
+
+impl StoredSession {
+    fn into_session(self, id: SessionId) -> Session {
+        Session {
+            id,
+            email: Email::from_db_string(&self.email),
+            device_label: self.device_label,
+            user_agent: self.user_agent,
+            ip_address: self.ip_address,
+            created_at: from_timestamp_millis(self.created_at),
+            last_seen_at: from_timestamp_millis(self.last_seen_at),
+        }
+    }
+}
+
+fn from_timestamp_millis(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+fn session_ttl() -> Result<u64, SessionStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .wrap_err("failed to cast REFRESH_TOKEN_TTL_SECONDS to u64")
+        .map_err(SessionStoreError::UnexpectedError)
+}
+
+async fn get_stored_session(conn: &mut ConnectionManager, id: &SessionId) -> Result<Option<StoredSession>, SessionStoreError> {
+    let json: Option<String> = conn.get(get_session_key(id))
+        .await
+        .wrap_err("failed to get session from Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    json.map(|json| serde_json::from_str(&json))
+        .transpose()
+        .wrap_err("failed to deserialize session")
+        .map_err(SessionStoreError::UnexpectedError)
+}
+
+async fn set_stored_session(conn: &mut ConnectionManager, id: &SessionId, session: &StoredSession) -> Result<(), SessionStoreError> {
+    let json = serde_json::to_string(session)
+        .wrap_err("failed to serialize session")
+        .map_err(SessionStoreError::UnexpectedError)?;
+    let ttl = session_ttl()?;
+    let user_sessions_key = get_user_sessions_key(&session.email);
+
+    let _: () = redis::pipe()
+        .atomic()
+        .set_ex(get_session_key(id), json, ttl)
+        .sadd(&user_sessions_key, id.as_ref())
+        .expire(&user_sessions_key, ttl as i64)
+        .query_async(conn)
+        .await
+        .wrap_err("failed to set session in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+
+    #[tracing::instrument(name = "Adding session to Redis", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let stored = StoredSession {
+            email: session.email.as_ref().expose_secret().to_string(),
+            device_label: session.device_label,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at.timestamp_millis(),
+            last_seen_at: session.last_seen_at.timestamp_millis(),
+        };
+
+        set_stored_session(&mut self.conn, &session.id, &stored).await
+    }
+
+    #[tracing::instrument(name = "Retrieving session from Redis", skip_all)]
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        get_stored_session(&mut self.conn.clone(), id).await?
+            .map(|stored| stored.into_session(id.clone()))
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from Redis", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.clone();
+        let user_sessions_key = get_user_sessions_key(email.as_ref().expose_secret());
+
+        let ids: Vec<String> = conn.smembers(&user_sessions_key)
+            .await
+            .wrap_err("failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in ids {
+            let id = SessionId::from_db_string(&id);
+            match get_stored_session(&mut conn, &id).await? {
+                Some(stored) => sessions.push(stored.into_session(id)),
+                // The session expired, so its ID is no longer needed either.
+                None => {
+                    let _: () = conn.srem(&user_sessions_key, id.as_ref())
+                        .await
+                        .wrap_err("failed to remove expired session from Redis")
+                        .map_err(SessionStoreError::UnexpectedError)?;
+                },
+            }
+        }
+
+        sessions.sort_by_key(|session| Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Updating session last seen time in Redis", skip_all)]
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let mut stored = get_stored_session(&mut self.conn, id).await?
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        stored.last_seen_at = Utc::now().timestamp_millis();
+
+        set_stored_session(&mut self.conn, id, &stored).await
+    }
+
+    #[tracing::instrument(name = "Removing session from Redis", skip_all)]
+    async fn remove_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let stored = get_stored_session(&mut self.conn, id).await?
+            .ok_or(SessionStoreError::SessionNotFound)?;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .del(get_session_key(id))
+            .srem(get_user_sessions_key(&stored.email), id.as_ref())
+            .query_async(&mut self.conn)
+            .await
+            .wrap_err("failed to remove session from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all sessions from Redis", skip_all)]
+    async fn remove_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        let user_sessions_key = get_user_sessions_key(email.as_ref().expose_secret());
+
+        let ids: Vec<String> = self.conn.smembers(&user_sessions_key)
+            .await
+            .wrap_err("failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&user_sessions_key);
+        for id in ids {
+            pipe.del(get_session_key(&SessionId::from_db_string(&id)));
+        }
+
+        let _: () = pipe.query_async(&mut self.conn)
+            .await
+            .wrap_err("failed to remove sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn get_session_key(id: &SessionId) -> String {
+    format!("{}{}", SESSION_KEY_PREFIX, id.as_ref())
+}
+
+fn get_user_sessions_key(email: &str) -> String {
+    format!("{}{}", USER_SESSIONS_KEY_PREFIX, email)
+}
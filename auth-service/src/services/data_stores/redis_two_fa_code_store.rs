@@ -1,21 +1,52 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, ExistenceCheck, Script, SetExpiry, SetOptions};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 use crate::domain::{Email, FromDbString, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::utils::constants::TWO_FA_CODE_TTL_SECONDS;
 
+/// Each pending login attempt is a JSON value that expires [TWO_FA_CODE_TTL_SECONDS] after it's added,
+/// so codes survive restarts and every replica of the service sees the same ones.
+/// Its failed attempts are counted in a key of their own, so concurrent guesses can't overwrite each other's count.
 #[derive(Clone)]
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
+impl RedisTwoFACodeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+/// Login attempt id, code, failed attempts, resends and when the code was last sent in unix millis.
+/// Failed attempts are counted under [TWO_FA_FAILED_ATTEMPTS_PREFIX] instead, the field is only kept so stored values still parse.
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFATuple(
+    pub String,
+    pub String,
+    #[serde(default)] pub u32,
+    #[serde(default)] pub u32,
+    #[serde(default)] pub i64,
+);
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_FAILED_ATTEMPTS_PREFIX: &str = "two_fa_failed_attempts:";
+
+// Counts a failed attempt only while the code exists, and lets the count expire with it.
+// It's a script so the check and the increment can't interleave with other requests.
+const RECORD_FAILED_ATTEMPT_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return false
+end
+local failed_attempts = redis.call('INCR', KEYS[2])
+if failed_attempts == 1 then
+    redis.call('PEXPIRE', KEYS[2], redis.call('PTTL', KEYS[1]))
+end
+return failed_attempts
+"#;
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore{
@@ -28,14 +59,30 @@ impl TwoFACodeStore for RedisTwoFACodeStore{
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>
     {
-        let key = get_key(&email);
-        let two_fa_tuple = TwoFATuple(login_attempt_id.as_ref().to_string(), code.to_string());
+        let key = get_key(email);
+        let two_fa_tuple = TwoFATuple(
+            login_attempt_id.as_ref().to_string(),
+            code.to_string(),
+            0,
+            0,
+            Utc::now().timestamp_millis(),
+        );
 
         let json = serde_json::to_string(&two_fa_tuple)
             .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
 
-        let mut conn = self.conn.write().await;
-        conn.set_ex(key, json, TEN_MINUTES_IN_SECONDS)
+        let ttl: u64 = TWO_FA_CODE_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast TWO_FA_CODE_TTL_SECONDS to u64")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // A new login attempt starts counting again.
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(key, json, ttl)
+            .del(get_failed_attempts_key(email))
+            .query_async(&mut self.conn)
+            .await
             .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
 
         Ok(())
@@ -43,9 +90,8 @@ impl TwoFACodeStore for RedisTwoFACodeStore{
 
     #[tracing::instrument(name = "Removing 2FA code from Redis", skip_all)]
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
-        let mut conn = self.conn.write().await;
-        conn.del(key)
+        let _: () = self.conn.del(&[get_key(email), get_failed_attempts_key(email)])
+            .await
             .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
 
         Ok(())
@@ -57,21 +103,230 @@ impl TwoFACodeStore for RedisTwoFACodeStore{
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         let key = get_key(email);
-        let mut conn = self.conn.write().await;
-        let json: String = conn.get(key)
-            .map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
-
-        let TwoFATuple(login_attempt_id, code) = serde_json::from_str(&json)
-            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+        let TwoFATuple(login_attempt_id, code, ..) = get_tuple(&mut self.conn.clone(), &key).await?;
 
         Ok((
             LoginAttemptId::from_db_string(&login_attempt_id),
             TwoFACode::parse(code)
-                .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+                .map_err(TwoFACodeStoreError::UnexpectedError)?
         ))
     }
+
+    #[tracing::instrument(name = "Recording failed 2FA attempt in Redis", skip_all)]
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<u32, TwoFACodeStoreError> {
+        let failed_attempts: Option<u32> = Script::new(RECORD_FAILED_ATTEMPT_SCRIPT)
+            .key(get_key(email))
+            .key(get_failed_attempts_key(email))
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+        failed_attempts.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    #[tracing::instrument(name = "Getting 2FA code resends from Redis", skip_all)]
+    async fn get_resends(&self, email: &Email) -> Result<(u32, DateTime<Utc>), TwoFACodeStoreError> {
+        let key = get_key(email);
+        let TwoFATuple(_, _, _, resends, sent_at) = get_tuple(&mut self.conn.clone(), &key).await?;
+
+        let sent_at = DateTime::from_timestamp_millis(sent_at)
+            .ok_or(TwoFACodeStoreError::UnexpectedError(eyre!("invalid 2FA code sent_at timestamp")))?;
+
+        Ok((resends, sent_at))
+    }
+
+    #[tracing::instrument(name = "Replacing 2FA code in Redis", skip_all)]
+    async fn replace_code(&mut self, email: &Email, code: TwoFACode) -> Result<(), TwoFACodeStoreError> {
+        let key = get_key(email);
+        let TwoFATuple(login_attempt_id, _, failed_attempts, resends, _) = get_tuple(&mut self.conn, &key).await?;
+        let two_fa_tuple = TwoFATuple(
+            login_attempt_id,
+            code.to_string(),
+            failed_attempts,
+            resends + 1,
+            Utc::now().timestamp_millis(),
+        );
+
+        set_tuple_keep_ttl(&mut self.conn, &key, &two_fa_tuple).await
+    }
+}
+
+async fn get_tuple(conn: &mut ConnectionManager, key: &str) -> Result<TwoFATuple, TwoFACodeStoreError> {
+    let json: Option<String> = conn.get(key)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+    serde_json::from_str(&json.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?)
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))
+}
+
+// Keeps the expiry set when the code was added, so wrong guesses and resends don't extend it.
+// XX, so a code that expired since it was read isn't stored again without an expiry.
+async fn set_tuple_keep_ttl(conn: &mut ConnectionManager, key: &str, two_fa_tuple: &TwoFATuple) -> Result<(), TwoFACodeStoreError> {
+    let json = serde_json::to_string(two_fa_tuple)
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+    let options = SetOptions::default()
+        .conditional_set(ExistenceCheck::XX)
+        .with_expiration(SetExpiry::KEEPTTL);
+    let updated: Option<()> = conn.set_options(key, json, options)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+
+    updated.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
 }
 
 fn get_key(email: &Email) -> String {
     format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
 }
+
+fn get_failed_attempts_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_FAILED_ATTEMPTS_PREFIX, email.as_ref().expose_secret())
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+    use crate::get_redis_connection;
+    use crate::services::data_stores::behavior_tests;
+    use crate::utils::constants::REDIS_HOST_NAME;
+    use super::*;
+
+    async fn create_store() -> RedisTwoFACodeStore {
+        let conn = get_redis_connection(REDIS_HOST_NAME.to_owned())
+            .await
+            .expect("Failed to get Redis connection");
+        RedisTwoFACodeStore::new(conn)
+    }
+
+    // Each test uses its own email, since they share one Redis.
+    fn create_email() -> Email {
+        Email::parse(Secret::new(format!("{}@somedomain.com", Uuid::new_v4())))
+            .expect("Failed to create Email")
+    }
+
+    async fn get_ttl(store: &RedisTwoFACodeStore, email: &Email) -> i64 {
+        store.conn.clone().ttl(get_key(email)).await.expect("Failed to get TTL")
+    }
+
+    #[tokio::test]
+    async fn test_behaves_like_a_two_fa_code_store() {
+        behavior_tests::two_fa_code_store_behaves(create_store().await).await;
+    }
+
+    #[tokio::test]
+    async fn test_add_code() {
+        let mut store = create_store().await;
+        let email = create_email();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        store.add_code(&email, login_attempt_id.clone(), code.clone())
+            .await.expect("Failed to add code");
+
+        assert_eq!(store.get_code(&email).await, Ok((login_attempt_id, code)));
+        let ttl = get_ttl(&store, &email).await;
+        assert!(ttl > 0 && ttl <= TWO_FA_CODE_TTL_SECONDS);
+    }
+
+    #[tokio::test]
+    async fn test_remove_code() {
+        let mut store = create_store().await;
+        let email = create_email();
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        store.remove_code(&email)
+            .await.expect("Failed to remove code");
+
+        assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_expired_code_is_not_found() {
+        let mut store = create_store().await;
+        let email = create_email();
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        let _: () = store.conn.del(get_key(&email)).await.expect("Failed to expire code");
+
+        assert_eq!(store.get_code(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+        assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+        assert_eq!(store.replace_code(&email, TwoFACode::default()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = create_store().await;
+        let email = create_email();
+
+        assert_eq!(store.record_failed_attempt(&email).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+
+        // A new login attempt starts counting again.
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_failed_attempts_are_all_counted() {
+        let mut store = create_store().await;
+        let email = create_email();
+
+        store.add_code(&email, LoginAttemptId::default(), TwoFACode::default())
+            .await.expect("Failed to add code");
+
+        let mut attempts = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            let mut store = store.clone();
+            let email = email.clone();
+            attempts.spawn(async move { store.record_failed_attempt(&email).await });
+        }
+        let mut counts = Vec::new();
+        while let Some(result) = attempts.join_next().await {
+            counts.push(result.unwrap().expect("Failed to record failed attempt"));
+        }
+        counts.sort();
+
+        assert_eq!(counts, (1..=10).collect::<Vec<u32>>());
+        let ttl: i64 = store.conn.clone().ttl(get_failed_attempts_key(&email)).await.expect("Failed to get TTL");
+        assert!(ttl > 0 && ttl <= TWO_FA_CODE_TTL_SECONDS);
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut store = create_store().await;
+        let email = create_email();
+        let login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(store.replace_code(&email, TwoFACode::default()).await, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+        store.add_code(&email, login_attempt_id.clone(), TwoFACode::parse("123456".to_string()).unwrap())
+            .await.expect("Failed to add code");
+        store.record_failed_attempt(&email).await.expect("Failed to record failed attempt");
+        let (resends, first_sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+        assert_eq!(resends, 0);
+        // Shorten the expiry, so it's clear replacing the code didn't reset it.
+        let _: () = store.conn.expire(get_key(&email), 60).await.expect("Failed to set TTL");
+
+        let new_code = TwoFACode::parse("654321".to_string()).unwrap();
+        store.replace_code(&email, new_code.clone())
+            .await.expect("Failed to replace code");
+
+        assert_eq!(store.get_code(&email).await, Ok((login_attempt_id, new_code)));
+        let (resends, sent_at) = store.get_resends(&email).await.expect("Failed to get resends");
+        assert_eq!(resends, 1);
+        assert!(sent_at >= first_sent_at);
+        // The attempt keeps its expiry and failed attempts.
+        let ttl = get_ttl(&store, &email).await;
+        assert!(ttl > 0 && ttl <= 60);
+        assert_eq!(store.record_failed_attempt(&email).await, Ok(2));
+    }
+}
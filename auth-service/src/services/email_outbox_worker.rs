@@ -0,0 +1,177 @@
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use tokio::sync::{Notify, RwLock};
+use crate::domain::{EmailClient, EmailOutboxStore, EmailOutboxStoreError};
+use crate::utils::constants::{
+    EMAIL_OUTBOX_BATCH_SIZE,
+    EMAIL_OUTBOX_POLL_INTERVAL_SECONDS,
+    EMAIL_RETRY_BASE_SECONDS,
+    EMAIL_RETRY_MAX_SECONDS,
+    MAX_EMAIL_DELIVERY_ATTEMPTS,
+};
+
+/// How long to wait before the next attempt, after `attempts` failed ones.
+/// Doubles with every failure, up to [EMAIL_RETRY_MAX_SECONDS].
+pub fn retry_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(30);
+    let seconds = EMAIL_RETRY_BASE_SECONDS.saturating_mul(1 << doublings);
+    Duration::seconds(seconds.min(EMAIL_RETRY_MAX_SECONDS))
+}
+
+/// Delivers the emails routes put in the outbox, so a slow or unavailable mail server never holds up a request.
+///
+/// Failed deliveries are retried after [retry_delay], and dead-lettered after [MAX_EMAIL_DELIVERY_ATTEMPTS].
+pub struct EmailOutboxWorker<E: EmailOutboxStore, W: EmailClient> {
+    outbox: Arc<RwLock<E>>,
+    email_client: Arc<RwLock<W>>,
+    wakeup: Arc<Notify>,
+}
+
+impl<E: EmailOutboxStore, W: EmailClient> EmailOutboxWorker<E, W> {
+    /// `wakeup` is notified whenever an email is queued, so it goes out without waiting for the next poll.
+    pub fn new(outbox: Arc<RwLock<E>>, email_client: Arc<RwLock<W>>, wakeup: Arc<Notify>) -> Self {
+        Self { outbox, email_client, wakeup }
+    }
+
+    pub async fn run(self) {
+        let poll_interval = std::time::Duration::from_secs(EMAIL_OUTBOX_POLL_INTERVAL_SECONDS);
+        loop {
+            if let Err(e) = self.deliver_due().await {
+                tracing::error!("failed to deliver queued emails: {:?}", e);
+            }
+
+            tokio::select! {
+                _ = self.wakeup.notified() => {},
+                _ = tokio::time::sleep(poll_interval) => {},
+            }
+        }
+    }
+
+    /// Tries every email that's due, and returns how many were sent.
+    #[tracing::instrument(name = "Delivering queued emails", skip_all)]
+    pub async fn deliver_due(&self) -> Result<usize, EmailOutboxStoreError> {
+        let mut sent = 0;
+        loop {
+            let emails = self.outbox.write().await
+                .claim_due(EMAIL_OUTBOX_BATCH_SIZE)
+                .await?;
+            let batch_size = emails.len();
+
+            for email in emails {
+                let result = self.email_client.read().await
+                    .send_email(&email.recipient, &email.message)
+                    .await;
+
+                let mut outbox = self.outbox.write().await;
+                match result {
+                    Ok(()) => {
+                        outbox.mark_sent(&email.id).await?;
+                        sent += 1;
+                    },
+                    Err(e) => {
+                        let attempts = email.attempts + 1;
+                        let retry_at = (attempts < MAX_EMAIL_DELIVERY_ATTEMPTS)
+                            .then(|| Utc::now() + retry_delay(attempts));
+                        match retry_at {
+                            Some(retry_at) => tracing::warn!("email {} failed, retrying at {}: {}", email.id.as_ref(), retry_at, e),
+                            None => tracing::error!("email {} failed {} times, giving up: {}", email.id.as_ref(), attempts, e),
+                        }
+                        outbox.record_failure(&email.id, &e, retry_at).await?;
+                    },
+                }
+            }
+
+            // A full batch means more could be waiting.
+            if batch_size < EMAIL_OUTBOX_BATCH_SIZE as usize {
+                return Ok(sent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use crate::domain::{DeliveryStatus, Email, EmailMessage, QueuedEmail};
+    use crate::services::{HashmapEmailOutboxStore, RecordingEmailClient};
+    use super::*;
+
+    #[derive(Clone)]
+    struct FailingEmailClient;
+
+    #[async_trait::async_trait]
+    impl EmailClient for FailingEmailClient {
+        async fn send_email(&self, _recipient: &Email, _message: &EmailMessage) -> Result<(), String> {
+            Err("connection refused".to_string())
+        }
+    }
+
+    fn create_email() -> QueuedEmail {
+        let recipient = Email::parse(Secret::new("someemail@somedomain.com".to_string()))
+            .expect("Failed to create Email");
+        QueuedEmail::new(recipient, EmailMessage {
+            subject: "Subject".to_string(),
+            text: "123456".to_string(),
+            html: "<p>123456</p>".to_string(),
+        })
+    }
+
+    fn worker<W: EmailClient>(outbox: &Arc<RwLock<HashmapEmailOutboxStore>>, email_client: W) -> EmailOutboxWorker<HashmapEmailOutboxStore, W> {
+        EmailOutboxWorker::new(outbox.clone(), Arc::new(RwLock::new(email_client)), Arc::new(Notify::new()))
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_max() {
+        assert_eq!(retry_delay(1), Duration::seconds(EMAIL_RETRY_BASE_SECONDS));
+        assert_eq!(retry_delay(2), Duration::seconds(EMAIL_RETRY_BASE_SECONDS * 2));
+        assert_eq!(retry_delay(3), Duration::seconds(EMAIL_RETRY_BASE_SECONDS * 4));
+        assert_eq!(retry_delay(100), Duration::seconds(EMAIL_RETRY_MAX_SECONDS));
+    }
+
+    #[tokio::test]
+    async fn delivers_and_marks_sent() {
+        let outbox = Arc::new(RwLock::new(HashmapEmailOutboxStore::default()));
+        let email = create_email();
+        outbox.write().await.enqueue(email.clone()).await.unwrap();
+        let email_client = RecordingEmailClient::default();
+
+        let sent = worker(&outbox, email_client.clone()).deliver_due().await.unwrap();
+
+        assert_eq!(sent, 1);
+        assert_eq!(email_client.last_email_to(&email.recipient, "Subject").await.unwrap().message, email.message);
+        let stored = outbox.read().await.get_emails(&email.recipient).await.unwrap().remove(0);
+        assert_eq!(stored.status, DeliveryStatus::Sent);
+    }
+
+    #[tokio::test]
+    async fn failure_is_retried_later() {
+        let outbox = Arc::new(RwLock::new(HashmapEmailOutboxStore::default()));
+        let email = create_email();
+        outbox.write().await.enqueue(email.clone()).await.unwrap();
+
+        let sent = worker(&outbox, FailingEmailClient).deliver_due().await.unwrap();
+
+        assert_eq!(sent, 0);
+        let stored = outbox.read().await.get_emails(&email.recipient).await.unwrap().remove(0);
+        assert_eq!(stored.status, DeliveryStatus::Pending);
+        assert_eq!(stored.attempts, 1);
+        assert_eq!(stored.last_error.as_deref(), Some("connection refused"));
+        assert!(stored.next_attempt_at > Utc::now() + retry_delay(1) - Duration::seconds(5));
+    }
+
+    #[tokio::test]
+    async fn last_failure_dead_letters() {
+        let outbox = Arc::new(RwLock::new(HashmapEmailOutboxStore::default()));
+        let email = QueuedEmail {
+            attempts: MAX_EMAIL_DELIVERY_ATTEMPTS - 1,
+            ..create_email()
+        };
+        outbox.write().await.enqueue(email.clone()).await.unwrap();
+
+        worker(&outbox, FailingEmailClient).deliver_due().await.unwrap();
+
+        let stored = outbox.read().await.get_emails(&email.recipient).await.unwrap().remove(0);
+        assert_eq!(stored.status, DeliveryStatus::DeadLettered);
+        assert_eq!(stored.attempts, MAX_EMAIL_DELIVERY_ATTEMPTS);
+    }
+}
@@ -0,0 +1,60 @@
+use sqlx::PgPool;
+use crate::services::{PostgresBannedTokenStore, PostgresLoginAttemptStore, PostgresPasskeyStore, PostgresRefreshTokenStore, PostgresTwoFACodeStore};
+use crate::utils::constants::EXPIRED_RECORD_PURGE_INTERVAL_SECONDS;
+
+/// Deletes the rows that have expired from the Postgres stores that keep expiring records.
+///
+/// Redis expires its keys by itself. Postgres stores only ignore expired rows, so without this the tables would keep growing.
+pub struct ExpiredRecordPurger {
+    banned_token_store: PostgresBannedTokenStore,
+    two_fa_code_store: PostgresTwoFACodeStore,
+    refresh_token_store: PostgresRefreshTokenStore,
+    passkey_store: PostgresPasskeyStore,
+    login_attempt_store: PostgresLoginAttemptStore,
+}
+
+impl ExpiredRecordPurger {
+    /// Purges every such table, whether or not the store is in use. Unused ones are just empty.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            banned_token_store: PostgresBannedTokenStore::new(pool.clone()),
+            two_fa_code_store: PostgresTwoFACodeStore::new(pool.clone()),
+            refresh_token_store: PostgresRefreshTokenStore::new(pool.clone()),
+            passkey_store: PostgresPasskeyStore::new(pool.clone()),
+            login_attempt_store: PostgresLoginAttemptStore::new(pool),
+        }
+    }
+
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(EXPIRED_RECORD_PURGE_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            self.purge().await;
+        }
+    }
+
+    /// Failures are only logged, the rows are purged on the next run instead.
+    #[tracing::instrument(name = "Purging expired records", skip_all)]
+    pub async fn purge(&self) {
+        match self.banned_token_store.purge_expired().await {
+            Ok(purged) => tracing::debug!("purged {} expired banned tokens", purged),
+            Err(e) => tracing::error!("failed to purge expired banned tokens: {:?}", e),
+        }
+        match self.two_fa_code_store.purge_expired().await {
+            Ok(purged) => tracing::debug!("purged {} expired 2FA codes", purged),
+            Err(e) => tracing::error!("failed to purge expired 2FA codes: {:?}", e),
+        }
+        match self.refresh_token_store.purge_expired().await {
+            Ok(purged) => tracing::debug!("purged {} expired refresh tokens", purged),
+            Err(e) => tracing::error!("failed to purge expired refresh tokens: {:?}", e),
+        }
+        match self.passkey_store.purge_expired().await {
+            Ok(purged) => tracing::debug!("purged {} expired passkey challenges", purged),
+            Err(e) => tracing::error!("failed to purge expired passkey challenges: {:?}", e),
+        }
+        match self.login_attempt_store.purge_expired().await {
+            Ok(purged) => tracing::debug!("purged {} expired login failures", purged),
+            Err(e) => tracing::error!("failed to purge expired login failures: {:?}", e),
+        }
+    }
+}
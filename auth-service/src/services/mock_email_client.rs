@@ -1,5 +1,5 @@
 use secrecy::ExposeSecret;
-use crate::domain::{Email, EmailClient};
+use crate::domain::{Email, EmailClient, EmailMessage};
 
 #[derive(Clone, Debug, Default)]
 pub struct MockEmailClient;
@@ -9,15 +9,14 @@ impl EmailClient for MockEmailClient {
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
+        message: &EmailMessage,
     ) -> Result<(), String> {
-        // Our mock email client will simply log the recipient, subject, and content to standard output
+        // Our mock email client will simply log the recipient, subject, and plain text content to standard output
         println!(
             "Sending email to {} with subject: {} and content: {}",
-            recipient.as_ref().expose_secret().to_string(),
-            subject,
-            content
+            recipient.as_ref().expose_secret(),
+            message.subject,
+            message.text
         );
 
         Ok(())
@@ -1,9 +1,41 @@
 mod mock_email_client;
+mod smtp_email_client;
+mod recording_email_client;
+mod email_outbox_worker;
+mod expired_record_purger;
 mod data_stores;
 
 pub use data_stores::hashmap_user_store::*;
 pub use data_stores::postgres_user_store::*;
 pub use data_stores::banned_token_store::*;
 pub use data_stores::redis_banned_token_store::*;
+pub use data_stores::postgres_banned_token_store::*;
 pub use data_stores::hashmap_two_fa_code_store::*;
-pub use mock_email_client::*;
\ No newline at end of file
+pub use data_stores::redis_two_fa_code_store::*;
+pub use data_stores::postgres_two_fa_code_store::*;
+pub use data_stores::hashmap_password_reset_token_store::*;
+pub use data_stores::postgres_password_reset_token_store::*;
+pub use data_stores::redis_password_reset_token_store::*;
+pub use data_stores::hashmap_email_verification_token_store::*;
+pub use data_stores::postgres_email_verification_token_store::*;
+pub use data_stores::hashmap_recovery_code_store::*;
+pub use data_stores::postgres_recovery_code_store::*;
+pub use data_stores::hashmap_passkey_store::*;
+pub use data_stores::postgres_passkey_store::*;
+pub use data_stores::hashmap_refresh_token_store::*;
+pub use data_stores::postgres_refresh_token_store::*;
+pub use data_stores::hashmap_session_store::*;
+pub use data_stores::postgres_session_store::*;
+pub use data_stores::redis_session_store::*;
+pub use data_stores::hashmap_login_attempt_store::*;
+pub use data_stores::redis_login_attempt_store::*;
+pub use data_stores::postgres_login_attempt_store::*;
+pub use data_stores::hashmap_email_outbox_store::*;
+pub use data_stores::postgres_email_outbox_store::*;
+pub use data_stores::hashmap_known_device_store::*;
+pub use data_stores::postgres_known_device_store::*;
+pub use mock_email_client::*;
+pub use smtp_email_client::*;
+pub use recording_email_client::*;
+pub use email_outbox_worker::*;
+pub use expired_record_purger::*;
\ No newline at end of file
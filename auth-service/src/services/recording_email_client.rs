@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::RwLock;
+use crate::domain::{Email, EmailClient, EmailMessage, SentEmail};
+
+/// Keeps every email in memory instead of sending it, so tests and the dev outbox can read them.
+/// Clones share the same emails.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingEmailClient {
+    sent: Arc<RwLock<Vec<SentEmail>>>,
+}
+
+impl RecordingEmailClient {
+    /// The most recent email sent to `recipient` with `subject`.
+    pub async fn last_email_to(&self, recipient: &Email, subject: &str) -> Option<SentEmail> {
+        self.sent.read().await
+            .iter()
+            .rev()
+            .find(|email| &email.recipient == recipient && email.message.subject == subject)
+            .cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<(), String> {
+        self.sent.write().await.push(SentEmail {
+            recipient: recipient.clone(),
+            message: message.clone(),
+            sent_at: Utc::now(),
+        });
+
+        Ok(())
+    }
+
+    async fn sent_emails(&self, recipient: Option<&Email>) -> Vec<SentEmail> {
+        self.sent.read().await
+            .iter()
+            .filter(|email| recipient.is_none_or(|recipient| &email.recipient == recipient))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use super::*;
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_string())).expect("Failed to create Email")
+    }
+
+    fn message(subject: &str, text: &str) -> EmailMessage {
+        EmailMessage {
+            subject: subject.to_string(),
+            text: text.to_string(),
+            html: format!("<p>{}</p>", text),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emails_are_looked_up_by_recipient() {
+        let client = RecordingEmailClient::default();
+        let first = email("first@example.com");
+        let second = email("second@example.com");
+
+        client.clone().send_email(&first, &message("Subject", "one")).await.unwrap();
+        client.send_email(&second, &message("Subject", "two")).await.unwrap();
+        client.send_email(&first, &message("Subject", "three")).await.unwrap();
+        client.send_email(&first, &message("Other subject", "four")).await.unwrap();
+
+        let sent = client.sent_emails(Some(&first)).await;
+        let texts: Vec<_> = sent.iter().map(|email| email.message.text.as_str()).collect();
+        assert_eq!(texts, vec!["one", "three", "four"]);
+        assert_eq!(client.sent_emails(None).await.len(), 4);
+
+        let last = client.last_email_to(&first, "Subject").await.expect("No email found");
+        assert_eq!(last.message, message("Subject", "three"));
+        assert!(client.last_email_to(&email("third@example.com"), "Subject").await.is_none());
+    }
+}
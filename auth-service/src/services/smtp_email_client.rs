@@ -0,0 +1,114 @@
+use std::str::FromStr;
+use std::time::Duration;
+use color_eyre::eyre::{eyre, Context, Result};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    /// Plain text. Only meant for local test servers.
+    None,
+    /// Upgrades a plain connection with `STARTTLS`, which the server has to support.
+    StartTls,
+    /// TLS from the start of the connection.
+    Tls,
+}
+
+impl FromStr for SmtpTls {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(SmtpTls::None),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            _ => Err(eyre!("SMTP TLS mode must be one of none, starttls or tls")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// Defaults to the usual port for the TLS mode: 25, 587 or 465.
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub credentials: Option<(String, Secret<String>)>,
+    /// `From` of every email, either `address` or `Name <address>`.
+    pub sender: String,
+    /// Limit on sending each email, including connecting to the server.
+    pub timeout: Duration,
+    /// Open connections kept for reuse between emails.
+    pub max_connections: u32,
+}
+
+/// Sends emails through an SMTP server, reusing its connections.
+#[derive(Clone)]
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+    timeout: Duration,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: &SmtpSettings) -> Result<Self> {
+        let sender = settings.sender.parse::<Mailbox>()
+            .wrap_err("invalid SMTP sender")?;
+
+        let mut builder = match settings.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                .wrap_err("failed to set up STARTTLS")?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+                .wrap_err("failed to set up TLS")?,
+        };
+
+        if let Some(port) = settings.port {
+            builder = builder.port(port);
+        }
+        if let Some((username, password)) = &settings.credentials {
+            builder = builder.credentials(Credentials::new(username.clone(), password.expose_secret().clone()));
+        }
+
+        let transport = builder
+            .timeout(Some(settings.timeout))
+            .pool_config(PoolConfig::new().max_size(settings.max_connections))
+            .build();
+
+        Ok(Self { transport, sender, timeout: settings.timeout })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<(), String> {
+        let recipient = recipient.as_ref().expose_secret()
+            .parse::<Mailbox>()
+            .map_err(|e| e.to_string())?;
+
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(&message.subject)
+            .multipart(MultiPart::alternative_plain_html(message.text.clone(), message.html.clone()))
+            .map_err(|e| e.to_string())?;
+
+        // The transport's own timeout only covers connecting, not a server that stops answering.
+        tokio::time::timeout(self.timeout, self.transport.send(email))
+            .await
+            .map_err(|_| "timed out sending email".to_string())?
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
@@ -1,17 +1,33 @@
 use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::Validation;
 use serde::{Deserialize, Serialize};
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use secrecy::ExposeSecret;
-use crate::domain::{BannedTokenStore, Email};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+use crate::domain::{BannedTokenStore, Email, RefreshToken, SessionId};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::constants::{
+    JWT_AUDIENCE,
+    JWT_COOKIE_NAME,
+    JWT_ISSUER,
+    JWT_KEYRING,
+    MAGIC_LINK_AUDIENCE,
+    MAGIC_LINK_TTL_SECONDS,
+    REFRESH_COOKIE_NAME,
+    REPORT_LOGIN_AUDIENCE,
+    REPORT_LOGIN_TTL_SECONDS,
+    TWO_FA_CODE_TTL_SECONDS,
+    TWO_FA_PENDING_AUDIENCE,
+    TWO_FA_PENDING_COOKIE_NAME,
+    UNLOCK_ACCOUNT_AUDIENCE,
+    UNLOCK_ACCOUNT_TTL_SECONDS,
+};
 
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token, tied to the session if there is one
+pub fn generate_auth_cookie(email: &Email, session_id: Option<&SessionId>) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(email, session_id)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -26,17 +42,76 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Create cookie holding an opaque refresh token
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    Cookie::build((REFRESH_COOKIE_NAME, token.as_ref().to_string()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+        .build()
+}
+
+// Create cookie with a token that only lets the user finish logging in with 2FA
+pub fn generate_two_fa_pending_cookie(email: &Email) -> Result<Cookie<'static>> {
+    let claims = Claims::new(email, TWO_FA_PENDING_AUDIENCE, TWO_FA_CODE_TTL_SECONDS)?;
+    let token = create_token(&claims)?;
+
+    Ok(Cookie::build((TWO_FA_PENDING_COOKIE_NAME, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(TWO_FA_CODE_TTL_SECONDS))
+        .build())
+}
+
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+/// Leeway `Validation` allows on `exp` by default, so tokens keep validating this long after they expire.
+pub const EXP_LEEWAY_SECONDS: i64 = 60;
+/// How long a banned or claimed token stays banned. Tokens that can be banned live at most TOKEN_TTL_SECONDS,
+/// so the ban outlasts them, leeway included, even when they're banned right after being issued.
+pub const BANNED_TOKEN_TTL_SECONDS: i64 = TOKEN_TTL_SECONDS + EXP_LEEWAY_SECONDS;
+// How long a refresh token can go unused before the session ends
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 1_209_600; // 14 days
 
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .wrap_err("failed to create 10 minute time delta")?;
+fn generate_auth_token(email: &Email, session_id: Option<&SessionId>) -> Result<String> {
+    let mut claims = Claims::new(email, &JWT_AUDIENCE, TOKEN_TTL_SECONDS)?;
+    claims.sid = session_id.map(|id| id.as_ref().to_string());
+
+    create_token(&claims)
+}
+
+// Create the signed token sent in a magic login link
+pub fn generate_magic_link_token(email: &Email) -> Result<String> {
+    let claims = Claims::new(email, MAGIC_LINK_AUDIENCE, MAGIC_LINK_TTL_SECONDS)?;
+
+    create_token(&claims)
+}
+
+// Create the signed token sent in an account unlock link
+pub fn generate_unlock_token(email: &Email) -> Result<String> {
+    let claims = Claims::new(email, UNLOCK_ACCOUNT_AUDIENCE, UNLOCK_ACCOUNT_TTL_SECONDS)?;
+
+    create_token(&claims)
+}
+
+// Create the signed token sent in the "this wasn't me" link of a new login alert
+pub fn generate_report_login_token(email: &Email) -> Result<String> {
+    let claims = Claims::new(email, REPORT_LOGIN_AUDIENCE, REPORT_LOGIN_TTL_SECONDS)?;
 
-    let exp = Utc::now()
+    create_token(&claims)
+}
+
+// Unix timestamps for `now` and `ttl_seconds` from then
+fn issued_and_expires_at(now: DateTime<Utc>, ttl_seconds: i64) -> Result<(usize, usize)> {
+    let delta = chrono::Duration::try_seconds(ttl_seconds)
+        .wrap_err(format!("failed to create {} second time delta", ttl_seconds))?;
+
+    let exp = now
         .checked_add_signed(delta)
-        .ok_or(eyre!("failed to add 10 minutes to current time"))?
+        .ok_or(eyre!("failed to add {} seconds to current time", ttl_seconds))?
         .timestamp();
 
     let exp: usize = exp.try_into().wrap_err(format!(
@@ -44,62 +119,157 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         exp
     ))?;
 
-    let sub = email.as_ref().expose_secret().to_string();
-
-    let claims = Claims { sub, exp };
+    let iat: usize = now.timestamp().try_into().wrap_err(format!(
+        "failed to cast iat time to usize. iat time: {}",
+        now.timestamp()
+    ))?;
 
-    create_token(&claims)
+    Ok((iat, exp))
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Check if JWT auth token is valid by decoding it with the key named in its header
 pub async fn validate_token<T: BannedTokenStore>(token: &str, banned_token_store: tokio::sync::RwLockReadGuard<'_, T>) -> Result<Claims>{
-    match banned_token_store.is_banned(token).await {
-        Ok(value) => {
-            if value {
-                return Err(eyre!("token is banned"));
-            }
+    let claims = JWT_KEYRING.decode::<Claims>(token, validation(&JWT_AUDIENCE))?;
+
+    check_not_banned(&claims, &*banned_token_store).await?;
+
+    Ok(claims)
+}
+
+// Check if a magic link token is valid. Callers must ban its `jti` once it's used.
+pub async fn validate_magic_link_token<T: BannedTokenStore>(token: &str, banned_token_store: &T) -> Result<Claims> {
+    validate_single_use_token(token, MAGIC_LINK_AUDIENCE, banned_token_store).await
+        .wrap_err("magic link can't be used")
+}
+
+// Check if an account unlock token is valid. Callers must ban its `jti` once it's used.
+pub async fn validate_unlock_token<T: BannedTokenStore>(token: &str, banned_token_store: &T) -> Result<Claims> {
+    validate_single_use_token(token, UNLOCK_ACCOUNT_AUDIENCE, banned_token_store).await
+        .wrap_err("unlock link can't be used")
+}
+
+// Check if a "this wasn't me" token is valid. Reporting the login revokes all tokens of the user, so it works once.
+pub async fn validate_report_login_token<T: BannedTokenStore>(token: &str, banned_token_store: &T) -> Result<Claims> {
+    validate_single_use_token(token, REPORT_LOGIN_AUDIENCE, banned_token_store).await
+        .wrap_err("report login link can't be used")
+}
+
+// Check if a 2FA pending token is valid. Callers must ban its `jti` once the login is complete.
+pub async fn validate_two_fa_pending_token<T: BannedTokenStore>(token: &str, banned_token_store: &T) -> Result<Claims> {
+    validate_single_use_token(token, TWO_FA_PENDING_AUDIENCE, banned_token_store).await
+        .wrap_err("2FA pending token can't be used")
+}
+
+async fn validate_single_use_token<T: BannedTokenStore>(token: &str, audience: &str, banned_token_store: &T) -> Result<Claims> {
+    let claims = JWT_KEYRING.decode::<Claims>(token, validation(audience))
+        .wrap_err(format!("failed to decode {} token", audience))?;
+
+    check_not_banned(&claims, banned_token_store).await?;
+
+    Ok(claims)
+}
+
+// Only tokens this service issued for `audience` pass, and every registered claim has to be present.
+fn validation(audience: &str) -> Validation {
+    let mut validation = Validation::default();
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+
+    validation
+}
+
+async fn check_not_banned<T: BannedTokenStore>(claims: &Claims, banned_token_store: &T) -> Result<()> {
+    if banned_token_store.is_banned(&claims.jti).await? {
+        return Err(eyre!("token is banned"));
+    }
+
+    if let Some(sid) = &claims.sid {
+        if banned_token_store.is_banned(sid).await? {
+            return Err(eyre!("session was revoked"));
         }
-        Err(e) => {
-            return Err(e.into());
+    }
+
+    check_not_revoked(claims, banned_token_store).await
+}
+
+async fn check_not_revoked<T: BannedTokenStore>(claims: &Claims, banned_token_store: &T) -> Result<()> {
+    // Tokens from before `iat_ms` existed are taken to be from the start of their second, so the whole second is revoked for them.
+    let issued_at_ms = claims.iat_ms.unwrap_or(claims.iat * 1000);
+    let email = Email::parse(Secret::new(claims.sub.clone()))
+        .wrap_err("token subject is not a valid email")?;
+    if let Some(revoked_at_ms) = banned_token_store.get_tokens_revoked_at(&email).await? {
+        if issued_at_ms <= revoked_at_ms {
+            return Err(eyre!("token was revoked"));
         }
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-        .map(|data| data.claims)
-        .wrap_err("failed to decode token")
+    Ok(())
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
-        .wrap_err("failed to create token")
+// Create JWT by signing the claims with the configured key
+fn create_token<C: Serialize>(claims: &C) -> Result<String> {
+    JWT_KEYRING.encode(claims)
 }
 
+/// Registered JWT claims (RFC 7519). `aud` tells auth tokens and magic link tokens apart.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    pub aud: String,
+    /// Unique per token. Logging out or using a magic link bans the token by this ID.
+    pub jti: String,
+    /// Session the token belongs to. Revoking the session bans every token carrying its ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// `iat` in milliseconds, so revoking the user's tokens spares those issued later in the same second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<usize>,
+}
+
+impl Claims {
+    fn new(email: &Email, audience: &str, ttl_seconds: i64) -> Result<Self> {
+        let now = Utc::now();
+        let (iat, exp) = issued_and_expires_at(now, ttl_seconds)?;
+        let iat_ms: usize = now.timestamp_millis().try_into().wrap_err(format!(
+            "failed to cast iat_ms time to usize. iat_ms time: {}",
+            now.timestamp_millis()
+        ))?;
+
+        Ok(Self {
+            sub: email.as_ref().expose_secret().to_string(),
+            exp,
+            iat,
+            nbf: iat,
+            iss: JWT_ISSUER.to_string(),
+            aud: audience.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            sid: None,
+            iat_ms: Some(iat_ms),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use secrecy::Secret;
     use tokio::sync::RwLock;
     use crate::domain::Email;
+    use sqlx::PgPool;
+    use crate::get_postgres_pool;
+    use crate::services::{HashSetBannedTokenStore, PostgresBannedTokenStore};
+    use crate::utils::constants::DATABASE_URL;
+    use crate::utils::jwt_keys::RETIRED_KEY_TTL_SECONDS;
     use super::*;
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, None).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -121,14 +291,14 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, None).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = generate_auth_token(&email, None).unwrap();
         let banned_token_store = crate::services::HashSetBannedTokenStore::default();
         let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await.unwrap();
         assert_eq!(result.sub, "test@example.com");
@@ -148,4 +318,264 @@ mod tests {
         let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_validate_token_revoked_for_user() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = generate_auth_token(&email, None).unwrap();
+        let mut banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        banned_token_store.revoke_all_tokens(&email).await.unwrap();
+        let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_right_after_revocation() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let mut banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        banned_token_store.revoke_all_tokens(&email).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+
+        // Most likely within the same second as the revocation, which used to be revoked along with it.
+        let token = generate_auth_token(&email, None).unwrap();
+        let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_magic_link_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = generate_magic_link_token(&email).unwrap();
+        let banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        let result = validate_magic_link_token(&token, &banned_token_store).await.unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_magic_link_token_once_banned() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = generate_magic_link_token(&email).unwrap();
+        let mut banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        let claims = validate_magic_link_token(&token, &banned_token_store).await.unwrap();
+        banned_token_store.add_banned_token(claims.jti).await.unwrap();
+        let result = validate_magic_link_token(&token, &banned_token_store).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_magic_link_and_auth_tokens_are_not_interchangeable() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let banned_token_store = RwLock::new(crate::services::HashSetBannedTokenStore::default());
+
+        let magic_link_token = generate_magic_link_token(&email).unwrap();
+        let result = validate_token(&magic_link_token, banned_token_store.read().await).await;
+        assert!(result.is_err());
+
+        let auth_token = generate_auth_token(&email, None).unwrap();
+        let result = validate_magic_link_token(&auth_token, &*banned_token_store.read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_two_fa_pending_token_is_not_an_auth_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let banned_token_store = RwLock::new(crate::services::HashSetBannedTokenStore::default());
+
+        let cookie = generate_two_fa_pending_cookie(&email).unwrap();
+        assert_eq!(cookie.name(), TWO_FA_PENDING_COOKIE_NAME);
+        assert!(validate_token(cookie.value(), banned_token_store.read().await).await.is_err());
+
+        let claims = validate_two_fa_pending_token(cookie.value(), &*banned_token_store.read().await).await.unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_unlock_and_magic_link_tokens_are_not_interchangeable() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let banned_token_store = crate::services::HashSetBannedTokenStore::default();
+
+        let unlock_token = generate_unlock_token(&email).unwrap();
+        assert_eq!(validate_unlock_token(&unlock_token, &banned_token_store).await.unwrap().sub, "test@example.com");
+        assert!(validate_magic_link_token(&unlock_token, &banned_token_store).await.is_err());
+
+        let magic_link_token = generate_magic_link_token(&email).unwrap();
+        assert!(validate_unlock_token(&magic_link_token, &banned_token_store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_report_login_token_stops_working_once_tokens_are_revoked() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let mut banned_token_store = crate::services::HashSetBannedTokenStore::default();
+
+        let token = generate_report_login_token(&email).unwrap();
+        assert_eq!(validate_report_login_token(&token, &banned_token_store).await.unwrap().sub, "test@example.com");
+        assert!(validate_unlock_token(&token, &banned_token_store).await.is_err());
+
+        banned_token_store.revoke_all_tokens(&email).await.unwrap();
+        assert!(validate_report_login_token(&token, &banned_token_store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_every_token_expires_before_a_retired_key_is_dropped() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let tokens = [
+            generate_auth_token(&email, None).unwrap(),
+            generate_two_fa_pending_cookie(&email).unwrap().value().to_string(),
+            generate_magic_link_token(&email).unwrap(),
+            generate_unlock_token(&email).unwrap(),
+            generate_report_login_token(&email).unwrap(),
+        ];
+
+        let mut validation = Validation::default();
+        validation.validate_aud = false;
+        for token in tokens {
+            let claims = JWT_KEYRING.decode::<Claims>(&token, validation.clone()).unwrap();
+            let lifetime = (claims.exp - claims.iat) as i64 + validation.leeway as i64;
+            assert!(lifetime <= RETIRED_KEY_TTL_SECONDS, "{} tokens outlive retired keys", claims.aud);
+        }
+    }
+
+    #[test]
+    fn test_bans_outlast_every_token_that_can_be_banned() {
+        for ttl in [TOKEN_TTL_SECONDS, TWO_FA_CODE_TTL_SECONDS, MAGIC_LINK_TTL_SECONDS, UNLOCK_ACCOUNT_TTL_SECONDS] {
+            assert!(ttl + EXP_LEEWAY_SECONDS <= BANNED_TOKEN_TTL_SECONDS);
+        }
+    }
+
+    async fn postgres_banned_token_store() -> (PgPool, PostgresBannedTokenStore) {
+        let pool = get_postgres_pool(&DATABASE_URL).await.unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        (pool.clone(), PostgresBannedTokenStore::new(pool))
+    }
+
+    // Claims issued this long ago, so tokens outliving their TTL by less than the leeway still validate.
+    fn issued_ago(email: &Email, audience: &str, ttl_seconds: i64, elapsed: i64) -> Claims {
+        let mut claims = Claims::new(email, audience, ttl_seconds).unwrap();
+        claims.iat -= elapsed as usize;
+        claims.nbf -= elapsed as usize;
+        claims.exp -= elapsed as usize;
+        claims.iat_ms = claims.iat_ms.map(|iat_ms| iat_ms - elapsed as usize * 1000);
+        claims
+    }
+
+    // As if the ban was added this long ago.
+    async fn age_ban(pool: &PgPool, jti: &str, elapsed: i64) {
+        sqlx::query("UPDATE banned_tokens SET expires_at = expires_at - make_interval(secs => $2) WHERE jti = $1")
+            .bind(jti)
+            .bind(elapsed as f64)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_claimed_magic_link_cannot_be_replayed_within_exp_leeway() {
+        let (pool, mut banned_token_store) = postgres_banned_token_store().await;
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+
+        // The link was claimed right after it was issued, and has expired since.
+        let elapsed = MAGIC_LINK_TTL_SECONDS + EXP_LEEWAY_SECONDS / 2;
+        let claims = issued_ago(&email, MAGIC_LINK_AUDIENCE, MAGIC_LINK_TTL_SECONDS, elapsed);
+        let token = create_token(&claims).unwrap();
+        assert!(banned_token_store.claim_token(claims.jti.clone()).await.unwrap());
+        age_ban(&pool, &claims.jti, elapsed).await;
+
+        assert!(validate_magic_link_token(&token, &HashSetBannedTokenStore::default()).await.is_ok());
+        assert!(validate_magic_link_token(&token, &banned_token_store).await.is_err());
+        assert!(!banned_token_store.claim_token(claims.jti).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_logged_out_token_cannot_be_replayed_within_exp_leeway() {
+        let (pool, mut banned_token_store) = postgres_banned_token_store().await;
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+
+        // The token was banned right after it was issued, and has expired since.
+        let elapsed = TOKEN_TTL_SECONDS + EXP_LEEWAY_SECONDS / 2;
+        let claims = issued_ago(&email, &JWT_AUDIENCE, TOKEN_TTL_SECONDS, elapsed);
+        let token = create_token(&claims).unwrap();
+        banned_token_store.add_banned_token(claims.jti.clone()).await.unwrap();
+        age_ban(&pool, &claims.jti, elapsed).await;
+
+        let unbanned = RwLock::new(HashSetBannedTokenStore::default());
+        assert!(validate_token(&token, unbanned.read().await).await.is_ok());
+        let banned_token_store = RwLock::new(banned_token_store);
+        assert!(validate_token(&token, banned_token_store.read().await).await.is_err());
+    }
+
+    fn create_test_token(email: &Email, update: impl FnOnce(&mut Claims)) -> String {
+        let mut claims = Claims::new(email, &JWT_AUDIENCE, TOKEN_TTL_SECONDS).unwrap();
+        update(&mut claims);
+        create_token(&claims).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_claims() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let banned_token_store = RwLock::new(crate::services::HashSetBannedTokenStore::default());
+
+        let first = validate_token(&generate_auth_token(&email, None).unwrap(), banned_token_store.read().await).await.unwrap();
+        let second = validate_token(&generate_auth_token(&email, None).unwrap(), banned_token_store.read().await).await.unwrap();
+
+        assert_eq!(first.iss, *JWT_ISSUER);
+        assert_eq!(first.aud, *JWT_AUDIENCE);
+        assert_eq!(first.nbf, first.iat);
+        assert_eq!(first.exp, first.iat + TOKEN_TTL_SECONDS as usize);
+        assert_ne!(first.jti, second.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_other_audience() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = create_test_token(&email, |claims| claims.aud = "other-service".to_string());
+        let banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_other_issuer() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = create_test_token(&email, |claims| claims.iss = "other-issuer".to_string());
+        let banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_token_not_yet_valid() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = create_test_token(&email, |claims| claims.nbf += 300);
+        let banned_token_store = crate::services::HashSetBannedTokenStore::default();
+        let result = validate_token(&token, RwLock::new(banned_token_store).read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_banned_by_jti() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let token = generate_auth_token(&email, None).unwrap();
+        let banned_token_store = RwLock::new(crate::services::HashSetBannedTokenStore::default());
+        let claims = validate_token(&token, banned_token_store.read().await).await.unwrap();
+
+        banned_token_store.write().await.add_banned_token(claims.jti).await.unwrap();
+
+        let result = validate_token(&token, banned_token_store.read().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_banned_by_session() {
+        let email = Email::parse(Secret::new("test@example.com".to_string())).unwrap();
+        let session_id = SessionId::default();
+        let token = generate_auth_token(&email, Some(&session_id)).unwrap();
+        let banned_token_store = RwLock::new(crate::services::HashSetBannedTokenStore::default());
+        let claims = validate_token(&token, banned_token_store.read().await).await.unwrap();
+        assert_eq!(claims.sid.as_deref(), Some(session_id.as_ref()));
+
+        banned_token_store.write().await.add_banned_token(session_id.as_ref().to_string()).await.unwrap();
+
+        let result = validate_token(&token, banned_token_store.read().await).await;
+        assert!(result.is_err());
+    }
+}
@@ -1,11 +1,47 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use std::env as std_env;
+use std::time::Duration;
+use jsonwebtoken::Algorithm;
+use secrecy::Secret;
+use crate::domain::{BannedTokenStoreBackend, LoginAttemptStoreBackend, LoginThrottle, TwoFACodeStoreBackend};
+use crate::services::{SmtpSettings, SmtpTls};
+use super::email_templates::EmailBranding;
+use super::jwt_keys::{JwtKey, JwtKeyring, JwtSigningKey};
 
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
+    pub static ref JWT_KEYRING: JwtKeyring = set_jwt_keyring();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
     pub static ref DATABASE_URL: String = set_db_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host(); // New!
+    pub static ref REDIS_RESPONSE_TIMEOUT: Duration = set_redis_timeout(env::REDIS_RESPONSE_TIMEOUT_MILLIS_ENV_VAR, DEFAULT_REDIS_RESPONSE_TIMEOUT_MILLIS);
+    pub static ref REDIS_CONNECTION_TIMEOUT: Duration = set_redis_timeout(env::REDIS_CONNECTION_TIMEOUT_MILLIS_ENV_VAR, DEFAULT_REDIS_CONNECTION_TIMEOUT_MILLIS);
+    pub static ref BANNED_TOKEN_STORE_BACKEND: BannedTokenStoreBackend = set_banned_token_store_backend();
+    pub static ref TWO_FA_CODE_STORE_BACKEND: TwoFACodeStoreBackend = set_two_fa_code_store_backend();
+    pub static ref LOGIN_ATTEMPT_STORE_BACKEND: LoginAttemptStoreBackend = set_login_attempt_store_backend();
+    pub static ref TOTP_ENCRYPTION_KEY: String = set_totp_encryption_key();
+    pub static ref TOTP_SKEW: u8 = set_totp_skew();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+    pub static ref MAGIC_LINK_URL: String = set_magic_link_url();
+    pub static ref ADMIN_API_KEY: Option<Secret<String>> = set_admin_api_key();
+    pub static ref LOGIN_LOCKOUT_SECONDS: i64 = set_login_lockout_seconds();
+    pub static ref LOGIN_THROTTLE_BY_EMAIL: LoginThrottle = LoginThrottle {
+        free_failures: LOGIN_FREE_FAILURES_BY_EMAIL,
+        lockout_failures: set_login_lockout_threshold(env::LOGIN_LOCKOUT_THRESHOLD_ENV_VAR, DEFAULT_LOGIN_LOCKOUT_THRESHOLD),
+        lockout_seconds: *LOGIN_LOCKOUT_SECONDS,
+    };
+    pub static ref LOGIN_THROTTLE_BY_IP_ADDRESS: LoginThrottle = LoginThrottle {
+        free_failures: LOGIN_FREE_FAILURES_BY_IP_ADDRESS,
+        lockout_failures: set_login_lockout_threshold(env::LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR, DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD),
+        lockout_seconds: *LOGIN_LOCKOUT_SECONDS,
+    };
+    pub static ref UNLOCK_ACCOUNT_URL: String = set_unlock_account_url();
+    pub static ref REPORT_LOGIN_URL: String = set_report_login_url();
+    pub static ref SMTP_SETTINGS: Option<SmtpSettings> = set_smtp_settings();
+    pub static ref EMAIL_BRANDING: EmailBranding = set_email_branding();
 }
 
 fn set_token() -> String {
@@ -17,6 +53,98 @@ fn set_token() -> String {
     secret
 }
 
+// HS256 with JWT_SECRET unless JWT_ALGORITHM asks for a key pair.
+// The next key and verify-only keys are for the same algorithm.
+fn set_jwt_keyring() -> JwtKeyring {
+    dotenv().ok();
+    let key_id = std_env::var(env::JWT_KEY_ID_ENV_VAR).ok().filter(|id| !id.is_empty());
+    let algorithm = std_env::var(env::JWT_ALGORITHM_ENV_VAR).unwrap_or(DEFAULT_JWT_ALGORITHM.to_owned());
+
+    let jwt_algorithm = match algorithm.as_str() {
+        "HS256" => Algorithm::HS256,
+        "RS256" => Algorithm::RS256,
+        "EdDSA" => Algorithm::EdDSA,
+        _ => panic!("JWT_ALGORITHM must be one of HS256, RS256 or EdDSA."),
+    };
+
+    let keyring = if jwt_algorithm == Algorithm::HS256 {
+        JwtKeyring::from_secret(key_id, JWT_SECRET.as_bytes())
+    } else {
+        let private_key = read_key(&required_key_path(env::JWT_PRIVATE_KEY_PATH_ENV_VAR, &algorithm));
+        let public_key = read_key(&required_key_path(env::JWT_PUBLIC_KEY_PATH_ENV_VAR, &algorithm));
+
+        JwtKeyring::from_pem(jwt_algorithm, key_id, &private_key, &public_key)
+            .expect("Failed to load JWT signing keys")
+    };
+
+    let mut keyring = match set_next_jwt_key(jwt_algorithm) {
+        Some(next_key) => keyring.with_next_key(next_key).expect("Failed to add the next JWT signing key"),
+        None => keyring,
+    };
+    for key in set_verify_only_jwt_keys(jwt_algorithm) {
+        keyring = keyring.with_verify_only_key(key).expect("Failed to add verify-only JWT key");
+    }
+
+    keyring
+}
+
+// The key the admin rotation switches to. JWT_NEXT_SECRET for HS256, which also needs JWT_NEXT_KEY_ID,
+// or JWT_NEXT_PRIVATE_KEY_PATH and JWT_NEXT_PUBLIC_KEY_PATH for key pairs.
+fn set_next_jwt_key(algorithm: Algorithm) -> Option<JwtSigningKey> {
+    let key_id = std_env::var(env::JWT_NEXT_KEY_ID_ENV_VAR).ok().filter(|id| !id.is_empty());
+
+    if algorithm == Algorithm::HS256 {
+        let secret = std_env::var(env::JWT_NEXT_SECRET_ENV_VAR).ok().filter(|secret| !secret.is_empty())?;
+        let key_id = key_id.expect("JWT_NEXT_KEY_ID must be set along with JWT_NEXT_SECRET.");
+        return Some(JwtSigningKey::from_secret(key_id, secret.as_bytes()));
+    }
+
+    let private_key_path = std_env::var(env::JWT_NEXT_PRIVATE_KEY_PATH_ENV_VAR).ok().filter(|path| !path.is_empty())?;
+    let public_key_path = std_env::var(env::JWT_NEXT_PUBLIC_KEY_PATH_ENV_VAR).ok().filter(|path| !path.is_empty())
+        .expect("JWT_NEXT_PUBLIC_KEY_PATH must be set along with JWT_NEXT_PRIVATE_KEY_PATH.");
+
+    let next_key = JwtSigningKey::from_pem(algorithm, key_id, &read_key(&private_key_path), &read_key(&public_key_path))
+        .expect("Failed to load the next JWT signing key");
+    Some(next_key)
+}
+
+// Comma separated keys from JWT_VERIFY_ONLY_KEYS, e.g. the ones tokens were signed with before the current key.
+// `kid=secret` for HS256, and public key paths for key pairs, as `kid=path` if the kid isn't the thumbprint.
+fn set_verify_only_jwt_keys(algorithm: Algorithm) -> Vec<JwtKey> {
+    let keys = std_env::var(env::JWT_VERIFY_ONLY_KEYS_ENV_VAR).unwrap_or_default();
+
+    keys.split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| match (algorithm, key.split_once('=')) {
+            (Algorithm::HS256, Some((key_id, secret))) => JwtKey::from_secret(key_id.to_string(), secret.as_bytes()),
+            (Algorithm::HS256, None) => panic!("JWT_VERIFY_ONLY_KEYS must be kid=secret pairs for HS256."),
+            (_, Some((key_id, path))) => JwtKey::from_public_pem(algorithm, Some(key_id.to_string()), &read_key(path))
+                .expect("Failed to load verify-only JWT key"),
+            (_, None) => JwtKey::from_public_pem(algorithm, None, &read_key(key))
+                .expect("Failed to load verify-only JWT key"),
+        })
+        .collect()
+}
+
+fn required_key_path(env_var: &str, algorithm: &str) -> String {
+    std_env::var(env_var).unwrap_or_else(|_| panic!("{} must be set for {}.", env_var, algorithm))
+}
+
+fn read_key(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR).unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+fn set_jwt_audience() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR).unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
 fn set_db_url() -> String {
     dotenv().ok();
     std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set.")
@@ -28,14 +156,272 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_redis_timeout(env_var: &str, default_millis: u64) -> Duration {
+    dotenv().ok();
+    let millis = std_env::var(env_var)
+        .map(|millis| millis.parse().unwrap_or_else(|_| panic!("{} must be a number of milliseconds.", env_var)))
+        .unwrap_or(default_millis);
+    Duration::from_millis(millis)
+}
+
+fn set_banned_token_store_backend() -> BannedTokenStoreBackend {
+    dotenv().ok();
+    std_env::var(env::BANNED_TOKEN_STORE_ENV_VAR)
+        .map(|backend| backend.parse().expect("BANNED_TOKEN_STORE must be one of redis or postgres."))
+        .unwrap_or(BannedTokenStoreBackend::Redis)
+}
+
+fn set_two_fa_code_store_backend() -> TwoFACodeStoreBackend {
+    dotenv().ok();
+    std_env::var(env::TWO_FA_CODE_STORE_ENV_VAR)
+        .map(|backend| backend.parse().expect("TWO_FA_CODE_STORE must be one of memory, redis or postgres."))
+        .unwrap_or(TwoFACodeStoreBackend::Redis)
+}
+
+fn set_login_attempt_store_backend() -> LoginAttemptStoreBackend {
+    dotenv().ok();
+    std_env::var(env::LOGIN_ATTEMPT_STORE_ENV_VAR)
+        .map(|backend| backend.parse().expect("LOGIN_ATTEMPT_STORE must be one of redis or postgres."))
+        .unwrap_or(LoginAttemptStoreBackend::Redis)
+}
+
+fn set_totp_encryption_key() -> String {
+    dotenv().ok();
+    let key = std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    if key.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty.");
+    }
+    key
+}
+
+fn set_totp_skew() -> u8 {
+    dotenv().ok();
+    std_env::var(env::TOTP_SKEW_ENV_VAR)
+        .map(|skew| skew.parse().expect("TOTP_SKEW must be a number of 30 second steps."))
+        .unwrap_or(DEFAULT_TOTP_SKEW)
+}
+
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+fn set_webauthn_origin() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_ORIGIN.to_owned())
+}
+
+fn set_magic_link_url() -> String {
+    dotenv().ok();
+    std_env::var(env::MAGIC_LINK_URL_ENV_VAR).unwrap_or(DEFAULT_MAGIC_LINK_URL.to_owned())
+}
+
+// Admin routes are disabled unless ADMIN_API_KEY is set.
+fn set_admin_api_key() -> Option<Secret<String>> {
+    dotenv().ok();
+    std_env::var(env::ADMIN_API_KEY_ENV_VAR)
+        .ok()
+        .filter(|key| !key.is_empty())
+        .map(Secret::new)
+}
+
+fn set_login_lockout_seconds() -> i64 {
+    dotenv().ok();
+    std_env::var(env::LOGIN_LOCKOUT_SECONDS_ENV_VAR)
+        .map(|seconds| seconds.parse().expect("LOGIN_LOCKOUT_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_LOGIN_LOCKOUT_SECONDS)
+}
+
+fn set_login_lockout_threshold(env_var: &str, default: u32) -> u32 {
+    dotenv().ok();
+    std_env::var(env_var)
+        .map(|threshold| threshold.parse().unwrap_or_else(|_| panic!("{} must be a number of failed logins.", env_var)))
+        .unwrap_or(default)
+}
+
+fn set_unlock_account_url() -> String {
+    dotenv().ok();
+    std_env::var(env::UNLOCK_ACCOUNT_URL_ENV_VAR).unwrap_or(DEFAULT_UNLOCK_ACCOUNT_URL.to_owned())
+}
+
+fn set_report_login_url() -> String {
+    dotenv().ok();
+    std_env::var(env::REPORT_LOGIN_URL_ENV_VAR).unwrap_or(DEFAULT_REPORT_LOGIN_URL.to_owned())
+}
+
+// Emails are only sent over SMTP when SMTP_HOST is set.
+fn set_smtp_settings() -> Option<SmtpSettings> {
+    dotenv().ok();
+    let host = std_env::var(env::SMTP_HOST_ENV_VAR).ok().filter(|host| !host.is_empty())?;
+
+    let port = std_env::var(env::SMTP_PORT_ENV_VAR)
+        .ok()
+        .filter(|port| !port.is_empty())
+        .map(|port| port.parse().expect("SMTP_PORT must be a port number."));
+    let tls = std_env::var(env::SMTP_TLS_ENV_VAR)
+        .map(|tls| tls.parse().expect("SMTP_TLS must be one of none, starttls or tls."))
+        .unwrap_or(SmtpTls::StartTls);
+    let credentials = std_env::var(env::SMTP_USERNAME_ENV_VAR)
+        .ok()
+        .filter(|username| !username.is_empty())
+        .map(|username| {
+            let password = std_env::var(env::SMTP_PASSWORD_ENV_VAR).expect("SMTP_PASSWORD must be set with SMTP_USERNAME.");
+            (username, Secret::new(password))
+        });
+    let sender = std_env::var(env::SMTP_SENDER_ENV_VAR).expect("SMTP_SENDER must be set with SMTP_HOST.");
+    let timeout_seconds = std_env::var(env::SMTP_TIMEOUT_SECONDS_ENV_VAR)
+        .map(|seconds| seconds.parse().expect("SMTP_TIMEOUT_SECONDS must be a number of seconds."))
+        .unwrap_or(DEFAULT_SMTP_TIMEOUT_SECONDS);
+    let max_connections = std_env::var(env::SMTP_MAX_CONNECTIONS_ENV_VAR)
+        .map(|max| max.parse().expect("SMTP_MAX_CONNECTIONS must be a number."))
+        .unwrap_or(DEFAULT_SMTP_MAX_CONNECTIONS);
+
+    Some(SmtpSettings {
+        host,
+        port,
+        tls,
+        credentials,
+        sender,
+        timeout: std::time::Duration::from_secs(timeout_seconds),
+        max_connections,
+    })
+}
+
+fn set_email_branding() -> EmailBranding {
+    dotenv().ok();
+    EmailBranding {
+        name: std_env::var(env::EMAIL_BRAND_NAME_ENV_VAR).unwrap_or(DEFAULT_EMAIL_BRAND_NAME.to_owned()),
+        url: std_env::var(env::EMAIL_BRAND_URL_ENV_VAR).unwrap_or(DEFAULT_EMAIL_BRAND_URL.to_owned()),
+        support_email: std_env::var(env::EMAIL_SUPPORT_ADDRESS_ENV_VAR).ok().filter(|address| !address.is_empty()),
+    }
+}
+
 pub mod env {
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_ALGORITHM_ENV_VAR: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_PUBLIC_KEY_PATH_ENV_VAR: &str = "JWT_PUBLIC_KEY_PATH";
+    pub const JWT_KEY_ID_ENV_VAR: &str = "JWT_KEY_ID";
+    pub const JWT_NEXT_KEY_ID_ENV_VAR: &str = "JWT_NEXT_KEY_ID";
+    pub const JWT_NEXT_SECRET_ENV_VAR: &str = "JWT_NEXT_SECRET";
+    pub const JWT_NEXT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_NEXT_PRIVATE_KEY_PATH";
+    pub const JWT_NEXT_PUBLIC_KEY_PATH_ENV_VAR: &str = "JWT_NEXT_PUBLIC_KEY_PATH";
+    pub const JWT_VERIFY_ONLY_KEYS_ENV_VAR: &str = "JWT_VERIFY_ONLY_KEYS";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME"; // New!
+    pub const REDIS_RESPONSE_TIMEOUT_MILLIS_ENV_VAR: &str = "REDIS_RESPONSE_TIMEOUT_MILLIS";
+    pub const REDIS_CONNECTION_TIMEOUT_MILLIS_ENV_VAR: &str = "REDIS_CONNECTION_TIMEOUT_MILLIS";
+    pub const BANNED_TOKEN_STORE_ENV_VAR: &str = "BANNED_TOKEN_STORE";
+    pub const TWO_FA_CODE_STORE_ENV_VAR: &str = "TWO_FA_CODE_STORE";
+    pub const LOGIN_ATTEMPT_STORE_ENV_VAR: &str = "LOGIN_ATTEMPT_STORE";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const TOTP_SKEW_ENV_VAR: &str = "TOTP_SKEW";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const MAGIC_LINK_URL_ENV_VAR: &str = "MAGIC_LINK_URL";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const LOGIN_LOCKOUT_SECONDS_ENV_VAR: &str = "LOGIN_LOCKOUT_SECONDS";
+    pub const LOGIN_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_LOCKOUT_THRESHOLD";
+    pub const LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_IP_LOCKOUT_THRESHOLD";
+    pub const UNLOCK_ACCOUNT_URL_ENV_VAR: &str = "UNLOCK_ACCOUNT_URL";
+    pub const REPORT_LOGIN_URL_ENV_VAR: &str = "REPORT_LOGIN_URL";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_SENDER_ENV_VAR: &str = "SMTP_SENDER";
+    pub const SMTP_TIMEOUT_SECONDS_ENV_VAR: &str = "SMTP_TIMEOUT_SECONDS";
+    pub const SMTP_MAX_CONNECTIONS_ENV_VAR: &str = "SMTP_MAX_CONNECTIONS";
+    pub const EMAIL_BRAND_NAME_ENV_VAR: &str = "EMAIL_BRAND_NAME";
+    pub const EMAIL_BRAND_URL_ENV_VAR: &str = "EMAIL_BRAND_URL";
+    pub const EMAIL_SUPPORT_ADDRESS_ENV_VAR: &str = "EMAIL_SUPPORT_ADDRESS";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const DEFAULT_JWT_ALGORITHM: &str = "HS256";
+/// `iss` of every token this service issues.
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+/// `aud` of auth tokens. Tokens minted for any other audience are rejected.
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+/// Holds the restricted token of a login that still has to pass 2FA, in place of the auth cookie.
+pub const TWO_FA_PENDING_COOKIE_NAME: &str = "two_fa_pending";
+pub const TWO_FA_PENDING_AUDIENCE: &str = "2fa-pending";
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 900; // 15 minutes
+pub const PASSWORD_RESET_COOLDOWN_SECONDS: i64 = 60;
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 86400; // 24 hours
+pub const EMAIL_VERIFICATION_RESEND_COOLDOWN_SECONDS: i64 = 60;
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1"; // New!
+/// A command that gets no reply in this long fails, instead of holding up the request.
+pub const DEFAULT_REDIS_RESPONSE_TIMEOUT_MILLIS: u64 = 1000;
+pub const DEFAULT_REDIS_CONNECTION_TIMEOUT_MILLIS: u64 = 2000;
+/// Reconnecting after the connection drops is retried this many times, backing off exponentially from 100ms.
+pub const REDIS_RECONNECT_RETRIES: usize = 6;
+pub const TOTP_ISSUER: &str = "Live Bootcamp Auth";
+/// Number of 30 second steps either side of the current one in which a TOTP code is still accepted.
+pub const DEFAULT_TOTP_SKEW: u8 = 1;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Also how long the 2FA pending token lasts. It's banned for [BANNED_TOKEN_TTL_SECONDS](crate::utils::auth::BANNED_TOKEN_TTL_SECONDS)
+/// once used, which only outlasts tokens that live at most [TOKEN_TTL_SECONDS](crate::utils::auth::TOKEN_TTL_SECONDS),
+/// so this must not be longer.
+pub const TWO_FA_CODE_TTL_SECONDS: i64 = 600; // 10 minutes
+/// Wrong 2FA codes allowed per login attempt. The attempt is dropped at this many, and the user has to log in again.
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+pub const TWO_FA_RESEND_COOLDOWN_SECONDS: i64 = 30;
+/// Times the code of a login attempt can be resent. After that the user has to log in again.
+pub const MAX_TWO_FA_RESENDS: u32 = 3;
+/// Relying party the passkeys are scoped to. It has to be the domain the browser sees.
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_ORIGIN: &str = "http://localhost:3000";
+pub const WEBAUTHN_RP_NAME: &str = "Live Bootcamp Auth";
+pub const PASSKEY_CHALLENGE_TTL_SECONDS: i64 = 300; // 5 minutes
+/// Page the emailed login link opens, with the token appended as `?token=`.
+/// It should POST the token to `/login/magic-link/consume`, so link scanners that prefetch URLs don't use it up.
+pub const DEFAULT_MAGIC_LINK_URL: &str = "http://localhost:3000/magic-link";
+pub const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+/// Used magic links are banned for [BANNED_TOKEN_TTL_SECONDS](crate::utils::auth::BANNED_TOKEN_TTL_SECONDS), which only
+/// outlasts tokens that live at most [TOKEN_TTL_SECONDS](crate::utils::auth::TOKEN_TTL_SECONDS), so this must not be longer.
+pub const MAGIC_LINK_TTL_SECONDS: i64 = 600; // 10 minutes
+/// Failed logins for one account before it's locked, and for how long.
+pub const DEFAULT_LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+pub const DEFAULT_LOGIN_LOCKOUT_SECONDS: i64 = 900; // 15 minutes
+/// Higher than the per-account threshold, since many users can share an IP.
+pub const DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD: u32 = 50;
+/// Failed logins allowed before every further attempt has to wait.
+pub const LOGIN_FREE_FAILURES_BY_EMAIL: u32 = 2;
+pub const LOGIN_FREE_FAILURES_BY_IP_ADDRESS: u32 = 10;
+/// Page the emailed unlock link opens, with the token appended as `?token=`.
+/// It should POST the token to `/unlock-account`.
+pub const DEFAULT_UNLOCK_ACCOUNT_URL: &str = "http://localhost:3000/unlock-account";
+pub const UNLOCK_ACCOUNT_AUDIENCE: &str = "unlock-account";
+/// Used unlock links are banned for [BANNED_TOKEN_TTL_SECONDS](crate::utils::auth::BANNED_TOKEN_TTL_SECONDS), which only
+/// outlasts tokens that live at most [TOKEN_TTL_SECONDS](crate::utils::auth::TOKEN_TTL_SECONDS), so this must not be longer.
+pub const UNLOCK_ACCOUNT_TTL_SECONDS: i64 = 600; // 10 minutes
+/// Page the "this wasn't me" link in new login alerts opens, with the token appended as `?token=`.
+/// It should POST the token to `/report-login`.
+pub const DEFAULT_REPORT_LOGIN_URL: &str = "http://localhost:3000/report-login";
+pub const REPORT_LOGIN_AUDIENCE: &str = "report-login";
+/// Reporting revokes every token of the user, the link's own included, so it can outlive the banned token TTL.
+pub const REPORT_LOGIN_TTL_SECONDS: i64 = 604800; // 7 days
+pub const DEFAULT_SMTP_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_SMTP_MAX_CONNECTIONS: u32 = 4;
+/// How often the outbox is checked for retries that came due. New emails are sent right away.
+pub const EMAIL_OUTBOX_POLL_INTERVAL_SECONDS: u64 = 5;
+pub const EMAIL_OUTBOX_BATCH_SIZE: u32 = 10;
+/// A claimed email is tried again after this long if its worker never reports back, e.g. because it crashed.
+/// It has to cover sending a whole batch, each email taking up to the SMTP timeout.
+pub const EMAIL_OUTBOX_LEASE_SECONDS: i64 = 300; // 5 minutes
+/// Attempts before an email is dead-lettered. Retries back off from 30 seconds, doubling up to an hour.
+pub const MAX_EMAIL_DELIVERY_ATTEMPTS: u32 = 8;
+pub const EMAIL_RETRY_BASE_SECONDS: i64 = 30;
+pub const EMAIL_RETRY_MAX_SECONDS: i64 = 3600; // 1 hour
+/// How often expired banned tokens, 2FA codes and refresh tokens are deleted from Postgres. They're ignored once expired either way.
+pub const EXPIRED_RECORD_PURGE_INTERVAL_SECONDS: u64 = 900; // 15 minutes
+pub const DEFAULT_EMAIL_BRAND_NAME: &str = "Live Bootcamp Auth";
+pub const DEFAULT_EMAIL_BRAND_URL: &str = "http://localhost:8000";
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
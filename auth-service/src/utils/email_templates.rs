@@ -0,0 +1,225 @@
+use color_eyre::eyre::{Context, Result};
+use lazy_static::lazy_static;
+use minijinja::{context, Environment, Value};
+use crate::domain::{EmailMessage, Locale, TwoFACode};
+use crate::utils::constants::{
+    EMAIL_BRANDING,
+    EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    MAGIC_LINK_TTL_SECONDS,
+    PASSWORD_RESET_TOKEN_TTL_SECONDS,
+    TWO_FA_CODE_TTL_SECONDS,
+};
+
+/// Company details every email template can use.
+#[derive(Debug, Clone)]
+pub struct EmailBranding {
+    pub name: String,
+    /// Where the name in the email header links to.
+    pub url: String,
+    pub support_email: Option<String>,
+}
+
+// Each email has a `.txt` template with `subject` and `text` blocks, and a `.html` template.
+// HTML templates extend their locale's `base.html` and are escaped, text templates aren't.
+macro_rules! email_templates {
+    ($($name:literal),* $(,)?) => {
+        &[
+            ("en/base.html", include_str!("../../templates/emails/en/base.html")),
+            ("es/base.html", include_str!("../../templates/emails/es/base.html")),
+            $(
+                (concat!("en/", $name, ".txt"), include_str!(concat!("../../templates/emails/en/", $name, ".txt"))),
+                (concat!("en/", $name, ".html"), include_str!(concat!("../../templates/emails/en/", $name, ".html"))),
+                (concat!("es/", $name, ".txt"), include_str!(concat!("../../templates/emails/es/", $name, ".txt"))),
+                (concat!("es/", $name, ".html"), include_str!(concat!("../../templates/emails/es/", $name, ".html"))),
+            )*
+        ]
+    };
+}
+
+const TEMPLATES: &[(&str, &str)] = email_templates!(
+    "two_fa_code",
+    "email_verification",
+    "password_reset",
+    "magic_link",
+    "account_locked",
+    "new_login_alert",
+);
+
+lazy_static! {
+    static ref ENVIRONMENT: Environment<'static> = {
+        let mut environment = Environment::new();
+        for (name, source) in TEMPLATES {
+            environment.add_template(name, source)
+                .unwrap_or_else(|e| panic!("Invalid email template {}: {}", name, e));
+        }
+        environment
+    };
+}
+
+/// A transactional email, with what its template needs.
+#[derive(Debug, Clone)]
+pub enum EmailTemplate<'a> {
+    TwoFACode { code: &'a TwoFACode },
+    EmailVerification { token: &'a str },
+    PasswordReset { token: &'a str },
+    MagicLink { link: &'a str },
+    AccountLocked { link: &'a str, unlocks_in_minutes: i64 },
+    NewLoginAlert { device: &'a str, ip_address: Option<&'a str>, time: &'a str, link: &'a str },
+}
+
+impl EmailTemplate<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::TwoFACode { .. } => "two_fa_code",
+            Self::EmailVerification { .. } => "email_verification",
+            Self::PasswordReset { .. } => "password_reset",
+            Self::MagicLink { .. } => "magic_link",
+            Self::AccountLocked { .. } => "account_locked",
+            Self::NewLoginAlert { .. } => "new_login_alert",
+        }
+    }
+
+    fn variables(&self) -> Value {
+        match self {
+            Self::TwoFACode { code } => context! {
+                code => code.as_ref(),
+                expires_in_minutes => TWO_FA_CODE_TTL_SECONDS / 60,
+            },
+            Self::EmailVerification { token } => context! {
+                token,
+                expires_in_hours => EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600,
+            },
+            Self::PasswordReset { token } => context! {
+                token,
+                expires_in_minutes => PASSWORD_RESET_TOKEN_TTL_SECONDS / 60,
+            },
+            Self::MagicLink { link } => context! {
+                link,
+                expires_in_minutes => MAGIC_LINK_TTL_SECONDS / 60,
+            },
+            Self::AccountLocked { link, unlocks_in_minutes } => context! { link, unlocks_in_minutes },
+            Self::NewLoginAlert { device, ip_address, time, link } => context! { device, ip_address, time, link },
+        }
+    }
+
+    /// Renders the email in `locale`, with the branding from [EMAIL_BRANDING].
+    pub fn render(&self, locale: Locale) -> Result<EmailMessage> {
+        self.render_with(locale, &EMAIL_BRANDING)
+    }
+
+    fn render_with(&self, locale: Locale, branding: &EmailBranding) -> Result<EmailMessage> {
+        let variables = context! {
+            brand_name => &branding.name,
+            brand_url => &branding.url,
+            support_email => &branding.support_email,
+            ..self.variables()
+        };
+        let name = format!("{}/{}", locale.as_str(), self.name());
+
+        let text_template = ENVIRONMENT.get_template(&format!("{}.txt", name))
+            .wrap_err("missing text email template")?;
+        let (subject, text) = text_template.render_captured(&variables)
+            .wrap_err("failed to render text email")?
+            .with_state_mut(|state| Ok::<_, minijinja::Error>((state.render_block("subject")?, state.render_block("text")?)))
+            .wrap_err("failed to render text email blocks")?;
+
+        let html = ENVIRONMENT.get_template(&format!("{}.html", name))
+            .wrap_err("missing HTML email template")?
+            .render(&variables)
+            .wrap_err("failed to render HTML email")?;
+
+        Ok(EmailMessage {
+            subject: subject.trim().to_string(),
+            text: text.trim().to_string(),
+            html: html.trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branding() -> EmailBranding {
+        EmailBranding {
+            name: "Acme".to_string(),
+            url: "https://acme.example".to_string(),
+            support_email: Some("help@acme.example".to_string()),
+        }
+    }
+
+    fn all_templates(code: &TwoFACode) -> Vec<EmailTemplate<'_>> {
+        vec![
+            EmailTemplate::TwoFACode { code },
+            EmailTemplate::EmailVerification { token: "verification-token" },
+            EmailTemplate::PasswordReset { token: "reset-token" },
+            EmailTemplate::MagicLink { link: "https://acme.example/magic-link?token=abc" },
+            EmailTemplate::AccountLocked { link: "https://acme.example/unlock?token=abc", unlocks_in_minutes: 15 },
+            EmailTemplate::NewLoginAlert {
+                device: "Firefox on Linux",
+                ip_address: Some("203.0.113.7"),
+                time: "2025-04-26 10:00 UTC",
+                link: "https://acme.example/report-login?token=abc",
+            },
+        ]
+    }
+
+    #[test]
+    fn every_template_renders_in_every_locale() {
+        let code = TwoFACode::default();
+        for template in all_templates(&code) {
+            for locale in [Locale::En, Locale::Es] {
+                let message = template.render_with(locale, &branding())
+                    .unwrap_or_else(|e| panic!("{} failed in {:?}: {:?}", template.name(), locale, e));
+
+                assert!(message.subject.contains("Acme"), "{} subject in {:?}", template.name(), locale);
+                assert!(!message.subject.contains('\n'));
+                assert!(message.text.contains("Acme"));
+                // HTML escaping turns the slashes of the URL into entities.
+                assert!(message.html.contains("https:&#x2f;&#x2f;acme.example"));
+                assert!(message.html.contains("help@acme.example"));
+                assert!(!message.text.contains("{{") && !message.html.contains("{{"));
+            }
+        }
+    }
+
+    #[test]
+    fn code_stands_alone_in_text_and_html() {
+        let code = TwoFACode::parse("123456".to_string()).unwrap();
+        let message = EmailTemplate::TwoFACode { code: &code }.render_with(Locale::En, &branding()).unwrap();
+
+        assert_eq!(message.subject, "Your Acme login code");
+        assert!(message.text.lines().any(|line| line == "123456"));
+        assert!(message.html.contains(">123456<"));
+        assert!(message.text.contains("10 minutes"));
+    }
+
+    #[test]
+    fn locale_picks_translation() {
+        let code = TwoFACode::default();
+        let english = EmailTemplate::TwoFACode { code: &code }.render_with(Locale::En, &branding()).unwrap();
+        let spanish = EmailTemplate::TwoFACode { code: &code }.render_with(Locale::Es, &branding()).unwrap();
+
+        assert_eq!(spanish.subject, "Tu código de acceso a Acme");
+        assert_ne!(english.text, spanish.text);
+        assert!(spanish.html.contains("lang=\"es\""));
+    }
+
+    #[test]
+    fn html_escapes_variables_but_text_does_not() {
+        let template = EmailTemplate::NewLoginAlert { device: "<b>Evil</b> & co", ip_address: None, time: "now", link: "https://acme.example" };
+        let message = template.render_with(Locale::En, &branding()).unwrap();
+
+        assert!(message.html.contains("&lt;b&gt;Evil&lt;&#x2f;b&gt; &amp; co"));
+        assert!(message.text.contains("Device: <b>Evil</b> & co"));
+        assert!(!message.text.contains("IP address"));
+    }
+
+    #[test]
+    fn support_email_is_optional() {
+        let branding = EmailBranding { support_email: None, ..branding() };
+        let message = EmailTemplate::PasswordReset { token: "token" }.render_with(Locale::En, &branding).unwrap();
+
+        assert!(!message.html.contains("mailto:"));
+    }
+}
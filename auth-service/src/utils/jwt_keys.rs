@@ -0,0 +1,564 @@
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters,
+    CommonParameters,
+    EllipticCurve,
+    Jwk,
+    JwkSet,
+    KeyAlgorithm,
+    OctetKeyPairParameters,
+    OctetKeyPairType,
+    PublicKeyUse,
+    RSAKeyParameters,
+    RSAKeyType,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use pkcs1::der::Decode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use spki::SubjectPublicKeyInfoRef;
+use super::auth::{EXP_LEEWAY_SECONDS, TOKEN_TTL_SECONDS};
+use super::constants::{
+    MAGIC_LINK_TTL_SECONDS,
+    REPORT_LOGIN_TTL_SECONDS,
+    TWO_FA_CODE_TTL_SECONDS,
+    UNLOCK_ACCOUNT_TTL_SECONDS,
+};
+
+const RSA_ENCRYPTION_OID: &str = "1.2.840.113549.1.1.1";
+const ED25519_OID: &str = "1.3.101.112";
+/// Key ID used for the shared secret when none is configured.
+pub const DEFAULT_SECRET_KEY_ID: &str = "hs256";
+/// Lifetimes of every kind of token signed with the keyring.
+const SIGNED_TOKEN_TTLS_SECONDS: [i64; 5] = [
+    TOKEN_TTL_SECONDS,
+    TWO_FA_CODE_TTL_SECONDS,
+    MAGIC_LINK_TTL_SECONDS,
+    UNLOCK_ACCOUNT_TTL_SECONDS,
+    REPORT_LOGIN_TTL_SECONDS,
+];
+/// How long a retired key keeps verifying. It has to outlive every token the key signed, including the leeway on `exp`,
+/// so it's as long as the longest-lived token, the "this wasn't me" link in new login alerts.
+pub const RETIRED_KEY_TTL_SECONDS: i64 = longest(&SIGNED_TOKEN_TTLS_SECONDS) + EXP_LEEWAY_SECONDS;
+
+/// Keys used to sign and verify JWTs.
+///
+/// One key signs new tokens. Verify-only keys, e.g. the keys of the previous configuration, only check tokens.
+/// Tokens carry the `kid` of the key that signed them, and verification looks the key up by it.
+/// Only asymmetric public keys are published in the JWKS, a shared secret never is.
+///
+/// A next key can be configured ahead of a rotation. It verifies from the start, and [JwtKeyring::rotate] promotes it
+/// to signing key, keeping the old one as verify-only for [RETIRED_KEY_TTL_SECONDS], after which it's dropped.
+/// Every instance is configured with the same keys, so tokens signed by an instance that rotated
+/// verify on the others, and after a restart too.
+pub struct JwtKeyring {
+    keys: RwLock<Keys>,
+}
+
+struct Keys {
+    signing_key: JwtSigningKey,
+    next_key: Option<JwtSigningKey>,
+    verify_only_keys: Vec<JwtKey>,
+    retired_keys: Vec<(DateTime<Utc>, JwtKey)>,
+}
+
+/// A key that signs tokens, and verifies them too.
+pub struct JwtSigningKey {
+    key: JwtKey,
+    encoding_key: EncodingKey,
+}
+
+/// A key that only verifies tokens.
+pub struct JwtKey {
+    key_id: String,
+    algorithm: Algorithm,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl JwtKeyring {
+    /// HS256 with a shared secret. Every service verifying tokens needs the same secret.
+    pub fn from_secret(key_id: Option<String>, secret: &[u8]) -> Self {
+        let key_id = key_id.unwrap_or(DEFAULT_SECRET_KEY_ID.to_string());
+
+        Self::new(JwtSigningKey::from_secret(key_id, secret))
+    }
+
+    /// RS256 or EdDSA (Ed25519) with a PKCS#8 private key and SPKI public key.
+    /// The key ID defaults to the RFC 7638 thumbprint of the public key.
+    pub fn from_pem(
+        algorithm: Algorithm,
+        key_id: Option<String>,
+        private_key_pem: &[u8],
+        public_key_pem: &[u8],
+    ) -> Result<Self> {
+        JwtSigningKey::from_pem(algorithm, key_id, private_key_pem, public_key_pem).map(Self::new)
+    }
+
+    pub fn new(signing_key: JwtSigningKey) -> Self {
+        Self {
+            keys: RwLock::new(Keys {
+                signing_key,
+                next_key: None,
+                verify_only_keys: vec![],
+                retired_keys: vec![],
+            })
+        }
+    }
+
+    /// Sets the key [JwtKeyring::rotate] promotes. It verifies right away,
+    /// so tokens from instances that rotated before this one are accepted.
+    pub fn with_next_key(mut self, next_key: JwtSigningKey) -> Result<Self> {
+        let keys = self.keys_mut();
+        keys.check_key_id_is_unused(&next_key.key.key_id)?;
+        keys.next_key = Some(next_key);
+
+        Ok(self)
+    }
+
+    /// Adds a key that verifies tokens without ever signing them, e.g. one that signed tokens before the current key.
+    pub fn with_verify_only_key(mut self, key: JwtKey) -> Result<Self> {
+        let keys = self.keys_mut();
+        keys.check_key_id_is_unused(&key.key_id)?;
+        keys.verify_only_keys.push(key);
+
+        Ok(self)
+    }
+
+    pub fn signing_key_id(&self) -> String {
+        self.read().signing_key.key.key_id.clone()
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let keys = self.read();
+        let mut header = Header::new(keys.signing_key.key.algorithm);
+        header.kid = Some(keys.signing_key.key.key_id.clone());
+
+        encode(&header, claims, &keys.signing_key.encoding_key)
+            .wrap_err("failed to create token")
+    }
+
+    /// Decodes a token with the key named by its `kid`.
+    /// The algorithm always comes from the key, never from the token header.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str, mut validation: Validation) -> Result<T> {
+        let header = decode_header(token).wrap_err("failed to decode token header")?;
+        let key_id = header.kid.wrap_err("token has no kid")?;
+        let keys = self.read();
+        let key = keys.verifying_keys(Utc::now())
+            .find(|key| key.key_id == key_id)
+            .wrap_err(format!("unknown kid: {}", key_id))?;
+
+        validation.algorithms = vec![key.algorithm];
+
+        decode::<T>(token, &key.decoding_key, &validation)
+            .map(|data| data.claims)
+            .wrap_err("failed to decode token")
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        let keys = self.read();
+        let keys = keys.verifying_keys(Utc::now())
+            .filter_map(|key| key.jwk.clone())
+            .collect();
+
+        JwkSet { keys }
+    }
+
+    /// Signs with the next key from now on and returns its `kid`, or `None` if there's no next key to promote.
+    /// The previous key keeps verifying until the tokens it signed have expired.
+    pub fn rotate(&self) -> Option<String> {
+        let now = Utc::now();
+        let mut keys = self.write();
+        let next_key = keys.next_key.take()?;
+        let key_id = next_key.key.key_id.clone();
+
+        let retired_key = std::mem::replace(&mut keys.signing_key, next_key);
+        keys.retired_keys.push((now, retired_key.key));
+
+        Some(key_id)
+    }
+
+    // The keys are always left consistent, so a panic elsewhere while holding the lock doesn't matter.
+    fn read(&self) -> RwLockReadGuard<'_, Keys> {
+        self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Keys> {
+        self.keys.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn keys_mut(&mut self) -> &mut Keys {
+        self.keys.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Keys {
+    // Expired retired keys are skipped here.
+    fn verifying_keys(&self, now: DateTime<Utc>) -> impl Iterator<Item = &JwtKey> {
+        let retired_keys = self.retired_keys
+            .iter()
+            .filter(move |(retired_at, _)| still_verifies(*retired_at, now))
+            .map(|(_, key)| key);
+
+        std::iter::once(&self.signing_key.key)
+            .chain(self.next_key.iter().map(|next_key| &next_key.key))
+            .chain(self.verify_only_keys.iter())
+            .chain(retired_keys)
+    }
+
+    // Tokens only name their key by `kid`, so two keys with the same one couldn't be told apart.
+    fn check_key_id_is_unused(&self, key_id: &str) -> Result<()> {
+        if self.verifying_keys(Utc::now()).any(|key| key.key_id == key_id) {
+            return Err(eyre!("JWT kid is used by more than one key: {}", key_id));
+        }
+
+        Ok(())
+    }
+}
+
+fn still_verifies(retired_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now < retired_at + Duration::seconds(RETIRED_KEY_TTL_SECONDS)
+}
+
+const fn longest(ttls: &[i64]) -> i64 {
+    let mut longest = 0;
+    let mut i = 0;
+    while i < ttls.len() {
+        if ttls[i] > longest {
+            longest = ttls[i];
+        }
+        i += 1;
+    }
+    longest
+}
+
+impl JwtSigningKey {
+    /// HS256 with a shared secret.
+    pub fn from_secret(key_id: String, secret: &[u8]) -> Self {
+        Self {
+            key: JwtKey::from_secret(key_id, secret),
+            encoding_key: EncodingKey::from_secret(secret),
+        }
+    }
+
+    /// RS256 or EdDSA (Ed25519) with a PKCS#8 private key and SPKI public key.
+    /// The key ID defaults to the RFC 7638 thumbprint of the public key.
+    pub fn from_pem(
+        algorithm: Algorithm,
+        key_id: Option<String>,
+        private_key_pem: &[u8],
+        public_key_pem: &[u8],
+    ) -> Result<Self> {
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_key_pem),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(private_key_pem),
+            _ => return Err(eyre!("unsupported JWT algorithm: {:?}", algorithm)),
+        }
+            .wrap_err("failed to load JWT private key")?;
+
+        let key = JwtKey::from_public_pem(algorithm, key_id, public_key_pem)?;
+
+        Ok(Self { key, encoding_key })
+    }
+}
+
+impl JwtKey {
+    /// HS256 with a shared secret.
+    pub fn from_secret(key_id: String, secret: &[u8]) -> Self {
+        Self {
+            key_id,
+            algorithm: Algorithm::HS256,
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    /// RS256 or EdDSA (Ed25519) with an SPKI public key.
+    /// The key ID defaults to the RFC 7638 thumbprint of the public key.
+    pub fn from_public_pem(algorithm: Algorithm, key_id: Option<String>, public_key_pem: &[u8]) -> Result<Self> {
+        let jwk = public_jwk(algorithm, key_id, public_key_pem)?;
+        let key_id = jwk.common.key_id.clone().wrap_err("public key has no kid")?;
+        let decoding_key = DecodingKey::from_jwk(&jwk)
+            .wrap_err("failed to load JWT public key")?;
+
+        Ok(Self { key_id, algorithm, decoding_key, jwk: Some(jwk) })
+    }
+}
+
+fn public_jwk(algorithm: Algorithm, key_id: Option<String>, public_key_pem: &[u8]) -> Result<Jwk> {
+    let pem = pem::parse(public_key_pem).wrap_err("JWT public key is not valid PEM")?;
+    let spki = SubjectPublicKeyInfoRef::from_der(pem.contents())
+        .map_err(|e| eyre!("JWT public key is not a valid SPKI public key: {}", e))?;
+
+    jwk_from_public_key(algorithm, key_id, &spki.algorithm.oid.to_string(), spki.subject_public_key.raw_bytes())
+}
+
+fn jwk_from_public_key(algorithm: Algorithm, key_id: Option<String>, key_oid: &str, key_bytes: &[u8]) -> Result<Jwk> {
+    // Members for the RFC 7638 thumbprint have to be in lexicographic order.
+    let (parameters, key_algorithm, thumbprint_input) = match (algorithm, key_oid) {
+        (Algorithm::RS256, RSA_ENCRYPTION_OID) => {
+            let rsa_key = pkcs1::RsaPublicKey::from_der(key_bytes)
+                .map_err(|e| eyre!("JWT public key is not a valid RSA key: {}", e))?;
+            let n = BASE64_URL.encode(rsa_key.modulus.as_bytes());
+            let e = BASE64_URL.encode(rsa_key.public_exponent.as_bytes());
+            let thumbprint_input = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+            let parameters = AlgorithmParameters::RSA(RSAKeyParameters { key_type: RSAKeyType::RSA, n, e });
+            (parameters, KeyAlgorithm::RS256, thumbprint_input)
+        },
+        (Algorithm::EdDSA, ED25519_OID) => {
+            let x = BASE64_URL.encode(key_bytes);
+            let thumbprint_input = format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x);
+            let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            });
+            (parameters, KeyAlgorithm::EdDSA, thumbprint_input)
+        },
+        _ => return Err(eyre!("JWT public key doesn't match algorithm {:?}", algorithm)),
+    };
+
+    let key_id = key_id.unwrap_or_else(|| BASE64_URL.encode(Sha256::digest(thumbprint_input.as_bytes())));
+
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(key_id),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use chrono::Utc;
+    use serde::Deserialize;
+    use super::*;
+
+    const RS256_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwt_rs256.pem");
+    const RS256_PUBLIC_KEY: &str = include_str!("../../tests/fixtures/jwt_rs256.pub.pem");
+    const EDDSA_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwt_eddsa.pem");
+    const EDDSA_PUBLIC_KEY: &str = include_str!("../../tests/fixtures/jwt_eddsa.pub.pem");
+    const RS256_NEXT_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwt_rs256_next.pem");
+    const RS256_NEXT_PUBLIC_KEY: &str = include_str!("../../tests/fixtures/jwt_rs256_next.pub.pem");
+    const EDDSA_NEXT_PRIVATE_KEY: &str = include_str!("../../tests/fixtures/jwt_eddsa_next.pem");
+    const EDDSA_NEXT_PUBLIC_KEY: &str = include_str!("../../tests/fixtures/jwt_eddsa_next.pub.pem");
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims { sub: "test@example.com".to_string(), exp: Utc::now().timestamp() as usize + 600 }
+    }
+
+    fn rs256_keys() -> JwtKeyring {
+        JwtKeyring::from_pem(Algorithm::RS256, None, RS256_PRIVATE_KEY.as_bytes(), RS256_PUBLIC_KEY.as_bytes()).unwrap()
+    }
+
+    fn eddsa_keys() -> JwtKeyring {
+        JwtKeyring::from_pem(Algorithm::EdDSA, None, EDDSA_PRIVATE_KEY.as_bytes(), EDDSA_PUBLIC_KEY.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for keys in [JwtKeyring::from_secret(None, b"secret"), rs256_keys(), eddsa_keys()] {
+            let token = keys.encode(&claims()).unwrap();
+            let decoded: TestClaims = keys.decode(&token, Validation::default()).unwrap();
+            assert_eq!(decoded, claims());
+        }
+    }
+
+    #[test]
+    fn test_token_verifies_with_published_jwk() {
+        for keys in [rs256_keys(), eddsa_keys()] {
+            let token = keys.encode(&claims()).unwrap();
+            let kid = decode_header(&token).unwrap().kid.unwrap();
+
+            let jwks = keys.jwks();
+            let jwk = jwks.find(&kid).expect("signing key is published");
+            let algorithm = Algorithm::from_str(&jwk.common.key_algorithm.unwrap().to_string()).unwrap();
+            let decoded = decode::<TestClaims>(&token, &DecodingKey::from_jwk(jwk).unwrap(), &Validation::new(algorithm));
+            assert!(decoded.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_secret_is_not_published() {
+        assert!(JwtKeyring::from_secret(None, b"secret").jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_key_id_defaults_to_thumbprint() {
+        let keys = rs256_keys();
+        assert_eq!(keys.jwks().keys.len(), 1);
+        assert_eq!(keys.signing_key_id(), keys.jwks().keys[0].common.key_id.clone().unwrap());
+        assert_eq!(keys.signing_key_id().len(), 43);
+
+        let keys = JwtKeyring::from_pem(Algorithm::EdDSA, Some("ed-1".to_string()), EDDSA_PRIVATE_KEY.as_bytes(), EDDSA_PUBLIC_KEY.as_bytes()).unwrap();
+        assert_eq!(keys.signing_key_id(), "ed-1");
+    }
+
+    #[test]
+    fn test_rejects_token_signed_with_other_key() {
+        let token = eddsa_keys().encode(&claims()).unwrap();
+
+        assert!(rs256_keys().decode::<TestClaims>(&token, Validation::default()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_token_without_kid() {
+        let token = encode(&Header::new(Algorithm::HS256), &claims(), &EncodingKey::from_secret(b"secret")).unwrap();
+
+        assert!(JwtKeyring::from_secret(None, b"secret").decode::<TestClaims>(&token, Validation::default()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_public_key() {
+        let result = JwtKeyring::from_pem(Algorithm::RS256, None, RS256_PRIVATE_KEY.as_bytes(), EDDSA_PUBLIC_KEY.as_bytes());
+
+        assert!(result.is_err());
+    }
+
+    fn rotatable_keys() -> [JwtKeyring; 3] {
+        let next_rs256_key = JwtSigningKey::from_pem(Algorithm::RS256, None, RS256_NEXT_PRIVATE_KEY.as_bytes(), RS256_NEXT_PUBLIC_KEY.as_bytes()).unwrap();
+        let next_eddsa_key = JwtSigningKey::from_pem(Algorithm::EdDSA, None, EDDSA_NEXT_PRIVATE_KEY.as_bytes(), EDDSA_NEXT_PUBLIC_KEY.as_bytes()).unwrap();
+
+        [
+            JwtKeyring::from_secret(None, b"secret").with_next_key(JwtSigningKey::from_secret("hs256-next".to_string(), b"next secret")).unwrap(),
+            rs256_keys().with_next_key(next_rs256_key).unwrap(),
+            eddsa_keys().with_next_key(next_eddsa_key).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_tokens_valid() {
+        for keys in rotatable_keys() {
+            let old_key_id = keys.signing_key_id();
+            let old_token = keys.encode(&claims()).unwrap();
+
+            let new_key_id = keys.rotate().unwrap();
+            assert_ne!(new_key_id, old_key_id);
+            assert_eq!(keys.signing_key_id(), new_key_id);
+
+            let new_token = keys.encode(&claims()).unwrap();
+            assert_eq!(decode_header(&new_token).unwrap().kid, Some(new_key_id));
+            assert!(keys.decode::<TestClaims>(&old_token, Validation::default()).is_ok());
+            assert!(keys.decode::<TestClaims>(&new_token, Validation::default()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_next_key_verifies_before_rotation() {
+        for (rotated, not_rotated) in rotatable_keys().into_iter().zip(rotatable_keys()) {
+            rotated.rotate().unwrap();
+
+            let token = rotated.encode(&claims()).unwrap();
+            assert!(not_rotated.decode::<TestClaims>(&token, Validation::default()).is_ok());
+            let token = not_rotated.encode(&claims()).unwrap();
+            assert!(rotated.decode::<TestClaims>(&token, Validation::default()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_rotation_without_next_key_keeps_signing_key() {
+        let keys = rs256_keys();
+        let key_id = keys.signing_key_id();
+
+        assert_eq!(keys.rotate(), None);
+        assert_eq!(keys.signing_key_id(), key_id);
+
+        let keys = rotatable_keys().into_iter().next().unwrap();
+        keys.rotate().unwrap();
+        assert_eq!(keys.rotate(), None);
+    }
+
+    #[test]
+    fn test_rotation_publishes_both_keys() {
+        for keys in rotatable_keys().into_iter().skip(1) {
+            let old_key_id = keys.signing_key_id();
+            assert_eq!(keys.jwks().keys.len(), 2);
+
+            let new_key_id = keys.rotate().unwrap();
+
+            let jwks = keys.jwks();
+            assert_eq!(jwks.keys.len(), 2);
+            assert!(jwks.find(&old_key_id).is_some());
+            assert!(jwks.find(&new_key_id).is_some());
+        }
+    }
+
+    #[test]
+    fn test_verify_only_key_verifies_but_never_signs() {
+        let old_keys = JwtKeyring::from_secret(Some("hs256-old".to_string()), b"old secret");
+        let old_token = old_keys.encode(&claims()).unwrap();
+        let keys = JwtKeyring::from_secret(None, b"secret")
+            .with_verify_only_key(JwtKey::from_secret("hs256-old".to_string(), b"old secret"))
+            .unwrap();
+
+        assert!(keys.decode::<TestClaims>(&old_token, Validation::default()).is_ok());
+        assert_eq!(keys.signing_key_id(), DEFAULT_SECRET_KEY_ID);
+        assert!(keys.jwks().keys.is_empty());
+
+        let old_token = eddsa_keys().encode(&claims()).unwrap();
+        let old_key = JwtKey::from_public_pem(Algorithm::EdDSA, None, EDDSA_PUBLIC_KEY.as_bytes()).unwrap();
+        let keys = rs256_keys().with_verify_only_key(old_key).unwrap();
+
+        assert!(keys.decode::<TestClaims>(&old_token, Validation::default()).is_ok());
+        assert_eq!(keys.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_key_with_kid_already_in_use() {
+        let next_key = JwtSigningKey::from_secret(DEFAULT_SECRET_KEY_ID.to_string(), b"next secret");
+        assert!(JwtKeyring::from_secret(None, b"secret").with_next_key(next_key).is_err());
+
+        let old_key = JwtKey::from_public_pem(Algorithm::RS256, None, RS256_PUBLIC_KEY.as_bytes()).unwrap();
+        assert!(rs256_keys().with_verify_only_key(old_key).is_err());
+    }
+
+    #[test]
+    fn test_retired_key_outlives_every_token() {
+        assert_eq!(Validation::default().leeway, EXP_LEEWAY_SECONDS as u64);
+        for ttl in SIGNED_TOKEN_TTLS_SECONDS {
+            assert!(ttl + EXP_LEEWAY_SECONDS <= RETIRED_KEY_TTL_SECONDS);
+        }
+    }
+
+    #[test]
+    fn test_retired_key_verifies_report_login_token_until_it_expires() {
+        let keys = rotatable_keys().into_iter().last().unwrap();
+        let token = keys.encode(&TestClaims {
+            sub: "test@example.com".to_string(),
+            exp: Utc::now().timestamp() as usize + REPORT_LOGIN_TTL_SECONDS as usize,
+        }).unwrap();
+        keys.rotate().unwrap();
+
+        keys.write().retired_keys[0].0 -= Duration::seconds(REPORT_LOGIN_TTL_SECONDS - 60);
+
+        assert!(keys.decode::<TestClaims>(&token, Validation::default()).is_ok());
+    }
+
+    #[test]
+    fn test_retired_key_is_dropped_after_token_lifetime() {
+        let keys = rotatable_keys().into_iter().last().unwrap();
+        let old_key_id = keys.signing_key_id();
+        let old_token = keys.encode(&claims()).unwrap();
+        keys.rotate().unwrap();
+
+        keys.write().retired_keys[0].0 -= Duration::seconds(RETIRED_KEY_TTL_SECONDS);
+
+        assert!(keys.decode::<TestClaims>(&old_token, Validation::default()).is_err());
+        assert!(keys.jwks().find(&old_key_id).is_none());
+    }
+}
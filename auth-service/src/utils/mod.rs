@@ -1,5 +1,9 @@
 pub mod constants;
 pub mod auth;
+pub mod totp;
+pub mod webauthn;
+pub mod jwt_keys;
+pub mod email_templates;
 mod tracing;
 
 pub use tracing::*;
\ No newline at end of file
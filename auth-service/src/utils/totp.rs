@@ -0,0 +1,152 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, TOTP};
+use crate::domain::{Email, TotpSecret, TwoFACode};
+
+use super::constants::{TOTP_ENCRYPTION_KEY, TOTP_ISSUER, TOTP_SKEW};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const NONCE_LENGTH: usize = 12;
+
+fn build_totp(secret: &TotpSecret, email: &Email) -> Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        *TOTP_SKEW,
+        TOTP_STEP_SECONDS,
+        secret.to_bytes()?,
+        Some(TOTP_ISSUER.to_string()),
+        email.as_ref().expose_secret().to_string(),
+    )
+        .map_err(|e| eyre!("failed to build TOTP: {}", e))
+}
+
+// Build the `otpauth://` URI that authenticator apps import, usually through a QR code
+pub fn generate_otpauth_uri(secret: &TotpSecret, email: &Email) -> Result<String> {
+    Ok(build_totp(secret, email)?.get_url())
+}
+
+// Check a code against the current time step, allowing `TOTP_SKEW` steps of clock drift either way.
+// Returns the step the code is for, so the caller can make sure it's only accepted once.
+pub fn verify_totp_code(secret: &TotpSecret, email: &Email, code: &TwoFACode) -> Result<Option<u64>> {
+    let mut totp = build_totp(secret, email)?;
+    let skew = totp.skew as u64;
+    // Each step is checked on its own, to learn which one matched
+    totp.skew = 0;
+
+    let current_step = current_time()? / TOTP_STEP_SECONDS;
+    Ok((current_step.saturating_sub(skew)..=current_step + skew)
+        .find(|step| totp.check(code.as_ref(), step * TOTP_STEP_SECONDS)))
+}
+
+fn current_time() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("system time is before the unix epoch")?
+        .as_secs())
+}
+
+fn cipher() -> Aes256Gcm {
+    // Hashing lets the key be configured as any string rather than exactly 32 bytes
+    let key = Sha256::digest(TOTP_ENCRYPTION_KEY.as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+// Encrypt the secret for storage as base64(nonce || ciphertext)
+pub fn encrypt_totp_secret(secret: &TotpSecret) -> Result<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher()
+        .encrypt(&nonce, secret.as_ref().expose_secret().as_bytes())
+        .map_err(|_| eyre!("failed to encrypt TOTP secret"))?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(bytes))
+}
+
+pub fn decrypt_totp_secret(encrypted: &str) -> Result<TotpSecret> {
+    let bytes = BASE64.decode(encrypted)
+        .wrap_err("encrypted TOTP secret is not valid base64")?;
+    if bytes.len() <= NONCE_LENGTH {
+        return Err(eyre!("encrypted TOTP secret is too short"));
+    }
+
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    let plaintext = cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| eyre!("failed to decrypt TOTP secret"))?;
+    let secret = String::from_utf8(plaintext)
+        .wrap_err("decrypted TOTP secret is not valid UTF-8")?;
+
+    TotpSecret::parse(Secret::new(secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_string())).unwrap()
+    }
+
+    fn current_code(secret: &TotpSecret) -> TwoFACode {
+        let code = build_totp(secret, &test_email()).unwrap().generate_current().unwrap();
+        TwoFACode::parse(code).unwrap()
+    }
+
+    #[test]
+    fn test_verify_totp_code() {
+        let secret = TotpSecret::default();
+        let code = current_code(&secret);
+        let current_step = current_time().unwrap() / TOTP_STEP_SECONDS;
+        assert_eq!(verify_totp_code(&secret, &test_email(), &code).unwrap(), Some(current_step));
+    }
+
+    #[test]
+    fn test_verify_totp_code_within_skew() {
+        let secret = TotpSecret::default();
+        let next_step = current_time().unwrap() / TOTP_STEP_SECONDS + 1;
+        let code = build_totp(&secret, &test_email()).unwrap().generate(next_step * TOTP_STEP_SECONDS);
+
+        let code = TwoFACode::parse(code).unwrap();
+        assert_eq!(verify_totp_code(&secret, &test_email(), &code).unwrap(), Some(next_step));
+    }
+
+    #[test]
+    fn test_verify_totp_code_with_wrong_secret() {
+        let code = current_code(&TotpSecret::default());
+        assert_eq!(verify_totp_code(&TotpSecret::default(), &test_email(), &code).unwrap(), None);
+    }
+
+    #[test]
+    fn test_generate_otpauth_uri() {
+        let secret = TotpSecret::default();
+        let uri = generate_otpauth_uri(&secret, &test_email()).unwrap();
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains(&format!("secret={}", secret.as_ref().expose_secret())));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_totp_secret() {
+        let secret = TotpSecret::default();
+        let encrypted = encrypt_totp_secret(&secret).unwrap();
+        assert!(!encrypted.contains(secret.as_ref().expose_secret().as_str()));
+        assert_eq!(decrypt_totp_secret(&encrypted).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_decrypt_tampered_totp_secret() {
+        let encrypted = encrypt_totp_secret(&TotpSecret::default()).unwrap();
+        let mut bytes = BASE64.decode(&encrypted).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(decrypt_totp_secret(&BASE64.encode(bytes)).is_err());
+    }
+}
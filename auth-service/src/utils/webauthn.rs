@@ -0,0 +1,416 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use ciborium::Value;
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::domain::{PasskeyChallenge, PasskeyCredential};
+
+use super::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID};
+
+/// COSE algorithm identifier for ECDSA with P-256 and SHA-256, the only one we accept.
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+const RP_ID_HASH_LENGTH: usize = 32;
+const AAGUID_LENGTH: usize = 16;
+
+// COSE_Key map labels and values for an EC2 P-256 key (RFC 9053)
+const COSE_KEY_KTY: i64 = 1;
+const COSE_KEY_ALG: i64 = 3;
+const COSE_KEY_CRV: i64 = -1;
+const COSE_KEY_X: i64 = -2;
+const COSE_KEY_Y: i64 = -3;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_CRV_P256: i64 = 1;
+
+/// Credential extracted from a verified registration.
+#[derive(Debug)]
+pub struct RegisteredCredential {
+    pub credential_id: String,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    /// Credential ID and COSE public key, only present when registering.
+    attested_credential: Option<(Vec<u8>, Value)>,
+}
+
+// Verify the response to `navigator.credentials.create()`.
+// Only "none" attestation is supported since we don't restrict which authenticators can be used.
+pub fn verify_registration(
+    challenge: &PasskeyChallenge,
+    client_data_json: &str,
+    attestation_object: &str,
+) -> Result<RegisteredCredential> {
+    verify_client_data(client_data_json, "webauthn.create", challenge)?;
+
+    let attestation_object = decode_base64_url(attestation_object, "attestationObject")?;
+    let attestation: Value = ciborium::de::from_reader(attestation_object.as_slice())
+        .wrap_err("attestationObject is not valid CBOR")?;
+    let attestation = attestation.as_map().wrap_err("attestationObject is not a CBOR map")?;
+
+    let fmt = map_get_text(attestation, "fmt")
+        .and_then(Value::as_text)
+        .wrap_err("attestationObject is missing fmt")?;
+    if fmt != "none" {
+        return Err(eyre!("unsupported attestation format: {}", fmt));
+    }
+
+    let auth_data = map_get_text(attestation, "authData")
+        .and_then(Value::as_bytes)
+        .wrap_err("attestationObject is missing authData")?;
+    let auth_data = parse_authenticator_data(auth_data)?;
+    verify_authenticator_data(&auth_data)?;
+
+    let (credential_id, cose_key) = auth_data.attested_credential
+        .wrap_err("authData is missing the attested credential")?;
+    let public_key = parse_cose_key(&cose_key)?;
+
+    Ok(RegisteredCredential {
+        credential_id: BASE64_URL.encode(credential_id),
+        public_key,
+        sign_count: auth_data.sign_count,
+    })
+}
+
+// Verify the response to `navigator.credentials.get()` and return the new signature counter.
+pub fn verify_authentication(
+    challenge: &PasskeyChallenge,
+    credential: &PasskeyCredential,
+    client_data_json: &str,
+    authenticator_data: &str,
+    signature: &str,
+) -> Result<u32> {
+    verify_client_data(client_data_json, "webauthn.get", challenge)?;
+
+    let raw_auth_data = decode_base64_url(authenticator_data, "authenticatorData")?;
+    let auth_data = parse_authenticator_data(&raw_auth_data)?;
+    verify_authenticator_data(&auth_data)?;
+
+    // The authenticator signs authenticatorData followed by the hash of clientDataJSON
+    let client_data_hash = Sha256::digest(decode_base64_url(client_data_json, "clientDataJSON")?);
+    let mut signed_data = raw_auth_data.clone();
+    signed_data.extend_from_slice(&client_data_hash);
+
+    let verifying_key = VerifyingKey::from_sec1_bytes(&credential.public_key)
+        .wrap_err("stored passkey public key is invalid")?;
+    let signature = Signature::from_der(&decode_base64_url(signature, "signature")?)
+        .wrap_err("signature is not a DER encoded ECDSA signature")?;
+    verifying_key
+        .verify(&signed_data, &signature)
+        .map_err(|_| eyre!("passkey signature is invalid"))?;
+
+    // Authenticators that don't keep a counter always report 0.
+    // Otherwise it has to go up on every use, or the key may have been cloned.
+    if (auth_data.sign_count != 0 || credential.sign_count != 0)
+        && auth_data.sign_count <= credential.sign_count
+    {
+        return Err(eyre!("passkey signature counter did not increase"));
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+fn verify_client_data(client_data_json: &str, expected_type: &str, challenge: &PasskeyChallenge) -> Result<()> {
+    let client_data: ClientData = serde_json::from_slice(&decode_base64_url(client_data_json, "clientDataJSON")?)
+        .wrap_err("clientDataJSON is not valid JSON")?;
+
+    if client_data.ceremony_type != expected_type {
+        return Err(eyre!("unexpected client data type: {}", client_data.ceremony_type));
+    }
+    if client_data.challenge != challenge.as_ref() {
+        return Err(eyre!("client data challenge does not match"));
+    }
+    if client_data.origin != *WEBAUTHN_ORIGIN {
+        return Err(eyre!("unexpected client data origin: {}", client_data.origin));
+    }
+    Ok(())
+}
+
+fn verify_authenticator_data(auth_data: &AuthenticatorData) -> Result<()> {
+    if auth_data.rp_id_hash != Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).as_slice() {
+        return Err(eyre!("authenticator data is for a different relying party"));
+    }
+    // Passkeys replace the password, so the authenticator has to verify the user, not just their presence
+    if auth_data.flags & FLAG_USER_PRESENT == 0 || auth_data.flags & FLAG_USER_VERIFIED == 0 {
+        return Err(eyre!("user was not verified by the authenticator"));
+    }
+    Ok(())
+}
+
+// Layout: rpIdHash (32) | flags (1) | signCount (4) | [aaguid (16) | credIdLen (2) | credId | COSE key]
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData<'_>> {
+    let header_length = RP_ID_HASH_LENGTH + 1 + 4;
+    if data.len() < header_length {
+        return Err(eyre!("authenticator data is too short"));
+    }
+
+    let rp_id_hash = &data[..RP_ID_HASH_LENGTH];
+    let flags = data[RP_ID_HASH_LENGTH];
+    let sign_count = u32::from_be_bytes(data[RP_ID_HASH_LENGTH + 1..header_length].try_into()?);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+        let rest = data.get(header_length + AAGUID_LENGTH..)
+            .wrap_err("attested credential data is too short")?;
+        let id_length = u16::from_be_bytes(
+            rest.get(..2).wrap_err("attested credential data is too short")?.try_into()?
+        ) as usize;
+        let credential_id = rest.get(2..2 + id_length)
+            .wrap_err("attested credential data is too short")?
+            .to_vec();
+        let cose_key: Value = ciborium::de::from_reader(&rest[2 + id_length..])
+            .wrap_err("credential public key is not valid CBOR")?;
+        Some((credential_id, cose_key))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData { rp_id_hash, flags, sign_count, attested_credential })
+}
+
+// Convert a COSE EC2 key into the uncompressed SEC1 encoding we store
+fn parse_cose_key(cose_key: &Value) -> Result<Vec<u8>> {
+    let cose_key = cose_key.as_map().wrap_err("credential public key is not a CBOR map")?;
+
+    let int_value = |label: i64| map_get_int(cose_key, label).and_then(Value::as_integer).map(i128::from);
+    if int_value(COSE_KEY_KTY) != Some(COSE_KTY_EC2.into())
+        || int_value(COSE_KEY_ALG) != Some(COSE_ALG_ES256.into())
+        || int_value(COSE_KEY_CRV) != Some(COSE_CRV_P256.into())
+    {
+        return Err(eyre!("only ES256 passkeys are supported"));
+    }
+
+    let x = map_get_int(cose_key, COSE_KEY_X).and_then(Value::as_bytes).wrap_err("public key is missing x")?;
+    let y = map_get_int(cose_key, COSE_KEY_Y).and_then(Value::as_bytes).wrap_err("public key is missing y")?;
+
+    let mut public_key = vec![0x04];
+    public_key.extend_from_slice(x);
+    public_key.extend_from_slice(y);
+
+    // Make sure the point is actually on the curve before we store it
+    VerifyingKey::from_sec1_bytes(&public_key).wrap_err("public key is not a valid P-256 point")?;
+
+    Ok(public_key)
+}
+
+fn map_get_text<'a>(map: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+    map.iter().find(|(k, _)| k.as_text() == Some(key)).map(|(_, v)| v)
+}
+
+fn map_get_int(map: &[(Value, Value)], key: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(k, _)| k.as_integer().map(i128::from) == Some(key.into()))
+        .map(|(_, v)| v)
+}
+
+fn decode_base64_url(value: &str, field: &str) -> Result<Vec<u8>> {
+    BASE64_URL.decode(value).wrap_err(format!("{} is not valid base64url", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+    use p256::elliptic_curve::rand_core::OsRng;
+    use secrecy::Secret;
+    use crate::domain::Email;
+    use super::*;
+
+    const CREDENTIAL_ID: &[u8] = b"test-credential";
+
+    fn client_data(ceremony_type: &str, challenge: &PasskeyChallenge, origin: &str) -> String {
+        BASE64_URL.encode(serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge.as_ref(),
+            "origin": origin,
+        }).to_string())
+    }
+
+    fn auth_data(flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn attestation_object(key: &SigningKey, flags: u8) -> String {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(COSE_KEY_KTY), Value::from(COSE_KTY_EC2)),
+            (Value::from(COSE_KEY_ALG), Value::from(COSE_ALG_ES256)),
+            (Value::from(COSE_KEY_CRV), Value::from(COSE_CRV_P256)),
+            (Value::from(COSE_KEY_X), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(COSE_KEY_Y), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+
+        let mut data = auth_data(flags | FLAG_ATTESTED_CREDENTIAL_DATA, 0);
+        data.extend_from_slice(&[0; AAGUID_LENGTH]);
+        data.extend_from_slice(&(CREDENTIAL_ID.len() as u16).to_be_bytes());
+        data.extend_from_slice(CREDENTIAL_ID);
+        ciborium::ser::into_writer(&cose_key, &mut data).unwrap();
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation, &mut bytes).unwrap();
+        BASE64_URL.encode(bytes)
+    }
+
+    fn registered_credential(key: &SigningKey, sign_count: u32) -> PasskeyCredential {
+        PasskeyCredential {
+            credential_id: BASE64_URL.encode(CREDENTIAL_ID),
+            email: Email::parse(Secret::new("test@example.com".to_string())).unwrap(),
+            public_key: key.verifying_key().to_encoded_point(false).as_bytes().to_vec(),
+            sign_count,
+        }
+    }
+
+    fn assertion(key: &SigningKey, client_data_json: &str, sign_count: u32) -> (String, String) {
+        let data = auth_data(FLAG_USER_PRESENT | FLAG_USER_VERIFIED, sign_count);
+        let mut signed_data = data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(BASE64_URL.decode(client_data_json).unwrap()));
+        let signature: Signature = key.sign(&signed_data);
+        (BASE64_URL.encode(data), BASE64_URL.encode(signature.to_der().as_bytes()))
+    }
+
+    #[test]
+    fn test_verify_registration() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.create", &challenge, &WEBAUTHN_ORIGIN);
+
+        let credential = verify_registration(
+            &challenge,
+            &client_data_json,
+            &attestation_object(&key, FLAG_USER_PRESENT | FLAG_USER_VERIFIED),
+        ).unwrap();
+
+        assert_eq!(credential.credential_id, BASE64_URL.encode(CREDENTIAL_ID));
+        assert_eq!(credential.public_key, registered_credential(&key, 0).public_key);
+    }
+
+    #[test]
+    fn test_verify_registration_wrong_challenge() {
+        let key = SigningKey::random(&mut OsRng);
+        let client_data_json = client_data("webauthn.create", &PasskeyChallenge::default(), &WEBAUTHN_ORIGIN);
+
+        assert!(verify_registration(
+            &PasskeyChallenge::default(),
+            &client_data_json,
+            &attestation_object(&key, FLAG_USER_PRESENT | FLAG_USER_VERIFIED),
+        ).is_err());
+    }
+
+    #[test]
+    fn test_verify_registration_wrong_origin() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.create", &challenge, "https://evil.example.com");
+
+        assert!(verify_registration(
+            &challenge,
+            &client_data_json,
+            &attestation_object(&key, FLAG_USER_PRESENT | FLAG_USER_VERIFIED),
+        ).is_err());
+    }
+
+    #[test]
+    fn test_verify_registration_without_user_verification() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.create", &challenge, &WEBAUTHN_ORIGIN);
+
+        assert!(verify_registration(
+            &challenge,
+            &client_data_json,
+            &attestation_object(&key, FLAG_USER_PRESENT),
+        ).is_err());
+    }
+
+    #[test]
+    fn test_verify_authentication() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.get", &challenge, &WEBAUTHN_ORIGIN);
+        let (authenticator_data, signature) = assertion(&key, &client_data_json, 5);
+
+        let sign_count = verify_authentication(
+            &challenge,
+            &registered_credential(&key, 4),
+            &client_data_json,
+            &authenticator_data,
+            &signature,
+        ).unwrap();
+
+        assert_eq!(sign_count, 5);
+    }
+
+    #[test]
+    fn test_verify_authentication_wrong_key() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.get", &challenge, &WEBAUTHN_ORIGIN);
+        let (authenticator_data, signature) = assertion(&key, &client_data_json, 1);
+
+        assert!(verify_authentication(
+            &challenge,
+            &registered_credential(&SigningKey::random(&mut OsRng), 0),
+            &client_data_json,
+            &authenticator_data,
+            &signature,
+        ).is_err());
+    }
+
+    #[test]
+    fn test_verify_authentication_registration_client_data() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.create", &challenge, &WEBAUTHN_ORIGIN);
+        let (authenticator_data, signature) = assertion(&key, &client_data_json, 1);
+
+        assert!(verify_authentication(
+            &challenge,
+            &registered_credential(&key, 0),
+            &client_data_json,
+            &authenticator_data,
+            &signature,
+        ).is_err());
+    }
+
+    #[test]
+    fn test_verify_authentication_sign_count_not_increased() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = PasskeyChallenge::default();
+        let client_data_json = client_data("webauthn.get", &challenge, &WEBAUTHN_ORIGIN);
+        let (authenticator_data, signature) = assertion(&key, &client_data_json, 3);
+
+        assert!(verify_authentication(
+            &challenge,
+            &registered_credential(&key, 3),
+            &client_data_json,
+            &authenticator_data,
+            &signature,
+        ).is_err());
+    }
+}
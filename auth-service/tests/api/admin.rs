@@ -0,0 +1,164 @@
+use auth_service::routes::{EmailDeliveriesResponse, RotateSigningKeyResponse};
+use auth_service::utils::constants::{env, ADMIN_API_KEY, JWT_COOKIE_NAME};
+use jsonwebtoken::decode_header;
+use secrecy::ExposeSecret;
+use test_helpers::api_test;
+use crate::helpers::{get_random_email, TestApp};
+
+fn admin_api_key() -> &'static str {
+    ADMIN_API_KEY.as_ref()
+        .expect("ADMIN_API_KEY must be set to run the admin tests")
+        .expose_secret()
+}
+
+fn next_key_id() -> String {
+    std::env::var(env::JWT_NEXT_KEY_ID_ENV_VAR)
+        .expect("JWT_NEXT_KEY_ID and JWT_NEXT_SECRET must be set to run the rotation test")
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_string()
+}
+
+#[api_test]
+async fn rotate_signing_key_returns_400_without_admin_key() {
+    let response = app.post_rotate_signing_key(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn rotate_signing_key_returns_401_with_wrong_admin_key() {
+    let response = app.post_rotate_signing_key(Some("not-the-admin-key")).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn rotate_signing_key_keeps_old_tokens_valid() {
+    let old_token = signup_and_login(&app, &get_random_email()).await;
+
+    let response = app.post_rotate_signing_key(Some(admin_api_key())).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let key_id = response
+        .json::<RotateSigningKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to RotateSigningKeyResponse")
+        .key_id;
+    assert_eq!(key_id, next_key_id());
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = signup_and_login(&app, &get_random_email()).await;
+    assert_eq!(decode_header(&new_token).unwrap().kid, Some(key_id));
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // There's no next key left to promote until the configuration names a new one.
+    let response = app.post_rotate_signing_key(Some(admin_api_key())).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[api_test]
+async fn admin_logout_all_revokes_user_tokens() {
+    let email = &get_random_email();
+    let token = signup_and_login(&app, email).await;
+
+    let response = app.post_admin_logout_all(Some(admin_api_key()), &serde_json::json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn admin_logout_all_requires_admin_key() {
+    let email = &get_random_email();
+    let token = signup_and_login(&app, email).await;
+    let body = serde_json::json!({ "email": email });
+
+    let response = app.post_admin_logout_all(None, &body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_admin_logout_all(Some("not-the-admin-key"), &body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn admin_logout_all_returns_404_for_unknown_user() {
+    let response = app.post_admin_logout_all(
+        Some(admin_api_key()),
+        &serde_json::json!({ "email": get_random_email() }),
+    ).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn email_deliveries_lists_sent_emails() {
+    let email = &get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.get_email_verification_token(email).await;
+
+    let response = app.get_email_deliveries(Some(admin_api_key()), email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let deliveries = response.json::<EmailDeliveriesResponse>()
+        .await
+        .expect("Could not deserialize response body to EmailDeliveriesResponse")
+        .deliveries;
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].subject, "Verify your Live Bootcamp Auth email");
+    assert_eq!(deliveries[0].status, "sent");
+    assert_eq!(deliveries[0].attempts, 0);
+    assert!(deliveries[0].sent_at.is_some());
+    assert!(deliveries[0].next_attempt_at.is_none());
+
+    let response = app.get_email_deliveries(Some(admin_api_key()), &get_random_email()).await;
+    let deliveries = response.json::<EmailDeliveriesResponse>()
+        .await
+        .expect("Could not deserialize response body to EmailDeliveriesResponse")
+        .deliveries;
+    assert!(deliveries.is_empty());
+}
+
+#[api_test]
+async fn email_deliveries_requires_admin_key() {
+    let response = app.get_email_deliveries(None, &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_email_deliveries(Some("not-the-admin-key"), &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn email_deliveries_returns_400_if_invalid_email() {
+    let response = app.get_email_deliveries(Some(admin_api_key()), "example.com").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,181 @@
+use reqwest::Url;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_helpers::api_test]
+async fn change_password_returns_200() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn change_password_revokes_all_existing_tokens() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    // Log in twice to simulate two devices.
+    let mut tokens = Vec::new();
+    for _ in 0..2 {
+        let response = app.post_login(&serde_json::json!({
+            "email": email,
+            "password": "password",
+        })).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let cookie = response.cookies()
+            .find(|c| c.name() == JWT_COOKIE_NAME)
+            .expect("No token found");
+        tokens.push(cookie.value().to_owned());
+    }
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in tokens {
+        let response = app.post_verify_token(&serde_json::json!({
+            "token": token,
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[test_helpers::api_test]
+async fn token_from_logging_in_right_after_change_works() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Most likely within the same second as the change revoked the user's tokens.
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response.cookies()
+        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .expect("No token found")
+        .value()
+        .to_owned();
+
+    let response = app.post_verify_token(&serde_json::json!({
+        "token": token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_incorrect_current_password() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "wrong_password",
+        "newPassword": "new_password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_invalid_token() {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_422_if_malformed_input() {
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
@@ -0,0 +1,49 @@
+use auth_service::routes::OutboxResponse;
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_helpers::api_test]
+async fn outbox_lists_emails_sent_to_recipient() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let other_email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": other_email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Emails go out in the background.
+    let token = app.get_email_verification_token(&email).await;
+    app.get_email_verification_token(&other_email).await;
+
+    let response = app.get_dev_outbox(Some(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let outbox = response.json::<OutboxResponse>()
+        .await
+        .expect("Could not deserialize response body to OutboxResponse");
+    assert_eq!(outbox.emails.len(), 1);
+    assert_eq!(outbox.emails[0].recipient, email);
+    assert_eq!(outbox.emails[0].subject, "Verify your Live Bootcamp Auth email");
+    assert!(outbox.emails[0].text.contains(&token));
+    assert!(outbox.emails[0].html.contains(&token));
+
+    let response = app.get_dev_outbox(None).await;
+    let outbox = response.json::<OutboxResponse>()
+        .await
+        .expect("Could not deserialize response body to OutboxResponse");
+    assert_eq!(outbox.emails.len(), 2);
+}
+
+#[test_helpers::api_test]
+async fn outbox_returns_400_if_invalid_email() {
+    let response = app.get_dev_outbox(Some("example.com")).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
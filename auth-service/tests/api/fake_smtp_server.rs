@@ -0,0 +1,124 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone)]
+pub struct ReceivedEmail {
+    pub mail_from: String,
+    pub rcpt_to: Vec<String>,
+    /// Headers and body, as sent after `DATA`.
+    pub data: String,
+}
+
+/// Plain text SMTP server that accepts every email, so the SMTP client can be tested without an outside service.
+pub struct FakeSmtpServer {
+    pub address: SocketAddr,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl FakeSmtpServer {
+    pub async fn start() -> Self {
+        Self::spawn(true).await
+    }
+
+    /// Accepts connections but never greets the client.
+    pub async fn start_unresponsive() -> Self {
+        Self::spawn(false).await
+    }
+
+    async fn spawn(responsive: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fake SMTP server");
+        let address = listener.local_addr().expect("Failed to get fake SMTP server address");
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let server_received = received.clone();
+        let server_connections = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                server_connections.fetch_add(1, Ordering::SeqCst);
+                let received = server_received.clone();
+                tokio::spawn(async move {
+                    if responsive {
+                        let _ = handle_connection(stream, received).await;
+                    } else {
+                        // Hold the connection open without answering.
+                        let _stream = stream;
+                        std::future::pending::<()>().await;
+                    }
+                });
+            }
+        });
+
+        Self { address, received, connections }
+    }
+
+    pub fn received(&self) -> Vec<ReceivedEmail> {
+        self.received.lock().unwrap().clone()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+async fn handle_connection(stream: TcpStream, received: Arc<Mutex<Vec<ReceivedEmail>>>) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer.write_all(b"220 localhost fake SMTP\r\n").await?;
+
+    let mut mail_from = String::new();
+    let mut rcpt_to = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        let command = line.to_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+            b"250 localhost\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            mail_from = address_of(&line);
+            rcpt_to.clear();
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            rcpt_to.push(address_of(&line));
+            b"250 OK\r\n"
+        } else if command == "DATA" {
+            writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await?;
+            let mut data = Vec::new();
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                data.push(line.strip_prefix('.').map(str::to_string).unwrap_or(line));
+            }
+            received.lock().unwrap().push(ReceivedEmail {
+                mail_from: mail_from.clone(),
+                rcpt_to: rcpt_to.clone(),
+                data: data.join("\r\n"),
+            });
+            b"250 OK\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else if command == "RSET" || command == "NOOP" {
+            b"250 OK\r\n"
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+        writer.write_all(reply).await?;
+    }
+
+    Ok(())
+}
+
+// The address between the angle brackets of `MAIL FROM:<...>` or `RCPT TO:<...>`.
+fn address_of(line: &str) -> String {
+    line.split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_string())
+        .unwrap_or_default()
+}
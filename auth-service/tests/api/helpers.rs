@@ -1,14 +1,17 @@
 use std::str::FromStr;
 use std::sync::Arc;
 use reqwest::cookie::Jar;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use redis::aio::ConnectionManager;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use auth_service::app_state::AppState;
-use auth_service::{Application, get_postgres_pool, get_redis_client};
-use auth_service::services::{HashmapTwoFACodeStore, HashSetBannedTokenStore, MockEmailClient, PostgresUserStore, RedisBannedTokenStore};
-use auth_service::utils::constants::{DATABASE_URL, REDIS_HOST_NAME};
+use auth_service::domain::{DeliveryStatus, Email, EmailOutboxStore, SentEmail};
+use auth_service::{Application, get_postgres_pool, get_redis_connection};
+use auth_service::services::{HashmapLoginAttemptStore, HashmapTwoFACodeStore, PostgresEmailOutboxStore, PostgresEmailVerificationTokenStore, PostgresKnownDeviceStore, PostgresPasskeyStore, PostgresPasswordResetTokenStore, PostgresRecoveryCodeStore, PostgresRefreshTokenStore, PostgresSessionStore, PostgresUserStore, RecordingEmailClient, RedisBannedTokenStore};
+use auth_service::utils::constants::{DATABASE_URL, EMAIL_BRANDING, REDIS_HOST_NAME};
 use auth_service::utils::constants::test;
 
 pub struct TestApp {
@@ -16,6 +19,9 @@ pub struct TestApp {
     pub cookie_jar: Arc<Jar>,
     pub http_client: reqwest::Client,
     pub db_name: String,
+    pub pg_pool: PgPool,
+    /// Shares its emails with the app's email client.
+    pub email_client: RecordingEmailClient,
     pub clean_up_called: bool,
 }
 
@@ -23,6 +29,16 @@ pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
+/// The code or token in an email, which sits alone on its own line of the text.
+pub fn emailed_code(email: &SentEmail) -> String {
+    email.message.text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.contains(' '))
+        .unwrap_or_else(|| panic!("No code in \"{}\" email", email.message.subject))
+        .to_string()
+}
+
 pub fn get_malformed_email() -> String {
     "example.com".to_owned()
 }
@@ -31,19 +47,29 @@ impl TestApp {
     pub async fn new() -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(db_name.clone()).await;
+        let email_client = RecordingEmailClient::default();
 
         let app_state = AppState::new(
-            Arc::new(RwLock::new(PostgresUserStore::new(pg_pool))),
-            Arc::new(RwLock::new(RedisBannedTokenStore::new(Arc::new(RwLock::new(configure_redis()))))),
+            Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(RedisBannedTokenStore::new(configure_redis().await, pg_pool.clone()))),
             Arc::new(RwLock::new(HashmapTwoFACodeStore::default())),
-            Arc::new(RwLock::new(MockEmailClient::default())),
+            Arc::new(RwLock::new(email_client.clone())),
+            Arc::new(RwLock::new(PostgresPasswordResetTokenStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresEmailVerificationTokenStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresRefreshTokenStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(HashmapLoginAttemptStore::default())),
+            Arc::new(RwLock::new(PostgresEmailOutboxStore::new(pg_pool.clone()))),
+            Arc::new(RwLock::new(PostgresKnownDeviceStore::new(pg_pool.clone()))),
         );
 
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
 
-        let address = format!("http://{}", app.address().to_string());
+        let address = format!("http://{}", app.address());
 
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(async { app.run().await });
@@ -59,13 +85,66 @@ impl TestApp {
             cookie_jar,
             http_client,
             db_name,
+            pg_pool,
+            email_client,
             clean_up_called: false,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_rotate_signing_key(&self, admin_api_key: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client
+            .post(format!("{}/admin/rotate-signing-key", &self.address));
+        if let Some(admin_api_key) = admin_api_key {
+            request = request.bearer_auth(admin_api_key);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn get_email_deliveries(&self, admin_api_key: Option<&str>, email: &str) -> reqwest::Response {
+        let mut request = self.http_client
+            .get(format!("{}/admin/email-deliveries", &self.address))
+            .query(&[("email", email)]);
+        if let Some(admin_api_key) = admin_api_key {
+            request = request.bearer_auth(admin_api_key);
+        }
+
+        request
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_admin_logout_all<T>(&self, admin_api_key: Option<&str>, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        let mut request = self.http_client
+            .post(format!("{}/admin/logout-all", &self.address))
+            .json(body);
+        if let Some(admin_api_key) = admin_api_key {
+            request = request.bearer_auth(admin_api_key);
+        }
+
+        request
             .send()
             .await
             .expect("Failed to send request")
@@ -75,7 +154,7 @@ impl TestApp {
     where T: serde::Serialize + ?Sized
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .header("content-type", "application/json")
             .json(&body)
             .send()
@@ -88,7 +167,7 @@ impl TestApp {
         T: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -99,7 +178,7 @@ impl TestApp {
     where T: serde::Serialize + ?Sized
     {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .header("content-type", "application/json")
             .json(&body)
             .send()
@@ -107,11 +186,82 @@ impl TestApp {
             .expect("Failed to send request")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_unlock_account<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/unlock-account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_report_login<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/report-login", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// POSTs to `path` as if from another browser, for requests that record the device they came from.
+    pub async fn post_with_user_agent<Body>(&self, path: &str, user_agent: &str, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}{}", &self.address, path))
+            .header("user-agent", user_agent)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where Body: serde::Serialize + ?Sized
     {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where Body: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
             .header("content-type", "application/json")
             .json(&body)
             .send()
@@ -131,11 +281,87 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_refresh_token<T>(&self, body: &T) -> reqwest::Response
+    pub async fn post_refresh_token(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh-token", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_password_reset_request<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_password_reset_confirm<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_change_password<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_verify_email<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_resend_verification<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/resend-verification", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/enroll-totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_confirm_totp<T>(&self, body: &T) -> reqwest::Response
     where T: serde::Serialize + ?Sized
     {
         self.http_client
-            .post(&format!("{}/refresh-token", &self.address))
+            .post(format!("{}/confirm-totp", &self.address))
             .header("content-type", "application/json")
             .json(&body)
             .send()
@@ -143,6 +369,146 @@ impl TestApp {
             .expect("Failed to send request")
     }
 
+    pub async fn post_regenerate_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/regenerate-recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_passkey_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkey/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_passkey_register_finish<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/passkey/register/finish", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_passkey_login_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkey/login/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_passkey_login_finish<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/passkey/login/finish", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_magic_link<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_consume_magic_link<T>(&self, body: &T) -> reqwest::Response
+    where T: serde::Serialize + ?Sized
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/consume", &self.address))
+            .header("content-type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    #[cfg(feature = "dev-outbox")]
+    pub async fn get_dev_outbox(&self, email: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/dev/outbox", &self.address));
+        if let Some(email) = email {
+            request = request.query(&[("email", email)]);
+        }
+        request
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// The most recent email the app sent to `email` with `subject`.
+    pub async fn get_sent_email(&self, email: &str, subject: &str) -> SentEmail {
+        self.find_sent_email(email, subject)
+            .await
+            .unwrap_or_else(|| panic!("No \"{}\" email sent to {}", subject, email))
+    }
+
+    /// Like [Self::get_sent_email], for checking an email wasn't sent.
+    pub async fn find_sent_email(&self, email: &str, subject: &str) -> Option<SentEmail> {
+        let recipient = Email::parse(Secret::new(email.to_string()))
+            .expect("Failed to parse email");
+        self.wait_for_email_delivery(&recipient).await;
+        self.email_client
+            .last_email_to(&recipient, subject)
+            .await
+    }
+
+    /// Waits for the outbox worker to get through every email queued for `recipient`.
+    pub async fn wait_for_email_delivery(&self, recipient: &Email) {
+        let outbox = PostgresEmailOutboxStore::new(self.pg_pool.clone());
+        for _ in 0..100 {
+            let emails = outbox.get_emails(recipient)
+                .await
+                .expect("Failed to get queued emails");
+            if emails.iter().all(|email| email.status != DeliveryStatus::Pending) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("Emails to {} were never delivered", recipient.as_ref().expose_secret());
+    }
+
+    pub async fn get_email_verification_token(&self, email: &str) -> String {
+        let subject = format!("Verify your {} email", EMAIL_BRANDING.name);
+        emailed_code(&self.get_sent_email(email, &subject).await)
+    }
+
+    pub async fn get_two_fa_code(&self, email: &str) -> String {
+        let subject = format!("Your {} login code", EMAIL_BRANDING.name);
+        emailed_code(&self.get_sent_email(email, &subject).await)
+    }
+
+    pub async fn get_password_reset_token(&self, email: &str) -> String {
+        let subject = format!("Reset your {} password", EMAIL_BRANDING.name);
+        emailed_code(&self.get_sent_email(email, &subject).await)
+    }
+
+    /// Completes email verification for a freshly signed up user so they can log in.
+    pub async fn verify_email(&self, email: &str) {
+        let token = self.get_email_verification_token(email).await;
+        let response = self.post_verify_email(&serde_json::json!({
+            "token": token,
+        })).await;
+        assert_eq!(response.status().as_u16(), 200, "Failed to verify email");
+    }
+
     pub async fn clean_up(&mut self) {
         if self.clean_up_called {
             return;
@@ -162,7 +528,7 @@ impl Drop for TestApp {
     }
 }
 
-async fn configure_postgresql(db_name: String) -> PgPool {
+pub async fn configure_postgresql(db_name: String) -> PgPool {
     let postgresql_conn_url = DATABASE_URL.to_owned();
 
     configure_database(&postgresql_conn_url, &db_name).await;
@@ -204,7 +570,7 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
         .expect("Failed to migrate the database");
 }
 
-async fn delete_database(db_name: &str) {
+pub async fn delete_database(db_name: &str) {
     let postgresql_conn_url = DATABASE_URL.to_owned();
     println!("Dropping database: {}", db_name);
 
@@ -239,9 +605,8 @@ async fn delete_database(db_name: &str) {
         .expect("Failed to drop the database.");
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
+pub async fn configure_redis() -> ConnectionManager {
+    get_redis_connection(REDIS_HOST_NAME.to_owned())
+        .await
         .expect("Failed to get Redis connection")
 }
\ No newline at end of file
@@ -0,0 +1,15 @@
+use jsonwebtoken::jwk::JwkSet;
+use crate::helpers::TestApp;
+
+#[test_helpers::api_test]
+async fn jwks_returns_key_set() {
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The tests sign with the shared HS256 secret, which must never be published.
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+    assert!(jwks.keys.is_empty());
+}
@@ -1,10 +1,43 @@
+use std::time::Duration;
+use reqwest::Url;
+use secrecy::Secret;
+use auth_service::domain::Email;
+use auth_service::http_response::ErrorResponse;
 use auth_service::routes::TwoFactorAuthResponse;
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::auth::generate_unlock_token;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, LOGIN_FREE_FAILURES_BY_EMAIL, LOGIN_THROTTLE_BY_EMAIL, TWO_FA_PENDING_COOKIE_NAME};
 use crate::helpers::{
     TestApp,
     get_random_email,
 };
 
+async fn signup(app: &TestApp, email: &str) {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": password,
+    })).await
+}
+
+fn retry_after(response: &reqwest::Response) -> i64 {
+    response.headers()
+        .get("retry-after")
+        .expect("No Retry-After header")
+        .to_str()
+        .expect("Retry-After is not a string")
+        .parse()
+        .expect("Retry-After is not a number of seconds")
+}
+
 
 #[test_helpers::api_test]
 async fn login_returns_200() {
@@ -15,6 +48,7 @@ async fn login_returns_200() {
         "requires2FA": false
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
 
     let response = app.post_login(&serde_json::json!({
         "email": email,
@@ -38,6 +72,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     let response = app.post_signup(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&random_email).await;
 
     let login_body = serde_json::json!({
         "email": random_email,
@@ -68,6 +103,7 @@ async fn login_returns_422_on_malformed_credentials() {
         "requires2FA": false
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
 
     let test_cases = [
         serde_json::json!({
@@ -99,6 +135,7 @@ async fn login_returns_401_on_invalid_credentials() {
         "requires2FA": false
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
 
     let test_cases = [
         serde_json::json!({
@@ -132,6 +169,7 @@ async fn login_returns_401_on_non_existent_user() {
         "requires2FA": false
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
 
     let response = app.post_login(&serde_json::json!({
         "email": get_random_email(),
@@ -156,6 +194,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let response = app.post_signup(&signup_body).await;
 
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&random_email).await;
 
     let login_body = serde_json::json!({
         "email": random_email,
@@ -175,4 +214,181 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
     
-}
\ No newline at end of file
+}
+
+#[test_helpers::api_test]
+async fn should_return_403_if_email_not_verified() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email not verified".to_owned()
+    );
+}
+
+#[test_helpers::api_test]
+async fn should_return_429_while_backing_off_after_failed_logins() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+
+    // The first failures are free, then the next attempt has to wait a second.
+    for _ in 0..3 {
+        let response = login(&app, email, "wrong_password").await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(retry_after(&response), 1);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn successful_login_resets_failed_logins() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+
+    for _ in 0..3 {
+        login(&app, email, "wrong_password").await;
+    }
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for _ in 0..3 {
+        let response = login(&app, email, "wrong_password").await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[test_helpers::api_test]
+async fn should_lock_account_after_too_many_failed_logins_until_unlocked() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+
+    for _ in 0..LOGIN_THROTTLE_BY_EMAIL.lockout_failures {
+        let mut response = login(&app, email, "wrong_password").await;
+        if response.status().as_u16() == 429 {
+            tokio::time::sleep(Duration::from_secs(retry_after(&response) as u64)).await;
+            response = login(&app, email, "wrong_password").await;
+        }
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(retry_after(&response) > LOGIN_THROTTLE_BY_EMAIL.lockout_seconds - 10);
+
+    let token = generate_unlock_token(&Email::parse(Secret::new(email.to_string())).unwrap()).unwrap();
+    let response = app.post_unlock_account(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_unlock_account(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn concurrent_failed_logins_cannot_skip_the_backoff() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+
+    let mut attempts = tokio::task::JoinSet::new();
+    for _ in 0..10 {
+        let http_client = app.http_client.clone();
+        let url = format!("{}/login", &app.address);
+        let body = serde_json::json!({ "email": email, "password": "wrong_password" });
+        attempts.spawn(async move {
+            http_client.post(url).json(&body).send().await
+                .expect("Failed to execute request.")
+                .status()
+                .as_u16()
+        });
+    }
+    let mut statuses = Vec::new();
+    while let Some(status) = attempts.join_next().await {
+        statuses.push(status.unwrap());
+    }
+
+    // Only the free failures get their password checked, the rest have to back off after them.
+    let checked = statuses.iter().filter(|&&status| status == 401).count();
+    assert_eq!(checked, LOGIN_FREE_FAILURES_BY_EMAIL as usize + 1);
+    assert!(statuses.iter().all(|&status| status == 401 || status == 429));
+}
+
+#[test_helpers::api_test]
+async fn should_return_429_after_many_failed_logins_from_one_ip() {
+    // Each account only fails once, so only the IP's count can slow these down.
+    let mut throttled = false;
+    for _ in 0..20 {
+        let response = login(&app, &get_random_email(), "password").await;
+        match response.status().as_u16() {
+            401 => {},
+            429 => {
+                throttled = true;
+                break;
+            },
+            status => panic!("Unexpected status {}", status),
+        }
+    }
+
+    assert!(throttled);
+}
+
+#[test_helpers::api_test]
+async fn half_logged_in_user_cannot_reach_protected_routes() {
+    let email = &get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+
+    let response = login(&app, email, "password").await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(!response.cookies().any(|c| c.name() == JWT_COOKIE_NAME));
+    let pending_token = response.cookies()
+        .find(|c| c.name() == TWO_FA_PENDING_COOKIE_NAME)
+        .expect("No 2FA pending cookie found")
+        .value()
+        .to_string();
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": pending_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Presenting the pending token as an auth token doesn't work either.
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", JWT_COOKIE_NAME, pending_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
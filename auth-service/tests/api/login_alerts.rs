@@ -0,0 +1,135 @@
+use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::utils::constants::{EMAIL_BRANDING, JWT_COOKIE_NAME, REPORT_LOGIN_URL};
+use crate::helpers::{get_random_email, TestApp};
+
+const FIREFOX_ON_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+fn alert_subject() -> String {
+    format!("New login to your {} account", EMAIL_BRANDING.name)
+}
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": requires_2fa
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+}
+
+/// Logs in without 2FA and returns the auth token.
+async fn login(app: &TestApp, email: &str, user_agent: Option<&str>) -> String {
+    let body = serde_json::json!({
+        "email": email,
+        "password": "password",
+    });
+    let response = match user_agent {
+        Some(user_agent) => app.post_with_user_agent("/login", user_agent, &body).await,
+        None => app.post_login(&body).await,
+    };
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    auth_cookie.value().to_string()
+}
+
+/// The token of the "this wasn't me" link in the last new login alert sent to `email`.
+async fn report_token(app: &TestApp, email: &str) -> String {
+    let alert = app.get_sent_email(email, &alert_subject()).await;
+    let prefix = format!("{}?token=", REPORT_LOGIN_URL.as_str());
+
+    alert.message.text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(&prefix))
+        .expect("No report link in alert")
+        .to_string()
+}
+
+#[test_helpers::api_test]
+async fn should_not_alert_on_first_or_known_device() {
+    let email = &get_random_email();
+    signup(&app, email, false).await;
+
+    login(&app, email, None).await;
+    login(&app, email, None).await;
+
+    assert!(app.find_sent_email(email, &alert_subject()).await.is_none());
+}
+
+#[test_helpers::api_test]
+async fn should_alert_on_login_from_new_device() {
+    let email = &get_random_email();
+    signup(&app, email, false).await;
+    login(&app, email, None).await;
+
+    login(&app, email, Some(FIREFOX_ON_WINDOWS)).await;
+
+    let alert = app.get_sent_email(email, &alert_subject()).await;
+    assert!(alert.message.text.contains("Device: Firefox on Windows"));
+    assert!(alert.message.text.contains("IP address: 127.0.0.1"));
+    assert!(alert.message.text.contains(REPORT_LOGIN_URL.as_str()));
+}
+
+#[test_helpers::api_test]
+async fn should_alert_after_2fa_from_new_device() {
+    let email = &get_random_email();
+    signup(&app, email, true).await;
+
+    for user_agent in [None, Some(FIREFOX_ON_WINDOWS)] {
+        let body = serde_json::json!({
+            "email": email,
+            "password": "password",
+        });
+        let response = app.post_login(&body).await;
+        assert_eq!(response.status().as_u16(), 206);
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let body = serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": app.get_two_fa_code(email).await,
+        });
+        let response = match user_agent {
+            Some(user_agent) => app.post_with_user_agent("/verify-2fa", user_agent, &body).await,
+            None => app.post_verify_2fa(&body).await,
+        };
+        assert_eq!(response.status().as_u16(), 200);
+
+        let alert = app.find_sent_email(email, &alert_subject()).await;
+        assert_eq!(alert.is_some(), user_agent.is_some());
+    }
+}
+
+#[test_helpers::api_test]
+async fn report_link_logs_out_every_session_once() {
+    let email = &get_random_email();
+    signup(&app, email, false).await;
+    let first_token = login(&app, email, None).await;
+    let second_token = login(&app, email, Some(FIREFOX_ON_WINDOWS)).await;
+    let token = report_token(&app, email).await;
+
+    let response = app.post_report_login(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for auth_token in [first_token, second_token] {
+        let response = app.post_verify_token(&serde_json::json!({ "token": auth_token })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_report_login(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_invalid_report_token() {
+    let response = app.post_report_login(&serde_json::json!({ "token": "invalid" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -1,6 +1,36 @@
+use redis::AsyncCommands;
 use reqwest::Url;
-use auth_service::utils::constants::JWT_COOKIE_NAME;
-use crate::helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::helpers::{configure_redis, get_random_email, TestApp};
+
+// Logs in and returns the access and refresh tokens.
+async fn signup_and_login(app: &TestApp, email: &str) -> (String, String) {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+
+    login(app, email).await
+}
+
+async fn login(app: &TestApp, email: &str) -> (String, String) {
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie_value = |name: &str| response.cookies()
+        .find(|c| c.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_string();
+
+    (cookie_value(JWT_COOKIE_NAME), cookie_value(REFRESH_COOKIE_NAME))
+}
 
 #[test_helpers::api_test]
 async fn logout_returns_200() {
@@ -10,6 +40,7 @@ async fn logout_returns_200() {
         "password": "password",
         "requires2FA": false
     })).await;
+    app.verify_email(email).await;
 
     let _ = app.post_login(&serde_json::json!({
         "email": email,
@@ -48,4 +79,52 @@ async fn should_return_401_if_invalid_token() {
     assert_eq!(response.status().as_u16(), 401);
     
 
-}
\ No newline at end of file
+}
+
+#[test_helpers::api_test]
+async fn logout_all_revokes_every_session() {
+    let email = &get_random_email();
+    let (first_token, first_refresh_token) = signup_and_login(&app, email).await;
+    let (second_token, _) = login(&app, email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|c| c.name() == JWT_COOKIE_NAME && c.value().is_empty()));
+
+    for token in [first_token, second_token] {
+        let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, first_refresh_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn logout_all_survives_redis_losing_the_revocation() {
+    let email = &get_random_email();
+    let (token, _) = signup_and_login(&app, email).await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let _: () = configure_redis()
+        .await
+        .del(format!("tokens_revoked_at_ms:{}", email))
+        .await
+        .expect("Failed to delete cached revocation");
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn logout_all_returns_400_if_jwt_cookie_missing() {
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,146 @@
+use secrecy::Secret;
+use auth_service::domain::Email;
+use auth_service::http_response::AuthMessageResponse;
+use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::utils::auth::generate_magic_link_token;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": requires_2fa
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// `MockEmailClient` doesn't keep the link, so tests sign their own token.
+fn magic_link_token(email: &str) -> String {
+    let email = Email::parse(Secret::new(email.to_string())).unwrap();
+    generate_magic_link_token(&email).unwrap()
+}
+
+#[test_helpers::api_test]
+async fn should_return_200_whether_or_not_account_exists() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app.post_magic_link(&serde_json::json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let known = response.json::<AuthMessageResponse>().await.unwrap();
+
+    let response = app.post_magic_link(&serde_json::json!({ "email": get_random_email() })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let unknown = response.json::<AuthMessageResponse>().await.unwrap();
+
+    assert_eq!(known, unknown);
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_email_is_malformed() {
+    let response = app.post_magic_link(&serde_json::json!({ "email": "example.com" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_set_auth_cookie_when_link_is_consumed() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let response = app.post_consume_magic_link(&serde_json::json!({ "token": magic_link_token(&email) })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_link_is_used_twice() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+    let body = serde_json::json!({ "token": magic_link_token(&email) });
+
+    let response = app.post_consume_magic_link(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_consume_magic_link(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_accept_link_only_once_when_used_concurrently() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+    let body = serde_json::json!({ "token": magic_link_token(&email) });
+
+    let (first, second) = tokio::join!(
+        app.post_consume_magic_link(&body),
+        app.post_consume_magic_link(&body),
+    );
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, [200, 401]);
+}
+
+#[test_helpers::api_test]
+async fn should_require_2fa_after_consuming_link() {
+    let email = get_random_email();
+    signup(&app, &email, true).await;
+    app.verify_email(&email).await;
+
+    let response = app.post_consume_magic_link(&serde_json::json!({ "token": magic_link_token(&email) })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(body.message, "2FA required");
+    assert!(!body.login_attempt_id.is_empty());
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_auth_token_is_used_as_link() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_string();
+
+    let response = app.post_consume_magic_link(&serde_json::json!({ "token": auth_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_token_is_invalid() {
+    let response = app.post_consume_magic_link(&serde_json::json!({ "token": "invalid" })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_403_if_email_not_verified() {
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app.post_consume_magic_link(&serde_json::json!({ "token": magic_link_token(&email) })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -5,4 +5,19 @@ mod root;
 mod signup;
 mod verify_2fa;
 mod verify_token;
-mod refresh_token;
\ No newline at end of file
+mod refresh_token;
+mod password_reset;
+mod change_password;
+mod verify_email;mod totp;
+mod recovery_codes;
+mod passkey;
+mod magic_link;
+mod jwks;
+mod admin;
+mod sessions;
+mod login_alerts;
+mod fake_smtp_server;
+mod smtp_email_client;
+mod startup;
+#[cfg(feature = "dev-outbox")]
+mod dev_outbox;
\ No newline at end of file
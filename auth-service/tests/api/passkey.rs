@@ -0,0 +1,285 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use ciborium::Value;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use auth_service::routes::{PasskeyLoginOptions, PasskeyRegistrationOptions};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, WEBAUTHN_ORIGIN};
+use crate::helpers::{get_random_email, TestApp};
+
+// User present and user verified
+const FLAGS: u8 = 0x05;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+/// Stands in for a platform authenticator holding a single ES256 credential.
+struct SoftwareAuthenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        Self {
+            key: SigningKey::random(&mut OsRng),
+            credential_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            sign_count: 0,
+        }
+    }
+
+    fn credential_id(&self) -> String {
+        BASE64_URL.encode(&self.credential_id)
+    }
+
+    fn client_data(ceremony_type: &str, challenge: &str) -> String {
+        BASE64_URL.encode(serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": WEBAUTHN_ORIGIN.as_str(),
+        }).to_string())
+    }
+
+    fn auth_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn register(&self, options: &PasskeyRegistrationOptions) -> serde_json::Value {
+        let options = &options.public_key;
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+
+        let mut auth_data = Self::auth_data(&options.rp.id, FLAGS | FLAG_ATTESTED_CREDENTIAL_DATA, 0);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        ciborium::ser::into_writer(&cose_key, &mut auth_data).unwrap();
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+        serde_json::json!({
+            "id": self.credential_id(),
+            "response": {
+                "clientDataJSON": Self::client_data("webauthn.create", &options.challenge),
+                "attestationObject": BASE64_URL.encode(attestation_object),
+            }
+        })
+    }
+
+    fn sign(&mut self, email: &str, options: &PasskeyLoginOptions) -> serde_json::Value {
+        self.sign_count += 1;
+
+        let client_data_json = Self::client_data("webauthn.get", &options.public_key.challenge);
+        let auth_data = Self::auth_data(&options.public_key.rp_id, FLAGS, self.sign_count);
+        let mut signed_data = auth_data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(BASE64_URL.decode(&client_data_json).unwrap()));
+        let signature: Signature = self.key.sign(&signed_data);
+
+        serde_json::json!({
+            "email": email,
+            "challengeId": options.challenge_id,
+            "credential": {
+                "id": self.credential_id(),
+                "response": {
+                    "clientDataJSON": client_data_json,
+                    "authenticatorData": BASE64_URL.encode(auth_data),
+                    "signature": BASE64_URL.encode(signature.to_der().as_bytes()),
+                }
+            }
+        })
+    }
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let _ = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    app.verify_email(email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &SoftwareAuthenticator) {
+    let response = app.post_passkey_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response
+        .json::<PasskeyRegistrationOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRegistrationOptions");
+
+    let response = app.post_passkey_register_finish(&authenticator.register(&options)).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn start_login(app: &TestApp) -> PasskeyLoginOptions {
+    let response = app.post_passkey_login_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeyLoginOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyLoginOptions")
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_registering_without_jwt_cookie() {
+    let response = app.post_passkey_register_start().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_login_with_registered_passkey() {
+    let email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_logout("").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = start_login(&app).await;
+
+    let response = app.post_passkey_login_finish(&authenticator.sign(&email, &options)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[test_helpers::api_test]
+async fn should_exclude_registered_credentials_when_registering() {
+    let email = get_random_email();
+    let authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = app.post_passkey_register_start().await
+        .json::<PasskeyRegistrationOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRegistrationOptions");
+    assert_eq!(options.public_key.exclude_credentials.len(), 1);
+
+    // Registering the same credential again is rejected.
+    let response = app.post_passkey_register_finish(&authenticator.register(&options)).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_signed_with_wrong_key() {
+    let email = get_random_email();
+    let authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    // Same credential ID, different private key.
+    let mut impostor = SoftwareAuthenticator {
+        key: SigningKey::random(&mut OsRng),
+        credential_id: authenticator.credential_id.clone(),
+        sign_count: 0,
+    };
+    let options = start_login(&app).await;
+
+    let response = app.post_passkey_login_finish(&impostor.sign(&email, &options)).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_assertion_is_replayed() {
+    let email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app).await;
+    let assertion = authenticator.sign(&email, &options);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_login_was_not_started() {
+    let email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app).await;
+    let assertion = authenticator.sign(&get_random_email(), &options);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_challenge_id_is_invalid() {
+    let email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app).await;
+    let mut assertion = authenticator.sign(&email, &options);
+    assertion["challengeId"] = serde_json::json!("not-a-challenge-id");
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_not_list_credentials_when_starting_login() {
+    let email = get_random_email();
+    let authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app).await;
+
+    assert!(options.public_key.allow_credentials.is_empty());
+}
+
+#[test_helpers::api_test]
+async fn should_login_when_another_login_was_started_meanwhile() {
+    let email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::new();
+    signup_and_login(&app, &email).await;
+    register_passkey(&app, &authenticator).await;
+    let response = app.post_logout("").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = start_login(&app).await;
+    let other_options = start_login(&app).await;
+    assert_ne!(options.challenge_id, other_options.challenge_id);
+
+    let response = app.post_passkey_login_finish(&authenticator.sign(&email, &options)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
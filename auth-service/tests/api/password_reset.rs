@@ -0,0 +1,183 @@
+use auth_service::domain::PasswordResetToken;
+use auth_service::http_response::ErrorResponse;
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_helpers::api_test]
+async fn request_returns_200_for_existing_user() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": email,
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn request_returns_200_for_unknown_user() {
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": get_random_email(),
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn request_returns_200_without_sending_during_cooldown() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": email,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = app.get_password_reset_token(&email).await;
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": email,
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.get_password_reset_token(&email).await, token);
+}
+
+#[test_helpers::api_test]
+async fn request_returns_400_if_invalid_email() {
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": "example.com",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn confirm_returns_401_if_unknown_token() {
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "token": PasswordResetToken::default().as_ref(),
+        "newPassword": "new_password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid token".to_owned()
+    );
+}
+
+#[test_helpers::api_test]
+async fn confirm_returns_400_if_invalid_input() {
+    let test_cases = [
+        serde_json::json!({
+            "token": "invalid_token",
+            "newPassword": "new_password",
+        }),
+        serde_json::json!({
+            "token": PasswordResetToken::default().as_ref(),
+            "newPassword": "short",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_helpers::api_test]
+async fn confirm_returns_422_if_malformed_input() {
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "token": PasswordResetToken::default().as_ref(),
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[test_helpers::api_test]
+async fn emailed_token_resets_password() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": email,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app.get_password_reset_token(&email).await;
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "token": token,
+        "newPassword": "newpassword",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "newpassword",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token only works once.
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "token": token,
+        "newPassword": "anotherpassword",
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn email_follows_accept_language_unless_user_chose_a_language() {
+    let spanish_reader = get_random_email();
+    let english_reader = get_random_email();
+    for (email, language) in [(&spanish_reader, None), (&english_reader, Some("en"))] {
+        let response = app.post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password",
+            "requires2FA": false,
+            "preferredLanguage": language
+        })).await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = app.http_client
+            .post(format!("{}/password-reset/request", &app.address))
+            .header("accept-language", "es-ES,es;q=0.9,en;q=0.5")
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.get_sent_email(&spanish_reader, "Restablece tu contraseña de Live Bootcamp Auth").await;
+    app.get_password_reset_token(&english_reader).await;
+}
@@ -0,0 +1,136 @@
+use auth_service::http_response::AuthMessageResponse;
+use auth_service::routes::{RecoveryCodesResponse, TwoFactorAuthResponse};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+
+    response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes
+}
+
+async fn login_with_2fa(app: &TestApp, email: &str) -> String {
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+async fn verify_with_recovery_code(app: &TestApp, email: &str, recovery_code: &str) -> u16 {
+    let login_attempt_id = login_with_2fa(app, email).await;
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_code,
+    })).await.status().as_u16()
+}
+
+#[test_helpers::api_test]
+async fn signup_with_2fa_returns_recovery_codes() {
+    let email = get_random_email();
+    let codes = signup_with_2fa(&app, &email).await;
+
+    assert_eq!(codes.len(), 10);
+    let mut unique_codes = codes.clone();
+    unique_codes.sort();
+    unique_codes.dedup();
+    assert_eq!(unique_codes.len(), codes.len());
+}
+
+#[test_helpers::api_test]
+async fn signup_without_2fa_returns_no_recovery_codes() {
+    let response = app.post_signup(&serde_json::json!({
+        "email": get_random_email(),
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body.get("recoveryCodes").is_none());
+    assert_eq!(
+        serde_json::from_value::<AuthMessageResponse>(body).unwrap().message_body,
+        "User created successfully!"
+    );
+}
+
+#[test_helpers::api_test]
+async fn verify_2fa_accepts_each_recovery_code_once() {
+    let email = get_random_email();
+    let codes = signup_with_2fa(&app, &email).await;
+
+    assert_eq!(verify_with_recovery_code(&app, &email, &codes[0]).await, 200);
+    assert_eq!(verify_with_recovery_code(&app, &email, &codes[0]).await, 401);
+    assert_eq!(verify_with_recovery_code(&app, &email, &codes[1].to_uppercase()).await, 200);
+}
+
+#[test_helpers::api_test]
+async fn verify_2fa_rejects_unknown_recovery_code() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+
+    assert_eq!(verify_with_recovery_code(&app, &email, "aaaaa-bbbbb").await, 401);
+}
+
+#[test_helpers::api_test]
+async fn regenerate_recovery_codes_invalidates_old_codes() {
+    let email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &email).await;
+    assert_eq!(verify_with_recovery_code(&app, &email, &old_codes[0]).await, 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(new_codes.len(), 10);
+
+    assert_eq!(verify_with_recovery_code(&app, &email, &old_codes[1]).await, 401);
+    assert_eq!(verify_with_recovery_code(&app, &email, &new_codes[0]).await, 200);
+}
+
+#[test_helpers::api_test]
+async fn regenerate_recovery_codes_returns_400_without_2fa() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn regenerate_recovery_codes_returns_400_if_jwt_cookie_missing() {
+    let response = app.post_regenerate_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
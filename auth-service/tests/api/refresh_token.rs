@@ -1,101 +1,185 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use reqwest::Url;
+use auth_service::routes::{RecoveryCodesResponse, TwoFactorAuthResponse};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
 use test_helpers::api_test;
 use crate::helpers::{get_random_email, TestApp};
 
-#[api_test]
-async fn refresh_token_returns_200() {
-    let email = &get_random_email();
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
     let response = app.post_signup(&serde_json::json!({
         "email": email,
         "password": "password",
         "requires2FA": false
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
 
     let login_response = app.post_login(&serde_json::json!({
         "email": email,
         "password": "password",
-        "requires2FA": false
     })).await;
-
     assert_eq!(login_response.status().as_u16(), 200);
 
-    let cookie = login_response.cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
-        .expect("No token found");
-    let token = cookie.value();
+    cookie_value(&login_response, REFRESH_COOKIE_NAME)
+}
 
-    let response = app.post_refresh_token(&serde_json::json!({
-        "email": email,
-        "password": "password",
-        "token": token
-    })).await;
+fn cookie_value(response: &reqwest::Response, name: &str) -> String {
+    response.cookies()
+        .find(|c| c.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_string()
+}
 
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+}
+
+#[api_test]
+async fn refresh_token_returns_200() {
+    let email = &get_random_email();
+    signup_and_login(&app, email).await;
+
+    let response = app.post_refresh_token().await;
     assert_eq!(response.status().as_u16(), 200);
 
+    let access_token = cookie_value(&response, JWT_COOKIE_NAME);
+    let response = app.post_verify_token(&serde_json::json!({ "token": access_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn refresh_token_rotates_refresh_token() {
+    let email = &get_random_email();
+    let first = signup_and_login(&app, email).await;
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let second = cookie_value(&response, REFRESH_COOKIE_NAME);
+    assert_ne!(first, second);
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(second, cookie_value(&response, REFRESH_COOKIE_NAME));
+}
+
+#[api_test]
+async fn refresh_token_returns_400_if_cookie_missing() {
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 400);
 }
 
 #[api_test]
 async fn refresh_token_returns_401_on_invalid_token() {
+    set_refresh_cookie(&app, "invalid");
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn refresh_token_returns_401_after_logout() {
     let email = &get_random_email();
-    let response = app.post_signup(&serde_json::json!({
-        "email": email,
-        "password": "password",
-        "requires2FA": false
-    })).await;
-    assert_eq!(response.status().as_u16(), 201);
+    let refresh_token = signup_and_login(&app, email).await;
 
-    let login_response = app.post_login(&serde_json::json!({
+    let response = app.post_logout("").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn reusing_rotated_token_revokes_family() {
+    let email = &get_random_email();
+    let stolen = signup_and_login(&app, email).await;
+
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let current = cookie_value(&response, REFRESH_COOKIE_NAME);
+
+    set_refresh_cookie(&app, &stolen);
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The token issued by the legitimate rotation is revoked as well.
+    set_refresh_cookie(&app, &current);
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn reusing_rotated_token_leaves_other_logins() {
+    let email = &get_random_email();
+    let stolen = signup_and_login(&app, email).await;
+
+    let other_login = app.post_login(&serde_json::json!({
         "email": email,
         "password": "password",
-        "requires2FA": false
     })).await;
+    let other = cookie_value(&other_login, REFRESH_COOKIE_NAME);
 
-    assert_eq!(login_response.status().as_u16(), 200);
+    set_refresh_cookie(&app, &stolen);
+    assert_eq!(app.post_refresh_token().await.status().as_u16(), 200);
+    set_refresh_cookie(&app, &stolen);
+    assert_eq!(app.post_refresh_token().await.status().as_u16(), 401);
 
-    let cookie = login_response.cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
-        .expect("No token found");
-    let token = cookie.value();
+    set_refresh_cookie(&app, &other);
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
 
-    app.post_logout(&serde_json::json!({
-        "email": email,
-    })).await;
+#[api_test]
+async fn refresh_token_returns_401_after_password_change() {
+    let email = &get_random_email();
+    let refresh_token = signup_and_login(&app, email).await;
 
-    let response = app.post_refresh_token(&serde_json::json!({
-        "email": email,
-        "password": "password",
-        "token": token
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password",
+        "newPassword": "new_password",
     })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh_token().await;
 
     assert_eq!(response.status().as_u16(), 401);
 }
 
 #[api_test]
-async fn refresh_token_returns_401_on_invalid_email() {
+async fn refresh_token_is_issued_after_2fa() {
     let email = &get_random_email();
     let response = app.post_signup(&serde_json::json!({
         "email": email,
         "password": "password",
-        "requires2FA": false
+        "requires2FA": true
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    let recovery_codes = response.json::<RecoveryCodesResponse>().await.unwrap().recovery_codes;
+    app.verify_email(email).await;
 
-    let login_response = app.post_login(&serde_json::json!({
+    let response = app.post_login(&serde_json::json!({
         "email": email,
-        "password": "password"
+        "password": "password",
     })).await;
-    let cookie = login_response.cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
-        .expect("No token found");
-    let token = cookie.value();
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response.cookies().all(|c| c.name() != REFRESH_COOKIE_NAME));
+    let login_attempt_id = response.json::<TwoFactorAuthResponse>().await.unwrap().login_attempt_id;
 
-    let response = app.post_refresh_token(&serde_json::json!({
-        "email": get_random_email(),
-        "password": "password",
-        "token": token
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_codes[0],
     })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    cookie_value(&response, REFRESH_COOKIE_NAME);
 
-    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 200);
 }
-
@@ -0,0 +1,130 @@
+use reqwest::Url;
+use auth_service::routes::SessionsResponse;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+}
+
+// Logs in and returns the access and refresh tokens.
+async fn login(app: &TestApp, email: &str) -> (String, String) {
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie_value = |name: &str| response.cookies()
+        .find(|c| c.name() == name)
+        .unwrap_or_else(|| panic!("No {} cookie found", name))
+        .value()
+        .to_string();
+
+    (cookie_value(JWT_COOKIE_NAME), cookie_value(REFRESH_COOKIE_NAME))
+}
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response.json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+#[test_helpers::api_test]
+async fn should_list_every_session_and_mark_the_current_one() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+    login(&app, email).await;
+    login(&app, email).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+    for session in &sessions {
+        assert_eq!(session.ip_address.as_deref(), Some("127.0.0.1"));
+        assert!(!session.device_label.is_empty());
+    }
+}
+
+#[test_helpers::api_test]
+async fn revoking_a_session_invalidates_its_tokens() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+    let (first_token, first_refresh_token) = login(&app, email).await;
+    login(&app, email).await;
+
+    let first_session = get_sessions(&app).await.sessions
+        .into_iter()
+        .find(|session| !session.current)
+        .expect("No other session listed");
+
+    let response = app.delete_session(&first_session.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": first_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_COOKIE_NAME, first_refresh_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.post_refresh_token().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn revoking_the_current_session_removes_the_cookies() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+    let (token, _) = login(&app, email).await;
+
+    let current_session = get_sessions(&app).await.sessions.remove(0);
+
+    let response = app.delete_session(&current_session.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|c| c.name() == JWT_COOKIE_NAME && c.value().is_empty()));
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn should_return_404_for_another_users_session() {
+    let email = &get_random_email();
+    signup(&app, email).await;
+    login(&app, email).await;
+    let other_session = get_sessions(&app).await.sessions.remove(0);
+
+    let other_email = &get_random_email();
+    signup(&app, other_email).await;
+    login(&app, other_email).await;
+
+    let response = app.delete_session(&other_session.id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.delete_session("not-a-session-id").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_session("not-a-session-id").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -143,3 +143,30 @@ async fn should_return_409_if_email_already_exists() {
     );
     
 }
+
+#[test_helpers::api_test]
+async fn preferred_language_sets_email_language() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false,
+        "preferredLanguage": "es-MX"
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let sent = app.get_sent_email(&email, "Verifica tu correo de Live Bootcamp Auth").await;
+    assert!(sent.message.html.contains("lang=\"es\""));
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_unsupported_preferred_language() {
+    let response = app.post_signup(&serde_json::json!({
+        "email": get_random_email(),
+        "password": "password123",
+        "requires2FA": false,
+        "preferredLanguage": "klingon"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
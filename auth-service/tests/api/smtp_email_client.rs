@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+use secrecy::Secret;
+use auth_service::domain::{Email, EmailClient, EmailMessage};
+use auth_service::services::{SmtpEmailClient, SmtpSettings, SmtpTls};
+use crate::fake_smtp_server::FakeSmtpServer;
+use crate::helpers::get_random_email;
+
+fn settings_for(server: &FakeSmtpServer) -> SmtpSettings {
+    SmtpSettings {
+        host: server.address.ip().to_string(),
+        port: Some(server.address.port()),
+        tls: SmtpTls::None,
+        credentials: None,
+        sender: "Live Bootcamp Auth <no-reply@example.com>".to_string(),
+        timeout: Duration::from_secs(1),
+        max_connections: 2,
+    }
+}
+
+fn parse_email(email: &str) -> Email {
+    Email::parse(Secret::new(email.to_string())).expect("Failed to parse email")
+}
+
+fn message(subject: &str) -> EmailMessage {
+    EmailMessage {
+        subject: subject.to_string(),
+        text: "Your code is 123456".to_string(),
+        html: "<p>Your code is <strong>123456</strong></p>".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn sends_email_from_configured_sender() {
+    let server = FakeSmtpServer::start().await;
+    let client = SmtpEmailClient::new(&settings_for(&server))
+        .expect("Failed to create SMTP email client");
+    let recipient = get_random_email();
+
+    client.send_email(&parse_email(&recipient), &message("2 factor auth code"))
+        .await
+        .expect("Failed to send email");
+
+    let received = server.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].mail_from, "no-reply@example.com");
+    assert_eq!(received[0].rcpt_to, vec![recipient.clone()]);
+    assert!(received[0].data.contains("From: \"Live Bootcamp Auth\" <no-reply@example.com>"));
+    assert!(received[0].data.contains(&format!("To: {}", recipient)));
+    assert!(received[0].data.contains("Subject: 2 factor auth code"));
+    assert!(received[0].data.contains("Content-Type: multipart/alternative"));
+    assert!(received[0].data.contains("Your code is 123456"));
+    assert!(received[0].data.contains("<strong>123456</strong>"));
+}
+
+#[tokio::test]
+async fn reuses_connection_between_emails() {
+    let server = FakeSmtpServer::start().await;
+    let client = SmtpEmailClient::new(&settings_for(&server))
+        .expect("Failed to create SMTP email client");
+
+    for _ in 0..3 {
+        client.send_email(&parse_email(&get_random_email()), &message("Subject"))
+            .await
+            .expect("Failed to send email");
+        // Connections go back to the pool in the background.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(server.received().len(), 3);
+    assert_eq!(server.connections(), 1);
+}
+
+#[tokio::test]
+async fn times_out_when_server_does_not_respond() {
+    let server = FakeSmtpServer::start_unresponsive().await;
+    let client = SmtpEmailClient::new(&settings_for(&server))
+        .expect("Failed to create SMTP email client");
+
+    let started = Instant::now();
+    let result = client.send_email(&parse_email(&get_random_email()), &message("Subject")).await;
+
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn rejects_invalid_sender() {
+    let server = FakeSmtpServer::start().await;
+    let settings = SmtpSettings {
+        sender: "not an address".to_string(),
+        ..settings_for(&server)
+    };
+
+    assert!(SmtpEmailClient::new(&settings).is_err());
+}
@@ -0,0 +1,74 @@
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use uuid::Uuid;
+use auth_service::utils::constants::{env, prod, DATABASE_URL};
+use crate::helpers::{configure_postgresql, delete_database, get_random_email};
+
+/// The built binary, so the stores are picked from its environment like in production.
+/// It's killed when this is dropped, even if the test fails.
+struct RunningApp(Child);
+
+impl Drop for RunningApp {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[tokio::test]
+async fn should_start_without_redis_if_no_store_uses_it() {
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = configure_postgresql(db_name.clone()).await;
+
+    let mut app = RunningApp(
+        Command::new(env!("CARGO_BIN_EXE_auth-service"))
+            .env_remove(env::REDIS_HOST_NAME_ENV_VAR)
+            .env(env::DATABASE_URL_ENV_VAR, format!("{}/{}", *DATABASE_URL, db_name))
+            .env(env::BANNED_TOKEN_STORE_ENV_VAR, "postgres")
+            .env(env::TWO_FA_CODE_STORE_ENV_VAR, "postgres")
+            .env(env::LOGIN_ATTEMPT_STORE_ENV_VAR, "postgres")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start app"),
+    );
+    let address = format!("http://{}", prod::APP_ADDRESS.replace("0.0.0.0", "127.0.0.1"));
+    let http_client = reqwest::Client::new();
+
+    let mut started = false;
+    for _ in 0..60 {
+        if let Some(status) = app.0.try_wait().expect("Failed to check on app") {
+            panic!("App exited with {}", status);
+        }
+        if http_client.get(format!("{}/", address)).send().await.is_ok() {
+            started = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert!(started, "App didn't start listening");
+
+    // A failed login is counted in Postgres instead of Redis.
+    let email = get_random_email();
+    let response = http_client
+        .post(format!("{}/login", address))
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let failures: i32 = sqlx::query_scalar("SELECT failures FROM login_attempts WHERE attempt_key = $1")
+        .bind(format!("email:{}", email))
+        .fetch_one(&pg_pool)
+        .await
+        .expect("Failed login wasn't counted");
+    assert_eq!(failures, 1);
+
+    drop(app);
+    pg_pool.close().await;
+    delete_database(&db_name).await;
+}
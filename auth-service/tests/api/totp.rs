@@ -0,0 +1,238 @@
+use auth_service::routes::{RecoveryCodesResponse, TotpEnrollmentResponse, TwoFactorAuthResponse};
+use auth_service::utils::constants::MAX_TWO_FA_ATTEMPTS;
+use totp_rs::TOTP;
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    email
+}
+
+async fn enroll(app: &TestApp) -> TotpEnrollmentResponse {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<TotpEnrollmentResponse>()
+        .await
+        .expect("Could not deserialize response body to TotpEnrollmentResponse")
+}
+
+// Generates the code an authenticator app would show after scanning the URI
+fn current_code(enrollment: &TotpEnrollmentResponse) -> String {
+    TOTP::from_url(&enrollment.otpauth_uri)
+        .expect("Invalid otpauth URI")
+        .generate_current()
+        .unwrap()
+}
+
+// The code for the next time step, which is accepted as clock drift.
+// Confirming the app uses up the current one, and a code is only accepted once.
+fn next_code(enrollment: &TotpEnrollmentResponse) -> String {
+    let totp = TOTP::from_url(&enrollment.otpauth_uri).expect("Invalid otpauth URI");
+    totp.generate(totp.next_step_current().unwrap())
+}
+
+fn wrong_code(code: &str) -> String {
+    let code: u32 = code.parse().unwrap();
+    format!("{:06}", (code + 500_000) % 1_000_000)
+}
+
+#[test_helpers::api_test]
+async fn enroll_totp_returns_secret_and_otpauth_uri() {
+    let email = signup_and_login(&app).await;
+
+    let enrollment = enroll(&app).await;
+
+    assert!(!enrollment.secret.is_empty());
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment.otpauth_uri.contains(&format!("secret={}", enrollment.secret)));
+    assert!(enrollment.otpauth_uri.contains(&email.replace('@', "%40")));
+}
+
+#[test_helpers::api_test]
+async fn enroll_totp_returns_400_if_jwt_cookie_missing() {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn confirm_totp_returns_400_if_not_enrolled() {
+    signup_and_login(&app).await;
+
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": "123456",
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn confirm_totp_returns_401_if_incorrect_code() {
+    signup_and_login(&app).await;
+    let enrollment = enroll(&app).await;
+
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": wrong_code(&current_code(&enrollment)),
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn confirm_totp_returns_200_and_prevents_re_enrollment() {
+    signup_and_login(&app).await;
+    let enrollment = enroll(&app).await;
+
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": current_code(&enrollment),
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[test_helpers::api_test]
+async fn login_with_totp_is_verified_with_authenticator_code() {
+    let email = signup_and_login(&app).await;
+    let enrollment = enroll(&app).await;
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": current_code(&enrollment),
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code(&next_code(&enrollment)),
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": next_code(&enrollment),
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn totp_code_is_only_accepted_once() {
+    let email = signup_and_login(&app).await;
+    let enrollment = enroll(&app).await;
+    let code = current_code(&enrollment);
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": code,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login = || async {
+        let response = app.post_login(&serde_json::json!({
+            "email": email,
+            "password": "password",
+        })).await;
+        assert_eq!(response.status().as_u16(), 206);
+
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id
+    };
+
+    // The code that confirmed the app can't log in too.
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login().await,
+        "2FACode": code,
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let code = next_code(&enrollment);
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login().await,
+        "2FACode": code,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Replaying it, as someone who saw it would, fails even on a fresh login attempt.
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login().await,
+        "2FACode": code,
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn too_many_wrong_codes_require_logging_in_again() {
+    let email = signup_and_login(&app).await;
+    let enrollment = enroll(&app).await;
+    let response = app.post_confirm_totp(&serde_json::json!({
+        "2FACode": current_code(&enrollment),
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    for attempt in 1..=MAX_TWO_FA_ATTEMPTS {
+        let response = app.post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code(&current_code(&enrollment)),
+        })).await;
+        let expected_status = if attempt == MAX_TWO_FA_ATTEMPTS { 429 } else { 401 };
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+
+    // Even the right code can't complete the dropped login attempt.
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": current_code(&enrollment),
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
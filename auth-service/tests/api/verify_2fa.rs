@@ -1,7 +1,40 @@
+use reqwest::Url;
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::domain::{LoginAttemptId, TwoFACode};
 use auth_service::http_response::ErrorResponse;
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::routes::{RecoveryCodesResponse, TwoFactorAuthResponse};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, TWO_FA_PENDING_COOKIE_NAME};
+
+// Returns the user's recovery codes, which verify_2fa accepts in place of a code
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(email).await;
+
+    response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes
+}
+
+async fn login_with_2fa(app: &TestApp, email: &str) -> String {
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
 
 #[test_helpers::api_test]
 async fn verify_2fa_returns_200() {
@@ -13,6 +46,7 @@ async fn verify_2fa_returns_200() {
         "requires2FA": true
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
 
     let response = app.post_login(&serde_json::json!({
         "email": email,
@@ -29,7 +63,7 @@ async fn verify_2fa_returns_200() {
         .unwrap().1.split_once(';')
         .unwrap().0;
 
-    assert_eq!(!token.is_empty(), true);
+    assert!(!token.is_empty());
     
 }
 
@@ -54,6 +88,7 @@ async fn should_return_401_if_incorrect_credentials() {
         "requires2FA": true
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
 
     let response = app.post_login(&serde_json::json!({
         "email": email,
@@ -75,6 +110,7 @@ async fn should_return_401_if_old_code() {
         "requires2FA": true
     })).await;
     assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
 
     let login_response = app.post_login(&serde_json::json!({
         "email": email,
@@ -85,11 +121,12 @@ async fn should_return_401_if_old_code() {
     assert_eq!(login_response.status().as_u16(), 206);
 
     let cookie = login_response.cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .find(|c| c.name() == TWO_FA_PENDING_COOKIE_NAME)
         .expect("No token found");
     let token = cookie.value();
 
-    assert_eq!(!token.is_empty(), true);
+    assert!(!token.is_empty());
+    assert!(!login_response.cookies().any(|c| c.name() == JWT_COOKIE_NAME));
 
     let response = app.post_login(&serde_json::json!({
         "email": email,
@@ -151,4 +188,168 @@ async fn should_return_400_if_invalid_input() {
         );
     }
     
-}
\ No newline at end of file
+}
+
+#[test_helpers::api_test]
+async fn verify_2fa_exchanges_pending_cookie_for_auth_cookie() {
+    let email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_codes[0],
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.cookies()
+        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+    assert!(response.cookies().any(|c| c.name() == TWO_FA_PENDING_COOKIE_NAME && c.value().is_empty()));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn should_return_400_if_pending_cookie_missing() {
+    let email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=; Max-Age=0; Path=/", TWO_FA_PENDING_COOKIE_NAME),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_codes[0],
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_pending_cookie_belongs_to_another_user() {
+    let email = get_random_email();
+    let recovery_codes = signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    // Replaces the first user's pending cookie with the other user's.
+    let other_email = get_random_email();
+    signup_with_2fa(&app, &other_email).await;
+    login_with_2fa(&app, &other_email).await;
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": recovery_codes[0],
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn resend_2fa_is_limited_by_cooldown() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    // The code was just sent by the login.
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[test_helpers::api_test]
+async fn resend_2fa_should_return_401_for_wrong_login_attempt() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    login_with_2fa(&app, &email).await;
+
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": LoginAttemptId::default().as_ref(),
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn resend_2fa_should_return_400_if_pending_cookie_missing() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}=; Max-Age=0; Path=/", TWO_FA_PENDING_COOKIE_NAME),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn emailed_code_completes_login() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    let code = app.get_two_fa_code(&email).await;
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|c| c.name() == JWT_COOKIE_NAME && !c.value().is_empty()));
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn should_start_one_session_if_verified_twice_at_once() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let code = app.get_two_fa_code(&email).await;
+
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+    let (first, second) = tokio::join!(app.post_verify_2fa(&body), app.post_verify_2fa(&body));
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, [200, 401]);
+}
+
+#[test_helpers::api_test]
+async fn code_from_earlier_login_is_rejected() {
+    let email = get_random_email();
+    signup_with_2fa(&app, &email).await;
+    login_with_2fa(&app, &email).await;
+    let old_code = app.get_two_fa_code(&email).await;
+
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let new_code = app.get_two_fa_code(&email).await;
+    // Codes are random, so the second login could draw the same one.
+    if old_code != new_code {
+        let response = app.post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": old_code,
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
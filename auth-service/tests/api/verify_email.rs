@@ -0,0 +1,119 @@
+use auth_service::domain::EmailVerificationToken;
+use auth_service::http_response::ErrorResponse;
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_helpers::api_test]
+async fn verify_email_returns_200_and_allows_login() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app.get_email_verification_token(&email).await;
+    let response = app.post_verify_email(&serde_json::json!({
+        "token": token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password",
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_helpers::api_test]
+async fn verify_email_token_is_single_use() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app.get_email_verification_token(&email).await;
+    let response = app.post_verify_email(&serde_json::json!({
+        "token": token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_email(&serde_json::json!({
+        "token": token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_helpers::api_test]
+async fn verify_email_returns_401_if_unknown_token() {
+    let response = app.post_verify_email(&serde_json::json!({
+        "token": EmailVerificationToken::default().as_ref(),
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid token".to_owned()
+    );
+}
+
+#[test_helpers::api_test]
+async fn verify_email_returns_400_if_invalid_token() {
+    let response = app.post_verify_email(&serde_json::json!({
+        "token": "invalid_token",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_helpers::api_test]
+async fn resend_verification_returns_200_without_sending_during_cooldown() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let token = app.get_email_verification_token(&email).await;
+
+    let response = app.post_resend_verification(&serde_json::json!({
+        "email": email,
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.get_email_verification_token(&email).await, token);
+}
+
+#[test_helpers::api_test]
+async fn resend_verification_returns_200_for_verified_or_unknown_user() {
+    let email = get_random_email();
+    let response = app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    app.verify_email(&email).await;
+
+    for email in [email, get_random_email()] {
+        let response = app.post_resend_verification(&serde_json::json!({
+            "email": email,
+        })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[test_helpers::api_test]
+async fn resend_verification_returns_422_if_malformed_input() {
+    let response = app.post_resend_verification(&serde_json::json!({})).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
@@ -1,5 +1,8 @@
+use chrono::Utc;
+use uuid::Uuid;
 use auth_service::http_response::ErrorResponse;
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::auth::Claims;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, JWT_ISSUER, JWT_KEYRING};
 use crate::helpers::{get_random_email, TestApp};
 
 // Reminder todo:
@@ -15,6 +18,7 @@ async fn should_return_200_if_token_is_not_banned() {
         "password": "password",
         "requires2FA": false
     })).await;
+    app.verify_email(email).await;
 
     let login_response = app.post_login(&serde_json::json!({
         "email": email,
@@ -83,4 +87,25 @@ async fn should_return_422_if_malformed_input() {
 
     assert_eq!(response.status().as_u16(), 422);
     
-}
\ No newline at end of file
+}
+
+#[test_helpers::api_test]
+async fn should_return_401_if_token_minted_for_another_audience() {
+    let now = Utc::now().timestamp() as usize;
+    let claims = Claims {
+        sub: get_random_email(),
+        exp: now + 600,
+        iat: now,
+        nbf: now,
+        iss: JWT_ISSUER.to_string(),
+        aud: "another-service".to_string(),
+        jti: Uuid::new_v4().to_string(),
+        sid: None,
+        iat_ms: None,
+    };
+    let token = JWT_KEYRING.encode(&claims).expect("Failed to sign token");
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}